@@ -16,7 +16,9 @@ use crate::message::Message;
 use crate::settings::AppSettings;
 use crate::utils::bln::bln_content_creator;
 use crate::utils::handlers::*;
+use crate::utils::viewer::{FitMode, PageZoom, ZoomAction};
 use crate::utils::{panes::MainPanes, tabs::ImageTabs};
+use std::collections::HashMap;
 
 use widgets::footer::footer;
 use widgets::main_content::{main_content_pane_grid, Pane};
@@ -35,17 +37,45 @@ pub struct LeScan {
     pub t2_content: text_editor::Content,
     // Comments
     pub t3_content: text_editor::Content,
+    // Source text
+    pub t4_content: text_editor::Content,
 
     pub current_balloon: usize,
 
     pub current_img_tab: ImageTabs,
     pub img_scroller: scrollable::Id,
     pub img_scroller_current_scroll: scrollable::RelativeOffset,
+    // Pixel sizes of the document's pages, in the same order as the images
+    pub page_sizes: Vec<Option<(u32, u32)>>,
+    // Downscaled and tiled pages, filled in the background
+    pub cached_pages: Vec<Option<rsff::page_cache::CachedPage>>,
+    pub img_scroll_offset: scrollable::AbsoluteOffset,
+    pub img_viewport: iced::Size,
+    // Set when the scroller has to be put back to `img_scroller_current_scroll`
+    pub img_scroller_restore: bool,
+    // Set when the next scroll event comes from us, not the user
+    pub img_programmatic_scroll: bool,
+    // Select the balloons in view while scrolling the pages
+    pub follow_scroll: bool,
+    pub page_zoom: PageZoom,
+    // Zoom levels of the documents opened before, by file location
+    pub document_zooms: HashMap<String, PageZoom>,
 
     pub current_settings_tab: modals::settings::SettingsTabs,
 
     pub document_file_location: Option<String>,
 
+    // Panels of each page, detected for sorting the balloons
+    pub page_panels: Vec<Vec<rsff::img_data::Region>>,
+    // Custom balloon types being edited in the balloon types window
+    pub custom_types_draft: Vec<modals::balloon_types::CustomTypeDraft>,
+
+    // Show the pages with the translations typeset into the balloons
+    pub typeset_preview: bool,
+    pub typesetter: Option<std::sync::Arc<rsff::typeset::Typesetter>>,
+    // Tiles of the typeset pages, filled in the background
+    pub typeset_pages: Vec<Option<Vec<widget::image::Handle>>>,
+
     pub show_modal: Option<modals::ModalType>,
     pub modal_markdowns: modals::ModalMarkdowns,
 }
@@ -56,7 +86,8 @@ impl LeScan {
         tl_doc.add_balloon_empty();
         let current_balloon: usize = 0;
 
-        let (t1_content, t2_content, t3_content) = bln_content_creator(&tl_doc, current_balloon);
+        let (t1_content, t2_content, t3_content, t4_content) =
+            bln_content_creator(&tl_doc, current_balloon);
 
         let pane_config = pane_grid::Configuration::Split {
             axis: pane_grid::Axis::Horizontal,
@@ -84,6 +115,20 @@ impl LeScan {
             general_settings_file_path: settings.settings_file_path.clone(),
             app_theme: settings.app_theme.clone(),
             language: settings.language.clone(),
+            crop_format: settings.crop_format,
+            sffz_codec: settings.sffz_codec,
+            ocr_command: settings.ocr_command.clone(),
+            mt_endpoint: settings.mt_endpoint.clone(),
+            mt_api_key: settings.mt_api_key.clone().unwrap_or_default(),
+            mt_source_lang: settings.mt_source_lang.clone(),
+            mt_target_lang: settings.mt_target_lang.clone(),
+            typeset_font: settings.typeset_font.clone(),
+            docx_columns: settings.docx_columns.clone(),
+            sheet_columns: settings.sheet_columns.clone(),
+            detector: rsff::detect::DetectorParams::FIELDS
+                .iter()
+                .map(|f| settings.detector.value(f).unwrap_or_default())
+                .collect(),
         };
 
         if let Some(lang) = &settings.language {
@@ -97,24 +142,43 @@ impl LeScan {
                 settings_menu_contents,
                 panes,
 
-                selected_bln_type: Some(widgets::main_content::BlnTypes::Dialogue),
+                selected_bln_type: Some(widgets::main_content::BlnTypes(rsff::TYPES::DIALOGUE)),
                 t1_content,
                 t2_content,
                 t3_content,
+                t4_content,
 
                 current_balloon,
 
                 current_img_tab: ImageTabs::Document,
                 img_scroller: scrollable::Id::unique(),
                 img_scroller_current_scroll: scrollable::RelativeOffset::START,
+                page_sizes: Vec::new(),
+                cached_pages: Vec::new(),
+                img_scroll_offset: scrollable::AbsoluteOffset::default(),
+                img_viewport: iced::Size::ZERO,
+                img_scroller_restore: false,
+                img_programmatic_scroll: false,
+                follow_scroll: false,
+                page_zoom: PageZoom::default(),
+                document_zooms: cache.document_zooms,
 
                 current_settings_tab: modals::settings::SettingsTabs::General,
 
                 document_file_location: None,
+                page_panels: Vec::new(),
+                custom_types_draft: Vec::new(),
+                typeset_preview: false,
+                typesetter: None,
+                typeset_pages: Vec::new(),
+
                 show_modal: None,
                 modal_markdowns: modals::ModalMarkdowns::default(),
             },
-            widget::focus_next(),
+            Task::batch([
+                widget::focus_next(),
+                crate::utils::pages::prune_page_cache(),
+            ]),
         )
     }
 
@@ -136,6 +200,20 @@ impl LeScan {
                 (menu_sub_button_file_save())
                 (menu_sub_button_file_save_as())
             )))
+            (menu_main_button(t!("tools_menu.tools")), menu_tpl_1(menu_items!(
+                (menu_sub_button_tools_detect_balloons())
+                (menu_sub_button_tools_ocr_current())
+                (menu_sub_button_tools_ocr_all())
+                (menu_sub_button_tools_machine_translate())
+                (menu_sub_button_tools_sort_balloons())
+                (menu_sub_button_tools_export_typeset())
+                (menu_sub_button_tools_export_typeset_script())
+                (menu_sub_button_tools_export_xliff())
+                (menu_sub_button_tools_merge_xliff())
+                (menu_sub_button_tools_export_po())
+                (menu_sub_button_tools_merge_po())
+                (menu_sub_button_tools_balloon_types())
+            )))
             (menu_main_button(t!("app_menu.app")), menu_tpl_1(menu_items!(
                 (menu_sub_button_app_settings())
                 (menu_sub_button_about())
@@ -200,12 +278,19 @@ impl LeScan {
             }),
             iced::keyboard::on_key_press(|k, m| {
                 if let iced::keyboard::Key::Character(c) = k {
-                    if c == "v" && m.control() || m.command() {
+                    let ctrl = m.control() || m.command();
+                    if c == "v" && ctrl {
                         Some(Message::CurrentBlnImgPaste)
-                    } else if c == "s" && m.control() || m.command() {
+                    } else if c == "s" && ctrl {
                         Some(Message::FileOperation(
                             crate::message::FileOperation::SaveFileDialog,
                         ))
+                    } else if (c == "=" || c == "+") && ctrl {
+                        Some(Message::ImageZoom(ZoomAction::In))
+                    } else if c == "-" && ctrl {
+                        Some(Message::ImageZoom(ZoomAction::Out))
+                    } else if c == "0" && ctrl {
+                        Some(Message::ImageZoom(ZoomAction::Fit(FitMode::FitWidth)))
                     } else {
                         None
                     }
@@ -0,0 +1,106 @@
+use crate::message::Message;
+use iced::advanced::text::Shaping;
+use iced::alignment::Vertical;
+use iced::widget::{
+    button, column, container, horizontal_rule, horizontal_space, row, scrollable, text,
+    text_input, Column,
+};
+use iced::{Element, Length};
+use rsff::style::Color;
+use rust_i18n::t;
+
+/// A custom balloon type being edited.
+/// `original` is the name of the document's type it edits, `None` for new ones.
+#[derive(Debug, Clone, Default)]
+pub struct CustomTypeDraft {
+    pub original: Option<String>,
+    pub name: String,
+    pub prefix: String,
+    pub color: String,
+}
+
+impl From<&rsff::CustomType> for CustomTypeDraft {
+    fn from(t: &rsff::CustomType) -> Self {
+        Self {
+            original: Some(t.name.clone()),
+            name: t.name.clone(),
+            prefix: t.prefix.clone(),
+            color: t.color.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CustomTypeField {
+    Name(String),
+    Prefix(String),
+    Color(String),
+}
+
+pub fn balloon_types_modal(app: &crate::LeScan) -> Element<Message> {
+    let rows = Column::with_children(app.custom_types_draft.iter().enumerate().map(
+        |(i, draft)| -> Element<Message> {
+            let swatch = draft.color.parse::<Color>().ok().map(|c| {
+                container(horizontal_space())
+                    .width(20)
+                    .height(20)
+                    .style(move |_| container::Style {
+                        background: Some(iced::Color::from_rgb8(c.r, c.g, c.b).into()),
+                        ..container::Style::default()
+                    })
+            });
+
+            row![
+                text_input(t!("custom_types.name").as_ref(), &draft.name)
+                    .on_input(move |s| Message::CustomTypeEdited(i, CustomTypeField::Name(s)))
+                    .width(Length::Fill),
+                text_input(t!("custom_types.prefix").as_ref(), &draft.prefix)
+                    .on_input(move |s| Message::CustomTypeEdited(i, CustomTypeField::Prefix(s)))
+                    .width(80),
+                text_input(t!("custom_types.color").as_ref(), &draft.color)
+                    .on_input(move |s| Message::CustomTypeEdited(i, CustomTypeField::Color(s)))
+                    .width(90),
+            ]
+            .push_maybe(swatch)
+            .push(
+                button(text(t!("custom_types.remove")).shaping(Shaping::Advanced))
+                    .style(button::danger)
+                    .on_press(Message::CustomTypeRemoved(i)),
+            )
+            .spacing(5)
+            .align_y(Vertical::Center)
+            .into()
+        },
+    ))
+    .spacing(5);
+
+    container(
+        column![
+            text(t!("custom_types.title"))
+                .size(20)
+                .shaping(Shaping::Advanced),
+            text(t!("custom_types.description")).shaping(Shaping::Advanced),
+            horizontal_rule(10),
+            scrollable(rows).height(Length::Fill),
+            button(text(t!("custom_types.add")).shaping(Shaping::Advanced))
+                .on_press(Message::CustomTypeAdded),
+            horizontal_rule(10),
+            row![
+                horizontal_space().width(Length::Fill),
+                button(text(t!("custom_types.cancel")).shaping(Shaping::Advanced))
+                    .on_press(Message::HideModal),
+                button(text(t!("custom_types.apply")).shaping(Shaping::Advanced))
+                    .on_press(Message::ApplyCustomTypes),
+            ]
+            .spacing(3)
+            .align_y(Vertical::Center),
+        ]
+        .spacing(5)
+        .height(Length::Fill),
+    )
+    .width(600)
+    .height(450)
+    .padding(10)
+    .style(container::rounded_box)
+    .into()
+}
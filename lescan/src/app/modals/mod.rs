@@ -4,12 +4,16 @@ use iced::{Color, Element};
 use rust_i18n::t;
 
 pub mod about;
+pub mod balloon_types;
+pub mod reading_order;
 pub mod settings;
 
 #[derive(Debug, Clone)]
 pub enum ModalType {
     Settings,
     About,
+    ReadingOrder,
+    BalloonTypes,
 }
 
 pub fn modal_handler<'a>(
@@ -22,6 +26,8 @@ pub fn modal_handler<'a>(
     match modal_type {
         ModalType::Settings => modal(base, settings::settings_modal(app), on_blur),
         ModalType::About => modal(base, about::about_modal(app, on_link_click), on_blur),
+        ModalType::ReadingOrder => modal(base, reading_order::reading_order_modal(app), on_blur),
+        ModalType::BalloonTypes => modal(base, balloon_types::balloon_types_modal(app), on_blur),
     }
 }
 
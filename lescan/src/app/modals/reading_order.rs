@@ -0,0 +1,97 @@
+use crate::message::Message;
+use iced::advanced::text::Shaping;
+use iced::alignment::Vertical;
+use iced::widget::{
+    button, column, container, horizontal_rule, horizontal_space, pick_list, row, scrollable, text,
+    Column,
+};
+use iced::{Element, Length};
+use rsff::reading_order::ReadingDirection;
+use rust_i18n::t;
+
+// Longest balloon text shown in the preview list
+const PREVIEW_CHARS: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirectionItem(pub ReadingDirection);
+
+impl std::fmt::Display for DirectionItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self.0 {
+            ReadingDirection::RightToLeft => t!("reading_order.right_to_left"),
+            ReadingDirection::LeftToRight => t!("reading_order.left_to_right"),
+            ReadingDirection::TopToBottom => t!("reading_order.top_to_bottom"),
+        };
+        write!(f, "{}", text)
+    }
+}
+
+pub fn reading_order_modal(app: &crate::LeScan) -> Element<Message> {
+    let doc = &app.translation_document;
+    // Computed on every draw, balloons may change while the window is open
+    let order = doc.reading_order(&app.page_panels);
+
+    let direction_picker = pick_list(
+        ReadingDirection::ALL.map(DirectionItem),
+        Some(DirectionItem(doc.reading_direction)),
+        |d| Message::ReadingDirectionSelected(d.0),
+    );
+
+    let changed = order.iter().enumerate().filter(|(n, o)| n != *o).count();
+    let list: Element<Message> = if changed == 0 {
+        text(t!("reading_order.unchanged"))
+            .shaping(Shaping::Advanced)
+            .into()
+    } else {
+        scrollable(Column::with_children(order.iter().enumerate().map(
+            |(new, old)| -> Element<Message> {
+                let preview = doc
+                    .balloon_line(&doc.balloons[*old])
+                    .chars()
+                    .take(PREVIEW_CHARS)
+                    .collect::<String>();
+                let line = text(format!("{:>4} ← {:<4} {}", new + 1, old + 1, preview))
+                    .font(iced::Font::MONOSPACE)
+                    .shaping(Shaping::Advanced);
+                if new != *old {
+                    line.style(text::primary).into()
+                } else {
+                    line.into()
+                }
+            },
+        )))
+        .height(Length::Fill)
+        .into()
+    };
+
+    container(
+        column![
+            row![
+                text(t!("reading_order.direction")).shaping(Shaping::Advanced),
+                direction_picker,
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center),
+            text(t!("reading_order.changed", n = changed)).shaping(Shaping::Advanced),
+            horizontal_rule(10),
+            container(list).height(Length::Fill),
+            horizontal_rule(10),
+            row![
+                horizontal_space().width(Length::Fill),
+                button(text(t!("reading_order.cancel")).shaping(Shaping::Advanced))
+                    .on_press(Message::HideModal),
+                button(text(t!("reading_order.apply")).shaping(Shaping::Advanced))
+                    .on_press_maybe((changed != 0).then_some(Message::ApplyReadingOrder)),
+            ]
+            .spacing(3)
+            .align_y(Vertical::Center),
+        ]
+        .spacing(5)
+        .height(Length::Fill),
+    )
+    .width(600)
+    .height(450)
+    .padding(10)
+    .style(container::rounded_box)
+    .into()
+}
@@ -57,6 +57,164 @@ fn general_settings(app: &crate::LeScan) -> impl Into<Element<Message>> {
     .padding(5)
 }
 
+fn advanced_settings(app: &crate::LeScan) -> impl Into<Element<Message>> {
+    container(scrollable(
+        Column::new()
+            .push(input_with_header(
+                t!("settings.crop_format"),
+                pick_list(
+                    rsff::img_data::CropFormat::ALL,
+                    Some(app.settings_menu_contents.crop_format),
+                    |f| {
+                        Message::SettingsMenu(SettingsMenu::ContentChanged(
+                            SettingsMenuContentChanged::AdvancedSettingsCropFormat(f),
+                        ))
+                    },
+                ),
+            ))
+            .push(input_with_header(
+                t!("settings.sffz_codec"),
+                pick_list(
+                    rsff::codec::Codec::ALL,
+                    Some(app.settings_menu_contents.sffz_codec),
+                    |c| {
+                        Message::SettingsMenu(SettingsMenu::ContentChanged(
+                            SettingsMenuContentChanged::AdvancedSettingsSffzCodec(c),
+                        ))
+                    },
+                ),
+            ))
+            .push(input_with_header(
+                t!("settings.ocr_command.label"),
+                text_input(
+                    t!("settings.ocr_command.placeholder").as_ref(),
+                    &app.settings_menu_contents.ocr_command,
+                )
+                .on_input(|text| {
+                    Message::SettingsMenu(SettingsMenu::ContentChanged(
+                        SettingsMenuContentChanged::AdvancedSettingsOcrCommand(text),
+                    ))
+                }),
+            ))
+            .push(input_with_header(
+                t!("settings.mt_endpoint.label"),
+                text_input(
+                    t!("settings.mt_endpoint.placeholder").as_ref(),
+                    &app.settings_menu_contents.mt_endpoint,
+                )
+                .on_input(|text| {
+                    Message::SettingsMenu(SettingsMenu::ContentChanged(
+                        SettingsMenuContentChanged::AdvancedSettingsMtEndpoint(text),
+                    ))
+                }),
+            ))
+            .push(input_with_header(
+                t!("settings.mt_api_key.label"),
+                text_input(
+                    t!("settings.mt_api_key.placeholder").as_ref(),
+                    &app.settings_menu_contents.mt_api_key,
+                )
+                .secure(true)
+                .on_input(|text| {
+                    Message::SettingsMenu(SettingsMenu::ContentChanged(
+                        SettingsMenuContentChanged::AdvancedSettingsMtApiKey(text),
+                    ))
+                }),
+            ))
+            .push(
+                row![
+                    input_with_header(
+                        t!("settings.mt_source_lang"),
+                        text_input("ja", &app.settings_menu_contents.mt_source_lang).on_input(
+                            |text| {
+                                Message::SettingsMenu(SettingsMenu::ContentChanged(
+                                    SettingsMenuContentChanged::AdvancedSettingsMtSourceLang(text),
+                                ))
+                            }
+                        ),
+                    ),
+                    input_with_header(
+                        t!("settings.mt_target_lang"),
+                        text_input("en", &app.settings_menu_contents.mt_target_lang).on_input(
+                            |text| {
+                                Message::SettingsMenu(SettingsMenu::ContentChanged(
+                                    SettingsMenuContentChanged::AdvancedSettingsMtTargetLang(text),
+                                ))
+                            }
+                        ),
+                    ),
+                ]
+                .spacing(5),
+            )
+            .push(input_with_header(
+                t!("settings.typeset_font.label"),
+                text_input(
+                    t!("settings.typeset_font.placeholder").as_ref(),
+                    &app.settings_menu_contents.typeset_font,
+                )
+                .on_input(|text| {
+                    Message::SettingsMenu(SettingsMenu::ContentChanged(
+                        SettingsMenuContentChanged::AdvancedSettingsTypesetFont(text),
+                    ))
+                }),
+            ))
+            .push(input_with_header(
+                t!("settings.docx_columns.label"),
+                text_input(
+                    t!("settings.docx_columns.placeholder").as_ref(),
+                    &app.settings_menu_contents.docx_columns,
+                )
+                .on_input(|text| {
+                    Message::SettingsMenu(SettingsMenu::ContentChanged(
+                        SettingsMenuContentChanged::AdvancedSettingsDocxColumns(text),
+                    ))
+                }),
+            ))
+            .push(input_with_header(
+                t!("settings.sheet_columns.label"),
+                text_input(
+                    t!("settings.sheet_columns.placeholder").as_ref(),
+                    &app.settings_menu_contents.sheet_columns,
+                )
+                .on_input(|text| {
+                    Message::SettingsMenu(SettingsMenu::ContentChanged(
+                        SettingsMenuContentChanged::AdvancedSettingsSheetColumns(text),
+                    ))
+                }),
+            ))
+            .push(
+                container(text(t!("settings.detector.title")).shaping(Shaping::Advanced))
+                    .padding(5),
+            )
+            .extend(
+                rsff::detect::DetectorParams::FIELDS
+                    .chunks(3)
+                    .enumerate()
+                    .map(|(row_idx, fields)| {
+                        row(fields.iter().enumerate().map(|(i, field)| {
+                            let idx = row_idx * 3 + i;
+                            input_with_header(
+                                detector_label(field),
+                                text_input("", &app.settings_menu_contents.detector[idx]).on_input(
+                                    move |text| {
+                                        Message::SettingsMenu(SettingsMenu::ContentChanged(
+                                            SettingsMenuContentChanged::AdvancedSettingsDetector(
+                                                idx, text,
+                                            ),
+                                        ))
+                                    },
+                                ),
+                            )
+                        }))
+                        .spacing(5)
+                        .into()
+                    }),
+            )
+            .spacing(5),
+    ))
+    .padding(5)
+}
+
 pub fn settings_modal(app: &crate::LeScan) -> Element<Message> {
     let settings_sidebar_with_content = SidebarWithContent::new(|t_id| {
         Message::SettingsMenu(SettingsMenu::SettingsTabSelected(t_id))
@@ -69,7 +227,7 @@ pub fn settings_modal(app: &crate::LeScan) -> Element<Message> {
     .push(
         SettingsTabs::Advanced,
         TabLabel::Text(t!("settings.buttons.advanced").into_owned()),
-        column![],
+        advanced_settings(app),
     )
     .tab_label_padding(5)
     .tab_label_spacing(5)
@@ -106,6 +264,19 @@ pub struct SettingsMenuContents {
     pub general_settings_file_path: String,
     pub app_theme: iced::Theme,
     pub language: Option<String>,
+    pub crop_format: rsff::img_data::CropFormat,
+    pub sffz_codec: rsff::codec::Codec,
+    pub ocr_command: String,
+    pub mt_endpoint: String,
+    /// Empty for providers without keys.
+    pub mt_api_key: String,
+    pub mt_source_lang: String,
+    pub mt_target_lang: String,
+    pub typeset_font: String,
+    pub docx_columns: String,
+    pub sheet_columns: String,
+    /// Detector parameters as text, in `DetectorParams::FIELDS` order.
+    pub detector: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -114,6 +285,22 @@ pub enum SettingsTabs {
     Advanced,
 }
 
+fn detector_label(field: &str) -> String {
+    match field {
+        "white_threshold" => t!("settings.detector.white_threshold"),
+        "min_area" => t!("settings.detector.min_area"),
+        "max_area_ratio" => t!("settings.detector.max_area_ratio"),
+        "min_fill_ratio" => t!("settings.detector.min_fill_ratio"),
+        "min_text_ratio" => t!("settings.detector.min_text_ratio"),
+        "max_text_ratio" => t!("settings.detector.max_text_ratio"),
+        "max_aspect_ratio" => t!("settings.detector.max_aspect_ratio"),
+        "padding" => t!("settings.detector.padding"),
+        "min_panel_size" => t!("settings.detector.min_panel_size"),
+        _ => field.into(),
+    }
+    .into_owned()
+}
+
 fn input_with_header<'a>(
     header_text: impl AsRef<str> + 'a,
     element: impl Into<Element<'a, Message>>,
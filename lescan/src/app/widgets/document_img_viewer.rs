@@ -1,29 +1,164 @@
-use iced::widget::{image, scrollable, text, Column};
-use iced::{Element, Length, Renderer, Theme};
+use iced::widget::{
+    button, canvas, checkbox, column, image, responsive, row, scrollable, stack, text, Column,
+    Space,
+};
+use iced::{Alignment, Element, Length, Renderer, Theme};
 
+use super::page_overlay::PageOverlay;
+use crate::utils::viewer::{page_layout, FitMode, ZoomAction};
 use crate::{message::Message, LeScan};
 
 use rust_i18n::t;
 
-// Ask discord about Message::ImageScrolled firing
-// after creation so when panes change, scroll state
-// returns to RelativeOffset::START
-
 pub fn generate_image_viewer(app: &LeScan) -> Element<'_, Message, Theme, Renderer> {
     if let Some(img_paths) = &app.translation_document.images {
-        let img_paths_to_img_widget_iter = img_paths.iter().map(|path_string| {
-            let img_handle = image::Handle::from_path(path_string);
-            image::Image::new(img_handle).width(Length::Fill).into()
+        let pages = responsive(move |viewport| {
+            let (content_width, layout) = page_layout(&app.page_sizes, &app.page_zoom, viewport);
+
+            // Only pages near the visible area get real image widgets,
+            // the rest are kept as empty space of the same size.
+            let visible_from = app.img_scroll_offset.y - viewport.height;
+            let visible_to = app.img_scroll_offset.y + viewport.height * 2.0;
+
+            let img_paths_to_img_widget_iter =
+                img_paths
+                    .iter()
+                    .zip(layout)
+                    .enumerate()
+                    .map(|(page, (path_string, rect))| {
+                        let page_size = app.page_sizes.get(page).copied().flatten();
+                        if page_size.is_some()
+                            && (rect.y + rect.height < visible_from || rect.y > visible_to)
+                        {
+                            return Space::new(rect.width, rect.height).into();
+                        }
+
+                        page_view(app, page, path_string, page_size, rect.width)
+                    });
+
+            scrollable(
+                Column::from_iter(img_paths_to_img_widget_iter)
+                    .width(content_width)
+                    .align_x(Alignment::Center),
+            )
+            .direction(scrollable::Direction::Both {
+                vertical: scrollable::Scrollbar::default(),
+                horizontal: scrollable::Scrollbar::default(),
+            })
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .id(app.img_scroller.clone())
+            .on_scroll(Message::ImageScrolled)
+            .into()
         });
-        let img_scroller =
-            scrollable(Column::from_iter(img_paths_to_img_widget_iter).width(Length::Fill))
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .id(app.img_scroller.clone())
-                .on_scroll(Message::ImageScrolled);
-
-        img_scroller.into()
+
+        column![zoom_toolbar(app), pages].spacing(5).into()
     } else {
         text!("{}", t!("imgtabbar.document_no_img")).into()
     }
 }
+
+fn page_view<'a>(
+    app: &'a LeScan,
+    page: usize,
+    path_string: &'a str,
+    page_size: Option<(u32, u32)>,
+    width: f32,
+) -> Element<'a, Message> {
+    let typeset = app
+        .typeset_pages
+        .get(page)
+        .and_then(|t| t.as_ref())
+        .filter(|_| app.typeset_preview);
+
+    // Prefer the tiled cache, the original may be too big for a single texture
+    let img: Element<'a, Message> = if let Some(tiles) = typeset {
+        Column::from_iter(
+            tiles
+                .iter()
+                .map(|tile| image::Image::new(tile.clone()).width(width).into()),
+        )
+        .width(width)
+        .into()
+    } else if let Some(Some(cached)) = app.cached_pages.get(page) {
+        Column::from_iter(cached.tiles.iter().map(|tile| {
+            image::Image::new(image::Handle::from_path(tile))
+                .width(width)
+                .into()
+        }))
+        .width(width)
+        .into()
+    } else {
+        image::Image::new(image::Handle::from_path(path_string))
+            .width(width)
+            .into()
+    };
+
+    let Some(page_size) = page_size else {
+        return img;
+    };
+
+    let overlay = PageOverlay {
+        page,
+        page_size,
+        markers: app
+            .translation_document
+            .balloons_on_page(page)
+            .filter_map(|(i, b)| b.region.map(|r| (i, r, type_color(app, &b.btype))))
+            .collect(),
+        selected: Some(app.current_balloon),
+    };
+
+    stack![
+        img,
+        canvas(overlay).width(Length::Fill).height(Length::Fill)
+    ]
+    .width(width)
+    .into()
+}
+
+// Marker color of a custom balloon type
+fn type_color(app: &LeScan, btype: &rsff::TYPES) -> Option<iced::Color> {
+    let rsff::TYPES::CUSTOM(name) = btype else {
+        return None;
+    };
+    let c = app.translation_document.custom_type(name)?.color;
+    Some(iced::Color::from_rgb8(c.r, c.g, c.b))
+}
+
+fn zoom_toolbar(app: &LeScan) -> Element<'_, Message> {
+    let fit_button = |label, fit| {
+        button(text(label).size(14))
+            .padding([2, 6])
+            .style(if app.page_zoom.fit == fit {
+                button::primary
+            } else {
+                button::secondary
+            })
+            .on_press(Message::ImageZoom(ZoomAction::Fit(fit)))
+    };
+
+    row![
+        button(text("-").size(14))
+            .padding([2, 8])
+            .on_press(Message::ImageZoom(ZoomAction::Out)),
+        text!("{:.0}%", app.page_zoom.zoom * 100.0).size(14),
+        button(text("+").size(14))
+            .padding([2, 8])
+            .on_press(Message::ImageZoom(ZoomAction::In)),
+        fit_button(t!("imgtabbar.fit_width"), FitMode::FitWidth),
+        fit_button(t!("imgtabbar.fit_height"), FitMode::FitHeight),
+        fit_button(t!("imgtabbar.actual_size"), FitMode::ActualSize),
+        checkbox(t!("imgtabbar.follow_scroll"), app.follow_scroll)
+            .on_toggle(Message::FollowScrollToggled)
+            .size(14)
+            .text_size(14),
+        checkbox(t!("imgtabbar.typeset_preview"), app.typeset_preview)
+            .on_toggle(Message::TypesetPreviewToggled)
+            .size(14)
+            .text_size(14),
+    ]
+    .spacing(5)
+    .align_y(Alignment::Center)
+    .into()
+}
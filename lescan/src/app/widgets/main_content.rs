@@ -1,44 +1,162 @@
 use super::document_img_viewer::generate_image_viewer;
 use crate::message::Message;
 use crate::utils::handlers::editor_kp_bindings;
+use crate::utils::markup_highlight::{markup_format, MarkupHighlighter};
 use crate::utils::{panes::MainPanes, tabs::ImageTabs};
-use iced::widget::{column, container, pane_grid, pick_list, text, text_editor};
+use iced::widget::{
+    button, checkbox, column, container, pane_grid, pick_list, row, text, text_editor, text_input,
+};
 use iced::{Fill, Length, Theme};
 use iced_aw::SelectionList;
+use rsff::sfx::{Sfx, SfxTreatment};
+use rsff::{BalloonStatus, TYPES};
 use rust_i18n::t;
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
-pub enum BlnTypes {
-    Dialogue,
-    Square,
-    Thinking,
-    ST,
-    OT,
+/// A balloon type in the type picker, built-in types are shown localized.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct BlnTypes(pub TYPES);
+
+impl std::fmt::Display for BlnTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match &self.0 {
+            TYPES::DIALOGUE => t!("btype.dialogue"),
+            TYPES::SQUARE => t!("btype.square"),
+            TYPES::THINKING => t!("btype.thinking"),
+            TYPES::ST => t!("btype.st"),
+            TYPES::OT => t!("btype.ot"),
+            TYPES::CUSTOM(name) => name.into(),
+        };
+        write!(f, "{}", text)
+    }
 }
 
-impl BlnTypes {
-    pub const ALL: [Self; 5] = [
-        BlnTypes::Dialogue,
-        BlnTypes::Square,
-        BlnTypes::Thinking,
-        BlnTypes::ST,
-        BlnTypes::OT,
-    ];
+#[derive(Debug, Clone)]
+pub enum SfxField {
+    Original(String),
+    Romanization(String),
+    Translation(String),
+    Treatment(SfxTreatment),
 }
 
-impl std::fmt::Display for BlnTypes {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreatmentItem(pub SfxTreatment);
+
+impl std::fmt::Display for TreatmentItem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let text = match self {
-            BlnTypes::Dialogue => t!("btype.dialogue"),
-            BlnTypes::Square => t!("btype.square"),
-            BlnTypes::Thinking => t!("btype.thinking"),
-            BlnTypes::ST => t!("btype.st"),
-            BlnTypes::OT => t!("btype.ot"),
+        let text = match self.0 {
+            SfxTreatment::Replace => t!("text_editors.sfx.replace"),
+            SfxTreatment::Subtitle => t!("text_editors.sfx.subtitle"),
         };
         write!(f, "{}", text)
     }
 }
 
+// Editor layout of sound effects: one line for each field instead of the text editors
+fn sfx_form(sfx: &Sfx) -> iced::Element<'_, Message> {
+    let field = |placeholder, value, to_field: fn(String) -> SfxField| {
+        text_input(placeholder, value)
+            .on_input(move |s| Message::SfxEdited(to_field(s)))
+            .on_submit(Message::EnterPressed)
+            .padding(10)
+    };
+
+    column![
+        field(
+            &t!("text_editors.sfx.original"),
+            &sfx.original,
+            SfxField::Original
+        ),
+        field(
+            &t!("text_editors.sfx.romanization"),
+            &sfx.romanization,
+            SfxField::Romanization
+        ),
+        field(
+            &t!("text_editors.sfx.translation"),
+            &sfx.translation,
+            SfxField::Translation
+        ),
+        pick_list(
+            SfxTreatment::ALL.map(TreatmentItem),
+            Some(TreatmentItem(sfx.treatment)),
+            |t| Message::SfxEdited(SfxField::Treatment(t.0)),
+        )
+        .width(Length::Fill)
+        .padding(5),
+    ]
+    .spacing(3)
+    .into()
+}
+
+/// An item of the style preset picker. `name` is `None` for the balloon type's preset.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PresetItem {
+    pub name: Option<String>,
+    // Preset name shown for the "by type" item
+    type_preset: String,
+}
+
+impl std::fmt::Display for PresetItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match &self.name {
+            Some(name) => t!("text_editors.preset", name = name),
+            None => t!("text_editors.preset_by_type", name = self.type_preset),
+        };
+        write!(f, "{}", text)
+    }
+}
+
+fn style_row(app: &crate::app::LeScan) -> iced::Element<'_, Message> {
+    let doc = &app.translation_document;
+    let balloon = &doc.balloons[app.current_balloon];
+    let type_preset = doc
+        .style_presets
+        .iter()
+        .find(|p| p.types.contains(&balloon.btype))
+        .map(|p| p.name.clone())
+        .unwrap_or_default();
+    let item = |name: Option<String>| PresetItem {
+        name,
+        type_preset: type_preset.clone(),
+    };
+
+    let presets = std::iter::once(item(None))
+        .chain(doc.style_presets.iter().map(|p| item(Some(p.name.clone()))))
+        .collect::<Vec<_>>();
+    let style = doc.balloon_style(balloon);
+    let toggle = |label, on: Option<bool>, msg| {
+        button(text(label))
+            .padding([2, 8])
+            .style(if on.unwrap_or(false) {
+                button::primary
+            } else {
+                button::secondary
+            })
+            .on_press(msg)
+    };
+
+    row![
+        pick_list(presets, Some(item(balloon.preset.clone())), |p| {
+            Message::BalloonPresetSelected(p.name)
+        })
+        .width(Length::Fill)
+        .padding(5),
+        toggle(
+            t!("text_editors.bold"),
+            style.bold,
+            Message::BalloonBoldToggled
+        ),
+        toggle(
+            t!("text_editors.italic"),
+            style.italic,
+            Message::BalloonItalicToggled
+        ),
+    ]
+    .spacing(3)
+    .align_y(iced::alignment::Vertical::Center)
+    .into()
+}
+
 pub fn main_content_pane_grid(app: &crate::app::LeScan) -> pane_grid::PaneGrid<Message> {
     pane_grid::PaneGrid::new(&app.panes, move |_id, pane, _is_max| {
         let title_bar = pane_grid::TitleBar::new(
@@ -108,31 +226,46 @@ pub fn main_content_pane_grid(app: &crate::app::LeScan) -> pane_grid::PaneGrid<M
             }
             MainPanes::Editor => {
                 let bln_type_picker = pick_list(
-                    [
-                        BlnTypes::Dialogue,
-                        BlnTypes::Square,
-                        BlnTypes::Thinking,
-                        BlnTypes::OT,
-                        BlnTypes::ST,
-                    ],
-                    app.selected_bln_type,
+                    app.translation_document
+                        .balloon_types()
+                        .into_iter()
+                        .map(BlnTypes)
+                        .collect::<Vec<_>>(),
+                    app.selected_bln_type.clone(),
                     Message::BlnTypeSelected,
                 )
                 .width(Length::Fill)
                 .padding(5);
+                let balloon = &app.translation_document.balloons[app.current_balloon];
+                let type_row = row![
+                    bln_type_picker,
+                    checkbox(t!("text_editors.sfx.toggle"), balloon.sfx.is_some())
+                        .on_toggle(Message::SfxToggled),
+                ]
+                .spacing(5)
+                .align_y(iced::alignment::Vertical::Center);
+                let editor_4 = text_editor(&app.t4_content)
+                    .placeholder(t!("text_editors.source_editor_placeholder"))
+                    .on_action(Message::T4ContentChanged)
+                    .height(100)
+                    .padding(10)
+                    .key_binding(editor_kp_bindings);
+
                 let editor_1 = text_editor(&app.t1_content)
                     .placeholder(t!("text_editors.translation_editor_placeholder"))
                     .on_action(Message::T1ContentChanged)
                     .height(100)
                     .padding(10)
-                    .key_binding(editor_kp_bindings);
+                    .key_binding(editor_kp_bindings)
+                    .highlight_with::<MarkupHighlighter>((), markup_format);
 
                 let editor_2 = text_editor(&app.t2_content)
                     .placeholder(t!("text_editors.proofread_editor_placeholder"))
                     .on_action(Message::T2ContentChanged)
                     .height(100)
                     .padding(10)
-                    .key_binding(editor_kp_bindings);
+                    .key_binding(editor_kp_bindings)
+                    .highlight_with::<MarkupHighlighter>((), markup_format);
 
                 let editor_3 = text_editor(&app.t3_content)
                     .placeholder(t!("text_editors.comment_editor_placeholder"))
@@ -140,8 +273,46 @@ pub fn main_content_pane_grid(app: &crate::app::LeScan) -> pane_grid::PaneGrid<M
                     .height(100)
                     .padding(10)
                     .key_binding(editor_kp_bindings);
-                container(column![bln_type_picker, editor_1, editor_2, editor_3].spacing(3))
-                    .center(Length::Fill)
+                let draft_notice = (app.translation_document.balloons[app.current_balloon].status
+                    == BalloonStatus::MachineDraft)
+                    .then(|| {
+                        row![
+                            text(t!("text_editors.machine_draft"))
+                                .style(text::danger)
+                                .width(Length::Fill),
+                            button(text(t!("text_editors.accept_draft")))
+                                .on_press(Message::AcceptMachineDraft)
+                                .padding([2, 8]),
+                        ]
+                        .align_y(iced::alignment::Vertical::Center)
+                        .padding([0, 5])
+                    });
+                let markup_errors = [&app.t1_content, &app.t2_content]
+                    .into_iter()
+                    .flat_map(|c| {
+                        c.text()
+                            .lines()
+                            .flat_map(rsff::markup::validate)
+                            .collect::<Vec<_>>()
+                    })
+                    .next()
+                    .map(|e| {
+                        text(t!("text_editors.markup_error", e = e))
+                            .style(text::danger)
+                            .shaping(iced::advanced::text::Shaping::Advanced)
+                    });
+                let content = if let Some(sfx) = &balloon.sfx {
+                    column![type_row, style_row(app), sfx_form(sfx), editor_3].spacing(3)
+                } else {
+                    column![type_row, style_row(app), editor_4]
+                        .push_maybe(draft_notice)
+                        .push(editor_1)
+                        .push(editor_2)
+                        .push_maybe(markup_errors)
+                        .push(editor_3)
+                        .spacing(3)
+                };
+                container(content).center(Length::Fill)
             }
             MainPanes::Table => {
                 let table = SelectionList::new_with(
@@ -1,4 +1,5 @@
 pub mod document_img_viewer;
 pub mod footer;
 pub mod main_content;
+pub mod page_overlay;
 pub mod top_menu;
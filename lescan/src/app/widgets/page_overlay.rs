@@ -0,0 +1,243 @@
+use iced::widget::canvas::{self, event, Event, Frame, Geometry, Path, Stroke, Text};
+use iced::{keyboard, mouse};
+use iced::{Color, Point, Rectangle, Renderer, Size, Theme};
+use rsff::img_data::Region;
+
+use crate::message::Message;
+use crate::utils::viewer::ZoomAction;
+
+// Drags smaller than this (in page pixels) are treated as misclicks.
+const MIN_REGION_SIZE: u32 = 8;
+const LABEL_SIZE: f32 = 14.0;
+
+/// Draws balloon markers on top of a page and turns clicks/drags into messages.
+///
+/// Dragging creates a balloon region, dragging with Shift held crops the
+/// selection into the current balloon's image.
+pub struct PageOverlay {
+    pub page: usize,
+    pub page_size: (u32, u32),
+    // Balloon index, region and the color of its type if it has one
+    pub markers: Vec<(usize, Region, Option<Color>)>,
+    pub selected: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct OverlayState {
+    drag_start: Option<Point>,
+    drag_end: Option<Point>,
+    is_cropping: bool,
+    // Last cursor position while panning with the middle mouse button
+    pan_from: Option<Point>,
+    modifiers: keyboard::Modifiers,
+}
+
+impl PageOverlay {
+    fn to_page(&self, p: Point, bounds: Rectangle) -> (u32, u32) {
+        let x = p.x * self.page_size.0 as f32 / bounds.width;
+        let y = p.y * self.page_size.1 as f32 / bounds.height;
+        (
+            (x.max(0.0) as u32).min(self.page_size.0),
+            (y.max(0.0) as u32).min(self.page_size.1),
+        )
+    }
+
+    fn to_widget(&self, r: &Region, bounds: Rectangle) -> Rectangle {
+        let sx = bounds.width / self.page_size.0 as f32;
+        let sy = bounds.height / self.page_size.1 as f32;
+        Rectangle {
+            x: r.x as f32 * sx,
+            y: r.y as f32 * sy,
+            width: r.width as f32 * sx,
+            height: r.height as f32 * sy,
+        }
+    }
+
+    fn marker_at(&self, p: Point, bounds: Rectangle) -> Option<usize> {
+        let (x, y) = self.to_page(p, bounds);
+        self.markers
+            .iter()
+            .rev()
+            .find(|(_, r, _)| r.contains(x, y))
+            .map(|(i, _, _)| *i)
+    }
+}
+
+impl canvas::Program<Message> for PageOverlay {
+    type State = OverlayState;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let pos = cursor.position_in(bounds);
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let Some(pos) = pos else {
+                    return (event::Status::Ignored, None);
+                };
+                if let Some(i) = self
+                    .marker_at(pos, bounds)
+                    .filter(|_| !state.modifiers.shift())
+                {
+                    return (event::Status::Captured, Some(Message::BalloonSelected(i)));
+                }
+                state.drag_start = Some(pos);
+                state.drag_end = Some(pos);
+                state.is_cropping = state.modifiers.shift();
+                (event::Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) if state.drag_start.is_some() => {
+                // Keep the rubber band inside the page even if the cursor leaves it
+                if let Some(p) = cursor.position() {
+                    state.drag_end = Some(Point::new(
+                        (p.x - bounds.x).clamp(0.0, bounds.width),
+                        (p.y - bounds.y).clamp(0.0, bounds.height),
+                    ));
+                }
+                (event::Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let (Some(start), Some(end)) = (state.drag_start.take(), state.drag_end.take())
+                else {
+                    return (event::Status::Ignored, None);
+                };
+                let region =
+                    Region::from_corners(self.to_page(start, bounds), self.to_page(end, bounds));
+                if region.width < MIN_REGION_SIZE || region.height < MIN_REGION_SIZE {
+                    return (event::Status::Captured, None);
+                }
+                let msg = if state.is_cropping {
+                    Message::PageRegionCropped(self.page, region)
+                } else {
+                    Message::BalloonRegionDrawn(self.page, region)
+                };
+                (event::Status::Captured, Some(msg))
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta })
+                if pos.is_some() && state.modifiers.command() =>
+            {
+                let y = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. } => y,
+                };
+                let action = if y > 0.0 {
+                    ZoomAction::In
+                } else {
+                    ZoomAction::Out
+                };
+                (event::Status::Captured, Some(Message::ImageZoom(action)))
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Middle)) if pos.is_some() => {
+                state.pan_from = cursor.position();
+                (event::Status::Captured, None)
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) if state.pan_from.is_some() => {
+                let from = state.pan_from.replace(position).unwrap_or(position);
+                (
+                    event::Status::Captured,
+                    Some(Message::ImagePanned(position - from)),
+                )
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Middle)) => {
+                state.pan_from = None;
+                (event::Status::Ignored, None)
+            }
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = modifiers;
+                (event::Status::Ignored, None)
+            }
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let ep = theme.extended_palette();
+        let mut frame = Frame::new(renderer, bounds.size());
+
+        for (i, region, type_color) in &self.markers {
+            let rect = self.to_widget(region, bounds);
+            let is_selected = self.selected == Some(*i);
+            let color = if is_selected {
+                ep.primary.strong.color
+            } else {
+                type_color.unwrap_or(ep.secondary.strong.color)
+            };
+
+            if is_selected {
+                frame.fill_rectangle(rect.position(), rect.size(), Color { a: 0.2, ..color });
+            }
+            frame.stroke(
+                &Path::rectangle(rect.position(), rect.size()),
+                Stroke::default().with_color(color).with_width(2.0),
+            );
+
+            let label = (i + 1).to_string();
+            let label_size = Size::new(
+                LABEL_SIZE * 0.6 * label.len() as f32 + 8.0,
+                LABEL_SIZE + 4.0,
+            );
+            frame.fill_rectangle(rect.position(), label_size, color);
+            frame.fill_text(Text {
+                content: label,
+                position: Point::new(rect.x + 4.0, rect.y + 2.0),
+                color: ep.primary.strong.text,
+                size: LABEL_SIZE.into(),
+                ..Text::default()
+            });
+        }
+
+        if let (Some(start), Some(end)) = (state.drag_start, state.drag_end) {
+            let band_color = if state.is_cropping {
+                ep.success.base.color
+            } else {
+                ep.primary.base.color
+            };
+            let top_left = Point::new(start.x.min(end.x), start.y.min(end.y));
+            let size = Size::new((start.x - end.x).abs(), (start.y - end.y).abs());
+            frame.fill_rectangle(
+                top_left,
+                size,
+                Color {
+                    a: 0.15,
+                    ..band_color
+                },
+            );
+            frame.stroke(
+                &Path::rectangle(top_left, size),
+                Stroke::default().with_color(band_color).with_width(1.0),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if state.drag_start.is_some() {
+            return mouse::Interaction::Crosshair;
+        }
+        if state.pan_from.is_some() {
+            return mouse::Interaction::Grabbing;
+        }
+        match cursor.position_in(bounds) {
+            Some(p) if self.marker_at(p, bounds).is_some() => mouse::Interaction::Pointer,
+            Some(_) => mouse::Interaction::Crosshair,
+            None => mouse::Interaction::default(),
+        }
+    }
+}
@@ -76,6 +76,72 @@ pub fn menu_sub_button_file_save_as<'a>() -> button::Button<'a, Message, iced::T
     )
 }
 
+pub fn menu_sub_button_tools_detect_balloons<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.detect_balloons"), Message::DetectBalloons)
+}
+
+pub fn menu_sub_button_tools_ocr_current<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.ocr_current"), Message::OcrCurrentBalloon)
+}
+
+pub fn menu_sub_button_tools_ocr_all<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.ocr_all"), Message::OcrAllBalloons)
+}
+
+pub fn menu_sub_button_tools_machine_translate<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(
+        t!("tools_menu.machine_translate"),
+        Message::MachineTranslate,
+    )
+}
+
+pub fn menu_sub_button_tools_sort_balloons<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.sort_balloons"), Message::ReadingOrderPreview)
+}
+
+pub fn menu_sub_button_tools_export_typeset<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.export_typeset"), Message::ExportTypesetPages)
+}
+
+pub fn menu_sub_button_tools_export_typeset_script<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(
+        t!("tools_menu.export_typeset_script"),
+        Message::ExportTypesetScript,
+    )
+}
+
+pub fn menu_sub_button_tools_export_xliff<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.export_xliff"), Message::ExportXliff)
+}
+
+pub fn menu_sub_button_tools_merge_xliff<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.merge_xliff"), Message::MergeXliff)
+}
+
+pub fn menu_sub_button_tools_export_po<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.export_po"), Message::ExportPo)
+}
+
+pub fn menu_sub_button_tools_merge_po<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.merge_po"), Message::MergePo)
+}
+
+pub fn menu_sub_button_tools_balloon_types<'a>(
+) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
+    menu_sub_button(t!("tools_menu.balloon_types"), Message::EditBalloonTypes)
+}
+
 pub fn menu_main_button<'a>(
     label: impl iced::advanced::text::IntoFragment<'a>,
 ) -> button::Button<'a, Message, iced::Theme, iced::Renderer> {
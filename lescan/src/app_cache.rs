@@ -1,7 +1,10 @@
 use rust_i18n::t;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::read_to_string;
 
+use crate::utils::viewer::PageZoom;
+
 #[cfg(unix)]
 const CACHE_FILE_PATH: &str = ".config/lescan/app_cache.toml";
 
@@ -18,6 +21,8 @@ const SETTINGS_FILE_PATH: &str = "AppData\\Roaming\\lescan\\settings.toml";
 pub struct AppCache {
     pub last_document: Option<String>,
     pub settings_file_path: String,
+    #[serde(default)]
+    pub document_zooms: HashMap<String, PageZoom>,
 }
 
 impl Default for AppCache {
@@ -44,6 +49,7 @@ impl Default for AppCache {
             let default_cache_file = AppCache {
                 last_document: None,
                 settings_file_path: home_dir.join(SETTINGS_FILE_PATH).display().to_string(),
+                document_zooms: HashMap::new(),
             };
             default_cache_file.save();
 
@@ -5,9 +5,15 @@ use iced::widget::{pane_grid, scrollable, text_editor};
 #[derive(Debug, Clone)]
 pub enum Message {
     BlnTypeSelected(BlnTypes),
+    BalloonPresetSelected(Option<String>),
+    SfxToggled(bool),
+    SfxEdited(crate::app::widgets::main_content::SfxField),
+    BalloonBoldToggled,
+    BalloonItalicToggled,
     T1ContentChanged(text_editor::Action),
     T2ContentChanged(text_editor::Action),
     T3ContentChanged(text_editor::Action),
+    T4ContentChanged(text_editor::Action),
 
     TabPressed,
     EnterPressed,
@@ -17,6 +23,10 @@ pub enum Message {
 
     ImageTabSelected(crate::utils::tabs::ImageTabs),
     ImageScrolled(scrollable::Viewport),
+    ImageZoom(crate::utils::viewer::ZoomAction),
+    ImagePanned(iced::Vector),
+    FollowScrollToggled(bool),
+    PageCached(usize, String, Result<rsff::page_cache::CachedPage, String>),
     FileDropped(std::path::PathBuf),
 
     BalloonTypeCycleUp,
@@ -25,6 +35,46 @@ pub enum Message {
 
     FileOperation(FileOperation),
     BalloonSelected(usize),
+    BalloonRegionDrawn(usize, rsff::img_data::Region),
+    PageRegionCropped(usize, rsff::img_data::Region),
+    BalloonImgCropped(usize, Result<rsff::img_data::BalloonImage, String>),
+    DetectBalloons,
+    BalloonsDetected(usize, Result<Vec<rsff::balloon::Balloon>, String>),
+    OcrCurrentBalloon,
+    OcrAllBalloons,
+    BalloonsRecognized(
+        Vec<(
+            usize,
+            rsff::img_data::BalloonImage,
+            Result<rsff::ocr::OcrResult, String>,
+        )>,
+    ),
+    MachineTranslate,
+    MachineTranslated(Result<Vec<(usize, String, String)>, String>),
+    AcceptMachineDraft,
+    ReadingOrderPreview,
+    PanelsDetected(Vec<Vec<rsff::img_data::Region>>),
+    ReadingDirectionSelected(rsff::reading_order::ReadingDirection),
+    ApplyReadingOrder,
+    EditBalloonTypes,
+    CustomTypeEdited(usize, crate::app::modals::balloon_types::CustomTypeField),
+    CustomTypeAdded,
+    CustomTypeRemoved(usize),
+    ApplyCustomTypes,
+    TypesetPreviewToggled(bool),
+    TypesetRendered(usize, Result<Vec<(u32, u32, Vec<u8>)>, String>),
+    ExportTypesetPages,
+    TypesetExported(Result<(), String>),
+    ExportTypesetScript,
+    TypesetScriptExported(Result<(), String>),
+    ExportXliff,
+    XliffExported(Result<(), String>),
+    MergeXliff,
+    XliffRead(Result<Option<String>, String>),
+    ExportPo,
+    PoExported(Result<(), String>),
+    MergePo,
+    PoRead(Result<Option<String>, String>),
 
     ShowModal(ModalType),
     HideModal,
@@ -41,6 +91,7 @@ pub enum FileOperation {
     New,
     Open,
     Save(Option<std::path::PathBuf>),
+    Saved(std::path::PathBuf, Result<String, String>),
     SaveFileDialog,
     SaveAsFileDialog,
     NewFileDialog,
@@ -60,4 +111,15 @@ pub enum SettingsMenuContentChanged {
     GeneralSettingsFilePath(String),
     GeneralSettingsAppTheme(iced::Theme),
     GeneralSettingsLanguage(String),
+    AdvancedSettingsCropFormat(rsff::img_data::CropFormat),
+    AdvancedSettingsSffzCodec(rsff::codec::Codec),
+    AdvancedSettingsDetector(usize, String),
+    AdvancedSettingsOcrCommand(String),
+    AdvancedSettingsMtEndpoint(String),
+    AdvancedSettingsMtApiKey(String),
+    AdvancedSettingsMtSourceLang(String),
+    AdvancedSettingsMtTargetLang(String),
+    AdvancedSettingsTypesetFont(String),
+    AdvancedSettingsDocxColumns(String),
+    AdvancedSettingsSheetColumns(String),
 }
@@ -4,6 +4,9 @@ use std::fs::read_to_string;
 use std::path::PathBuf;
 
 use crate::app::modals::settings::SettingsMenuContents;
+use rsff::codec::Codec;
+use rsff::detect::DetectorParams;
+use rsff::img_data::CropFormat;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AppSettings {
@@ -11,6 +14,52 @@ pub struct AppSettings {
     #[serde(with = "theme_serde")]
     pub app_theme: iced::Theme,
     pub language: Option<String>,
+    #[serde(default)]
+    pub crop_format: CropFormat,
+    #[serde(default)]
+    pub sffz_codec: Codec,
+    #[serde(default)]
+    pub detector: DetectorParams,
+    #[serde(default = "default_ocr_command")]
+    pub ocr_command: String,
+    #[serde(default = "default_mt_endpoint")]
+    pub mt_endpoint: String,
+    #[serde(default = "default_mt_source_lang")]
+    pub mt_source_lang: String,
+    #[serde(default = "default_mt_target_lang")]
+    pub mt_target_lang: String,
+    #[serde(default)]
+    pub mt_api_key: Option<String>,
+    #[serde(default)]
+    pub typeset_font: String,
+    #[serde(default = "default_docx_columns")]
+    pub docx_columns: String,
+    #[serde(default = "default_sheet_columns")]
+    pub sheet_columns: String,
+}
+
+fn default_ocr_command() -> String {
+    "tesseract {input} stdout -l jpn".to_string()
+}
+
+fn default_mt_endpoint() -> String {
+    "http://localhost:5000/translate".to_string()
+}
+
+fn default_mt_source_lang() -> String {
+    "ja".to_string()
+}
+
+fn default_mt_target_lang() -> String {
+    "en".to_string()
+}
+
+fn default_docx_columns() -> String {
+    rsff::docx_import::DocxColumns::default().to_string()
+}
+
+fn default_sheet_columns() -> String {
+    rsff::spreadsheet::SheetColumns::default().to_string()
 }
 
 impl AppSettings {
@@ -37,6 +86,17 @@ impl AppSettings {
                 settings_file_path: settings_file_path.to_string(),
                 app_theme: iced::Theme::TokyoNight,
                 language: None,
+                crop_format: CropFormat::default(),
+                sffz_codec: Codec::default(),
+                detector: DetectorParams::default(),
+                ocr_command: default_ocr_command(),
+                mt_endpoint: default_mt_endpoint(),
+                mt_source_lang: default_mt_source_lang(),
+                mt_target_lang: default_mt_target_lang(),
+                mt_api_key: None,
+                typeset_font: String::new(),
+                docx_columns: default_docx_columns(),
+                sheet_columns: default_sheet_columns(),
             }
         }
     }
@@ -45,11 +105,43 @@ impl AppSettings {
         self.settings_file_path = settings_modal.general_settings_file_path.clone();
         self.app_theme = settings_modal.app_theme.clone();
         self.language = settings_modal.language.clone();
+        self.crop_format = settings_modal.crop_format;
+        self.sffz_codec = settings_modal.sffz_codec;
+        self.ocr_command = settings_modal.ocr_command.clone();
+        self.mt_endpoint = settings_modal.mt_endpoint.clone();
+        self.mt_api_key = Some(settings_modal.mt_api_key.trim())
+            .filter(|k| !k.is_empty())
+            .map(String::from);
+        self.mt_source_lang = settings_modal.mt_source_lang.clone();
+        self.mt_target_lang = settings_modal.mt_target_lang.clone();
+        self.typeset_font = settings_modal.typeset_font.clone();
+        self.docx_columns = settings_modal.docx_columns.clone();
+        self.sheet_columns = settings_modal.sheet_columns.clone();
+        // Invalid detector values keep their previous value
+        for (field, value) in DetectorParams::FIELDS.iter().zip(&settings_modal.detector) {
+            let _ = self.detector.set(field, value);
+        }
         if let Some(lang) = &self.language {
             rust_i18n::set_locale(lang);
         }
     }
 
+    /// Docx import options, invalid column lists fall back to the default columns.
+    pub fn docx_import_options(&self) -> rsff::docx_import::DocxImportOptions {
+        rsff::docx_import::DocxImportOptions {
+            columns: self.docx_columns.parse().unwrap_or_default(),
+            detect_header: true,
+        }
+    }
+
+    /// Spreadsheet import options, invalid column lists fall back to the default columns.
+    pub fn sheet_import_options(&self) -> rsff::spreadsheet::SheetImportOptions {
+        rsff::spreadsheet::SheetImportOptions {
+            columns: self.sheet_columns.parse().unwrap_or_default(),
+            detect_header: true,
+        }
+    }
+
     pub fn save(&self) {
         let pb = PathBuf::from(&self.settings_file_path);
         let file = toml::to_string_pretty(&self).unwrap_or_else(|_| {
@@ -8,6 +8,7 @@ pub fn bln_content_creator(
     text_editor::Content,
     text_editor::Content,
     text_editor::Content,
+    text_editor::Content,
 ) {
     let t1_content = {
         if tl_doc.balloons.is_empty() {
@@ -36,5 +37,14 @@ pub fn bln_content_creator(
         }
     };
 
-    (t1_content, t2_content, t3_content)
+    let t4_content = {
+        if tl_doc.balloons.is_empty() {
+            text_editor::Content::default()
+        } else {
+            let src = &tl_doc.balloons[current_balloon].source_content;
+            text_editor::Content::with_text(src.join("\n//\n").as_str())
+        }
+    };
+
+    (t1_content, t2_content, t3_content, t4_content)
 }
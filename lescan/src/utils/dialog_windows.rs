@@ -9,3 +9,13 @@ pub async fn show_error_dialog(title: impl Into<String>, message: impl Into<Stri
         .show()
         .await;
 }
+
+pub async fn show_warning_dialog(title: impl Into<String>, message: impl Into<String>) {
+    AsyncMessageDialog::new()
+        .set_level(MessageLevel::Warning)
+        .set_buttons(MessageButtons::Ok)
+        .set_title(title)
+        .set_description(message)
+        .show()
+        .await;
+}
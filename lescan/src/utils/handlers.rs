@@ -1,7 +1,8 @@
-use crate::app::widgets::main_content::BlnTypes;
+use crate::app::modals::balloon_types::{CustomTypeDraft, CustomTypeField};
+use crate::app::widgets::main_content::{BlnTypes, SfxField};
 use crate::app::LeScan;
 use crate::message::*;
-use crate::utils::dialog_windows;
+use crate::utils::{dialog_windows, pages};
 use iced::keyboard::key::{Key, Named};
 use iced::widget::{
     self,
@@ -9,9 +10,13 @@ use iced::widget::{
 };
 use iced::{window, Task};
 use rsff::balloon::Balloon;
-use rsff::TYPES;
+use rsff::ocr::{CommandOcr, OcrBackend};
+use rsff::sfx::Sfx;
+use rsff::translate::{HttpJsonProvider, TranslationProvider};
+use rsff::{BalloonStatus, MergeReport, TYPES};
 
 use super::tabs::ImageTabs;
+use super::viewer::{page_layout, region_rect, PageRect, PageZoom};
 
 use rust_i18n::t;
 
@@ -22,67 +27,119 @@ const SUPPORTED_IMG_EXTENSIONS: [&str; 12] = [
 pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<Message> {
     match msg {
         Message::BlnTypeSelected(bln_type) => {
+            app.translation_document.balloons[app.current_balloon].btype = bln_type.0.clone();
             app.selected_bln_type = Some(bln_type);
-            match app.selected_bln_type {
-                Some(BlnTypes::Dialogue) => {
-                    app.translation_document.balloons[app.current_balloon].btype =
-                        rsff::TYPES::DIALOGUE
-                }
-                Some(BlnTypes::Thinking) => {
-                    app.translation_document.balloons[app.current_balloon].btype =
-                        rsff::TYPES::THINKING
-                }
-                Some(BlnTypes::Square) => {
-                    app.translation_document.balloons[app.current_balloon].btype =
-                        rsff::TYPES::SQUARE
-                }
-                Some(BlnTypes::OT) => {
-                    app.translation_document.balloons[app.current_balloon].btype = rsff::TYPES::OT
-                }
-                Some(BlnTypes::ST) => {
-                    app.translation_document.balloons[app.current_balloon].btype = rsff::TYPES::ST
-                }
-                _ => {}
-            }
         }
         Message::T1ContentChanged(action) => {
+            // Editing a machine translation draft makes it a human translation
+            let b = &mut app.translation_document.balloons[app.current_balloon];
+            if action.is_edit() && b.status == BalloonStatus::MachineDraft {
+                b.status = BalloonStatus::Translated;
+            }
             app.t1_content.perform(action);
+            let types = type_prefixes(&app.translation_document);
             handle_text_input_balloon_type_selection(
                 &mut app.t1_content,
                 &mut app.translation_document.balloons[app.current_balloon],
                 &mut app.selected_bln_type,
+                &types,
             );
         }
         Message::T2ContentChanged(action) => {
             app.t2_content.perform(action);
+            let types = type_prefixes(&app.translation_document);
             handle_text_input_balloon_type_selection(
                 &mut app.t2_content,
                 &mut app.translation_document.balloons[app.current_balloon],
                 &mut app.selected_bln_type,
+                &types,
             );
         }
         Message::T3ContentChanged(action) => {
             app.t3_content.perform(action);
         }
+        Message::T4ContentChanged(action) => {
+            app.t4_content.perform(action);
+        }
         Message::TabPressed => return iced::widget::focus_next(),
-        Message::EnterPressed => handle_enter_key_press(app),
+        Message::EnterPressed => {
+            let page = app.translation_document.balloons[app.current_balloon].page;
+            handle_enter_key_press(app);
+            // Show the finished balloon's text in the preview
+            if let Some(page) = page.filter(|_| app.typeset_preview) {
+                return pages::render_typeset(app, [page]);
+            }
+        }
         Message::PaneGridResized(widget::pane_grid::ResizeEvent { split, ratio }) => {
             app.panes.resize(split, ratio);
         }
         Message::PaneGridDragged(widget::pane_grid::DragEvent::Dropped { pane, target }) => {
             app.panes.drop(pane, target);
+            app.img_scroller_restore = true;
         }
         Message::PaneGridDragged(_) => {}
         Message::ImageTabSelected(tab) => {
+            if tab == ImageTabs::Document && app.current_img_tab != tab {
+                app.img_scroller_restore = true;
+            }
             app.current_img_tab = tab;
         }
         Message::ImageScrolled(vp) => {
             if app.translation_document.images.is_some() {
+                app.img_viewport = vp.bounds().size();
+
+                // The scroller is recreated from the top when panes or tabs change,
+                // put it back where it was instead of saving that position.
+                if app.img_scroller_restore {
+                    app.img_scroller_restore = false;
+                    return widget::scrollable::snap_to(
+                        app.img_scroller.clone(),
+                        app.img_scroller_current_scroll,
+                    );
+                }
+
+                app.img_scroll_offset = vp.absolute_offset();
                 app.img_scroller_current_scroll = vp.relative_offset();
-                return widget::scrollable::snap_to(
-                    app.img_scroller.clone(),
-                    app.img_scroller_current_scroll,
-                );
+
+                if app.img_programmatic_scroll {
+                    app.img_programmatic_scroll = false;
+                } else if app.follow_scroll {
+                    follow_scroll(app);
+                }
+            }
+        }
+        Message::FollowScrollToggled(follow) => {
+            app.follow_scroll = follow;
+        }
+        Message::ImageZoom(action) => {
+            app.page_zoom.apply(action);
+            if let Some(location) = &app.document_file_location {
+                app.document_zooms.insert(location.clone(), app.page_zoom);
+            }
+        }
+        Message::ImagePanned(delta) => {
+            return widget::scrollable::scroll_by(
+                app.img_scroller.clone(),
+                widget::scrollable::AbsoluteOffset {
+                    x: -delta.x,
+                    y: -delta.y,
+                },
+            );
+        }
+        Message::PageCached(page, path, cached) => {
+            // Pages may have changed while this one was being cached
+            let is_current = app
+                .translation_document
+                .images
+                .as_ref()
+                .is_some_and(|imgs| imgs.get(page) == Some(&path));
+            match cached {
+                Ok(cached) if is_current => app.cached_pages[page] = Some(cached),
+                Ok(_) => {}
+                Err(_e) => {
+                    #[cfg(debug_assertions)]
+                    println!("Could not cache {}: {}", path, _e);
+                }
             }
         }
         Message::FileDropped(path) => {
@@ -113,11 +170,26 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
                     }
                 };
 
-                if ["sffx", "sffz", "txt"].contains(&ext.as_str()) {
+                if ["sffx", "sffz", "sffb", "txt", "docx", "csv", "xlsx", "json"]
+                    .contains(&ext.as_str())
+                {
                     app.current_balloon = 0;
                     app.translation_document = {
-                        match rsff::Document::open(&path) {
-                            Ok(doc) => doc,
+                        let opened = if ext == "docx" {
+                            rsff::Document::open_docx(&path, &app.settings.docx_import_options())
+                        } else if ext == "csv" || ext == "xlsx" {
+                            rsff::Document::open_sheet(&path, &app.settings.sheet_import_options())
+                        } else {
+                            rsff::Document::open(&path)
+                        };
+                        match opened {
+                            // The editors need a balloon, empty imports get a blank one
+                            Ok(mut doc) => {
+                                if doc.balloons.is_empty() {
+                                    doc.add_balloon_empty();
+                                }
+                                doc
+                            }
                             Err(e) => {
                                 let e = e.to_string();
                                 return Task::future(async move {
@@ -136,6 +208,12 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
                         }
                     };
                     app.document_file_location = Some(path.display().to_string());
+                    app.page_zoom = app
+                        .document_zooms
+                        .get(&path.display().to_string())
+                        .copied()
+                        .unwrap_or_default();
+                    return pages::reload_pages(app);
                 } else if SUPPORTED_IMG_EXTENSIONS.contains(&ext.as_str()) {
                     let current_bln = app.current_balloon;
                     match std::fs::read(&path) {
@@ -179,71 +257,12 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
                     .collect::<Vec<_>>();
                 images_in_path.sort();
 
-                app.translation_document.images = Some(images_in_path)
-            }
-        }
-        Message::BalloonTypeCycleDown => {
-            if let Some(current_bln_type) = app.selected_bln_type {
-                let cbi = (BlnTypes::ALL
-                    .iter()
-                    .position(|bt| *bt == current_bln_type)
-                    .unwrap()
-                    + 1)
-                    % BlnTypes::ALL.len();
-                app.selected_bln_type = Some(BlnTypes::ALL[cbi]);
-                app.translation_document.balloons[app.current_balloon].btype = [
-                    TYPES::DIALOGUE,
-                    TYPES::SQUARE,
-                    TYPES::THINKING,
-                    TYPES::ST,
-                    TYPES::OT,
-                ][cbi]
-                    .clone();
-            } else {
-                app.selected_bln_type = Some(BlnTypes::ALL[0]);
-                app.translation_document.balloons[app.current_balloon].btype = [
-                    TYPES::DIALOGUE,
-                    TYPES::SQUARE,
-                    TYPES::THINKING,
-                    TYPES::ST,
-                    TYPES::OT,
-                ][0]
-                .clone()
-            }
-        }
-        Message::BalloonTypeCycleUp => {
-            if let Some(current_bln_type) = app.selected_bln_type {
-                let cbi = BlnTypes::ALL
-                    .iter()
-                    .position(|bt| *bt == current_bln_type)
-                    .unwrap();
-                let cbi = if cbi == 0 {
-                    BlnTypes::ALL.len() - 1
-                } else {
-                    cbi - 1
-                };
-                println!("{cbi}");
-                app.selected_bln_type = Some(BlnTypes::ALL[cbi]);
-                app.translation_document.balloons[app.current_balloon].btype = [
-                    TYPES::DIALOGUE,
-                    TYPES::SQUARE,
-                    TYPES::THINKING,
-                    TYPES::ST,
-                    TYPES::OT,
-                ][cbi]
-                    .clone();
-            } else {
-                app.selected_bln_type = Some(BlnTypes::ALL[0]);
-                app.translation_document.balloons[app.current_balloon].btype = [
-                    TYPES::DIALOGUE,
-                    TYPES::SQUARE,
-                    TYPES::THINKING,
-                    TYPES::ST,
-                    TYPES::OT,
-                ][0]
-                .clone();
+                app.translation_document.images = Some(images_in_path);
+                return pages::reload_pages(app);
             }
         }
+        Message::BalloonTypeCycleDown => cycle_balloon_type(app, 1),
+        Message::BalloonTypeCycleUp => cycle_balloon_type(app, -1),
         Message::CurrentBlnImgPaste => clipboard_img_paste(app),
         Message::FileOperation(file_op) => match file_op {
             FileOperation::NewFileDialog => {
@@ -264,20 +283,30 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
                 .and_then(|_| Task::done(Message::FileOperation(FileOperation::New)))
             }
             FileOperation::New => {
-                app.selected_bln_type = Some(BlnTypes::Dialogue);
+                app.selected_bln_type = Some(BlnTypes(TYPES::DIALOGUE));
                 app.t1_content = text_editor::Content::default();
                 app.t2_content = text_editor::Content::default();
                 app.t3_content = text_editor::Content::default();
+                app.t4_content = text_editor::Content::default();
                 app.current_balloon = 0;
                 app.current_img_tab = ImageTabs::Document;
                 app.document_file_location = None;
                 app.translation_document = rsff::Document::default();
                 app.translation_document.add_balloon_empty();
+                app.page_zoom = PageZoom::default();
+                return pages::reload_pages(app);
             }
             FileOperation::Open => {
                 return Task::future(async {
                     rfd::AsyncFileDialog::new()
-                        .add_filter("RSFF", &["txt", "sffx", "sffz"])
+                        .add_filter(
+                            "RSFF",
+                            &["txt", "sffx", "sffz", "sffb", "docx", "csv", "xlsx"],
+                        )
+                        .add_filter(
+                            t!("dialog_windows.open_document.filter_other_tools"),
+                            &["txt", "json"],
+                        )
                         .set_title(t!("dialog_windows.open_document.title"))
                         .pick_file()
                         .await
@@ -286,12 +315,49 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
             }
             FileOperation::Save(save_location) => {
                 if let Some(save_location) = save_location {
-                    let save_res = app.translation_document.save(&save_location);
-
-                    #[cfg(debug_assertions)]
-                    println!("Saved to {:?}", save_location);
+                    // Compressing image heavy chapters takes a while, a copy of the document
+                    // is saved off the UI thread
+                    let document = app.translation_document.clone();
+                    let codec = app.settings.sffz_codec;
+                    return Task::perform(
+                        async move {
+                            let location = save_location.display().to_string();
+                            let ext = save_location.extension().and_then(|e| e.to_str());
+                            let save_res = match ext {
+                                Some("sffz") => {
+                                    document.save_sffz(&save_location, codec).map(|_| location)
+                                }
+                                Some("sffb") => {
+                                    document.save_sffb(&save_location, codec).map(|_| location)
+                                }
+                                _ => document.save(&save_location),
+                            };
+                            (save_location, save_res.map_err(|e| e.to_string()))
+                        },
+                        |(save_location, save_res)| {
+                            Message::FileOperation(FileOperation::Saved(save_location, save_res))
+                        },
+                    );
+                }
+            }
+            FileOperation::Saved(save_location, save_res) => {
+                #[cfg(debug_assertions)]
+                println!("Saved to {:?}", save_location);
 
-                    if let Err(save_error) = save_res {
+                match save_res {
+                    Ok(location) => {
+                        // HTML and Markdown can't be opened again, they are only exports and
+                        // later saves keep going to the document
+                        let export_only = matches!(
+                            save_location.extension().and_then(|e| e.to_str()),
+                            Some("html" | "htm" | "md")
+                        );
+                        if !export_only {
+                            app.document_zooms.insert(location.clone(), app.page_zoom);
+                            app.document_file_location = Some(location);
+                        }
+                    }
+                    Err(save_error) => {
                         return Task::future(async move {
                             rfd::AsyncMessageDialog::new()
                                 .set_description(format!(
@@ -304,8 +370,6 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
                                 .await;
                         })
                         .then(|_| Task::none());
-                    } else {
-                        app.document_file_location = Some(save_location.display().to_string())
                     }
                 }
             }
@@ -337,6 +401,10 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
                                 t!("dialog_windows.save_as_document.filter_sffz"),
                                 &["sffz"],
                             )
+                            .add_filter(
+                                t!("dialog_windows.save_as_document.filter_sffb"),
+                                &["sffb"],
+                            )
                             .add_filter(
                                 t!("dialog_windows.save_as_document.filter_sffx"),
                                 &["sffx"],
@@ -346,6 +414,16 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
                                 t!("dialog_windows.save_as_document.filter_docx"),
                                 &["docx"],
                             )
+                            .add_filter(t!("dialog_windows.save_as_document.filter_csv"), &["csv"])
+                            .add_filter(
+                                t!("dialog_windows.save_as_document.filter_xlsx"),
+                                &["xlsx"],
+                            )
+                            .add_filter(
+                                t!("dialog_windows.save_as_document.filter_html"),
+                                &["html"],
+                            )
+                            .add_filter(t!("dialog_windows.save_as_document.filter_md"), &["md"])
                             .set_title(t!("dialog_windows.save_as_document.title"))
                             .set_can_create_directories(true)
                             .set_file_name("scan")
@@ -358,35 +436,554 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
             }
         },
         Message::BalloonSelected(i) => {
-            app.current_balloon = i;
-            app.t1_content = text_editor::Content::with_text(
-                app.translation_document.balloons[i]
-                    .tl_content
-                    .join("\n//\n")
-                    .as_str(),
+            select_balloon(app, i);
+            return scroll_to_current_balloon(app);
+        }
+        Message::BalloonRegionDrawn(page, region) => {
+            // Fill the current balloon first so typing then drawing doesn't leave
+            // an unplaced balloon behind, otherwise start a new one.
+            let current = &mut app.translation_document.balloons[app.current_balloon];
+            if current.region.is_none() {
+                current.set_region(page, region);
+            } else {
+                let mut b = Balloon::default();
+                b.set_region(page, region);
+                app.translation_document.add_balloon(b);
+                return Task::done(Message::BalloonSelected(
+                    app.translation_document.balloons.len() - 1,
+                ));
+            }
+        }
+        Message::PageRegionCropped(page, region) => {
+            let Some(page_path) = app
+                .translation_document
+                .images
+                .as_ref()
+                .and_then(|imgs| imgs.get(page).cloned())
+            else {
+                return Task::none();
+            };
+            let balloon = app.current_balloon;
+            let format = app.settings.crop_format;
+
+            return Task::perform(
+                async move {
+                    rsff::img_data::crop_page(&page_path, &region, format)
+                        .map_err(|e| format!("{}\n{}", page_path, e))
+                },
+                move |cropped| Message::BalloonImgCropped(balloon, cropped),
             );
-            app.t2_content = text_editor::Content::with_text(
-                app.translation_document.balloons[i]
-                    .pr_content
-                    .join("\n//\n")
-                    .as_str(),
+        }
+        Message::BalloonImgCropped(balloon, cropped) => match cropped {
+            Ok(img) => {
+                if let Some(b) = app.translation_document.balloons.get_mut(balloon) {
+                    b.balloon_img = Some(img);
+                }
+            }
+            Err(e) => {
+                return Task::future(async move {
+                    dialog_windows::show_error_dialog(
+                        t!("errors.crop_error.title"),
+                        t!("errors.crop_error.description", e = e),
+                    )
+                    .await
+                })
+                .then(|_| Task::none());
+            }
+        },
+        Message::DetectBalloons => {
+            let Some(pages) = app.translation_document.images.clone() else {
+                return Task::none();
+            };
+            let params = app.settings.detector;
+            let direction = app.translation_document.reading_direction;
+            let format = app.settings.crop_format;
+
+            return Task::batch(pages.into_iter().enumerate().map(|(page, page_path)| {
+                Task::perform(
+                    async move {
+                        rsff::detect::balloons_from_page(
+                            &page_path, page, &params, direction, format,
+                        )
+                        .map_err(|e| format!("{}\n{}", page_path, e))
+                    },
+                    move |detected| Message::BalloonsDetected(page, detected),
+                )
+            }));
+        }
+        Message::BalloonsDetected(page, detected) => match detected {
+            Ok(detected) => {
+                save_editors(app);
+                let doc = &mut app.translation_document;
+                // Replace the empty balloon a new document starts with. Pages without
+                // balloons, like covers, must not leave the document empty.
+                if !detected.is_empty() && doc.balloons.len() == 1 && doc.balloons[0].is_blank() {
+                    doc.balloons.clear();
+                }
+                let had_balloons = !doc.balloons.is_empty();
+
+                let added = doc.add_detected_balloons(page, detected);
+                if had_balloons && added.start <= app.current_balloon {
+                    app.current_balloon += added.len();
+                }
+                let current = app.current_balloon.min(doc.len().saturating_sub(1));
+                select_balloon(app, current);
+            }
+            Err(e) => {
+                return Task::future(async move {
+                    dialog_windows::show_error_dialog(
+                        t!("errors.detect_error.title"),
+                        t!("errors.detect_error.description", e = e),
+                    )
+                    .await
+                })
+                .then(|_| Task::none());
+            }
+        },
+        Message::OcrCurrentBalloon => {
+            save_editors(app);
+            if app.translation_document.balloons[app.current_balloon]
+                .balloon_img
+                .is_none()
+            {
+                let e = t!("errors.ocr_error.no_image");
+                return Task::future(async move {
+                    dialog_windows::show_error_dialog(
+                        t!("errors.ocr_error.title"),
+                        t!("errors.ocr_error.description", e = e),
+                    )
+                    .await
+                })
+                .then(|_| Task::none());
+            }
+            return ocr_balloons(app, vec![app.current_balloon]);
+        }
+        Message::OcrAllBalloons => {
+            save_editors(app);
+            // Source text typed in or corrected by hand is kept
+            let targets = app
+                .translation_document
+                .balloons
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.source_content.is_empty() && b.balloon_img.is_some())
+                .map(|(i, _)| i)
+                .collect();
+            return ocr_balloons(app, targets);
+        }
+        Message::BalloonsRecognized(results) => {
+            let mut errors = Vec::new();
+            for (i, img, res) in results {
+                // Balloons may be added, removed or moved while OCR runs, the text only
+                // goes to the balloon that still has the recognized image
+                let Some(b) = app
+                    .translation_document
+                    .balloons
+                    .get_mut(i)
+                    .filter(|b| b.balloon_img.as_ref() == Some(&img))
+                else {
+                    continue;
+                };
+                match res {
+                    Ok(res) => {
+                        b.source_content = res.lines();
+                        if i == app.current_balloon {
+                            app.t4_content = text_editor::Content::with_text(
+                                res.lines().join("\n//\n").as_str(),
+                            );
+                        }
+                    }
+                    Err(e) => errors.push(format!("#{}: {}", i + 1, e)),
+                }
+            }
+
+            if !errors.is_empty() {
+                let e = errors.join("\n");
+                return Task::future(async move {
+                    dialog_windows::show_error_dialog(
+                        t!("errors.ocr_error.title"),
+                        t!("errors.ocr_error.description", e = e),
+                    )
+                    .await
+                })
+                .then(|_| Task::none());
+            }
+        }
+        Message::MachineTranslate => {
+            save_editors(app);
+            // Sources are sent back with the drafts, balloons may move in the meantime
+            let pending = app.translation_document.machine_translation_sources();
+            if pending.is_empty() {
+                return Task::none();
+            }
+            let sources = Vec::from_iter(pending.iter().map(|(_, s)| s.clone()));
+
+            let mut provider = HttpJsonProvider::new(
+                &app.settings.mt_endpoint,
+                &app.settings.mt_source_lang,
+                &app.settings.mt_target_lang,
             );
-            app.t3_content = text_editor::Content::with_text(
-                app.translation_document.balloons[i]
-                    .comments
-                    .join("\n//\n")
-                    .as_str(),
+            provider.api_key = app.settings.mt_api_key.clone();
+
+            return Task::perform(
+                async move {
+                    provider
+                        .translate(&sources)
+                        .map(|drafts| {
+                            pending
+                                .into_iter()
+                                .zip(drafts)
+                                .map(|((i, source), draft)| (i, source, draft))
+                                .collect()
+                        })
+                        .map_err(|e| e.to_string())
+                },
+                Message::MachineTranslated,
             );
-            app.selected_bln_type = Some({
-                match app.translation_document.balloons[i].btype {
-                    TYPES::DIALOGUE => BlnTypes::Dialogue,
-                    TYPES::OT => BlnTypes::OT,
-                    TYPES::SQUARE => BlnTypes::Square,
-                    TYPES::ST => BlnTypes::ST,
-                    TYPES::THINKING => BlnTypes::Thinking,
+        }
+        Message::MachineTranslated(drafts) => match drafts {
+            Ok(drafts) => {
+                // Keep what was typed while waiting for the drafts
+                save_editors(app);
+                app.translation_document.apply_machine_drafts(drafts);
+                select_balloon(app, app.current_balloon);
+            }
+            Err(e) => {
+                return Task::future(async move {
+                    dialog_windows::show_error_dialog(
+                        t!("errors.mt_error.title"),
+                        t!("errors.mt_error.description", e = e),
+                    )
+                    .await
+                })
+                .then(|_| Task::none());
+            }
+        },
+        Message::AcceptMachineDraft => {
+            app.translation_document.balloons[app.current_balloon].status =
+                BalloonStatus::Translated;
+        }
+        Message::ReadingOrderPreview => {
+            save_editors(app);
+            let pages = app.translation_document.images.clone().unwrap_or_default();
+            let params = app.settings.detector;
+
+            // Pages that can't be read are sorted without panels
+            return Task::perform(
+                async move {
+                    pages
+                        .iter()
+                        .map(|p| rsff::detect::panels_from_page(p, &params).unwrap_or_default())
+                        .collect()
+                },
+                Message::PanelsDetected,
+            );
+        }
+        Message::PanelsDetected(panels) => {
+            app.page_panels = panels;
+            app.show_modal = Some(crate::app::modals::ModalType::ReadingOrder);
+        }
+        Message::ReadingDirectionSelected(direction) => {
+            app.translation_document.reading_direction = direction;
+        }
+        Message::ApplyReadingOrder => {
+            // Balloons may have been added or removed while the window was open,
+            // the order is computed again for the current balloons
+            let order = app.translation_document.reading_order(&app.page_panels);
+            let current = order
+                .iter()
+                .position(|i| *i == app.current_balloon)
+                .unwrap_or(0);
+            if app.translation_document.reorder_balloons(&order).is_ok() {
+                select_balloon(app, current);
+            }
+            app.show_modal = None;
+        }
+        Message::SfxToggled(on) => {
+            save_editors(app);
+            let b = &mut app.translation_document.balloons[app.current_balloon];
+            b.sfx = on.then(|| {
+                // Start from what was already typed for the balloon
+                Sfx::new(
+                    b.source_content.join(" "),
+                    "",
+                    b.tl_content.join(" ").trim(),
+                )
+            });
+        }
+        Message::SfxEdited(field) => {
+            if let Some(sfx) = &mut app.translation_document.balloons[app.current_balloon].sfx {
+                match field {
+                    SfxField::Original(s) => sfx.original = s,
+                    SfxField::Romanization(s) => sfx.romanization = s,
+                    SfxField::Translation(s) => sfx.translation = s,
+                    SfxField::Treatment(t) => sfx.treatment = t,
                 }
+            }
+        }
+        Message::BalloonPresetSelected(preset) => {
+            app.translation_document.balloons[app.current_balloon].preset = preset;
+        }
+        Message::BalloonBoldToggled => {
+            let doc = &mut app.translation_document;
+            let bold = doc.balloon_style(&doc.balloons[app.current_balloon]).bold;
+            doc.balloons[app.current_balloon].style.bold = Some(!bold.unwrap_or(false));
+        }
+        Message::BalloonItalicToggled => {
+            let doc = &mut app.translation_document;
+            let italic = doc.balloon_style(&doc.balloons[app.current_balloon]).italic;
+            doc.balloons[app.current_balloon].style.italic = Some(!italic.unwrap_or(false));
+        }
+        Message::EditBalloonTypes => {
+            app.custom_types_draft = app
+                .translation_document
+                .custom_types
+                .iter()
+                .map(CustomTypeDraft::from)
+                .collect();
+            app.show_modal = Some(crate::app::modals::ModalType::BalloonTypes);
+        }
+        Message::CustomTypeEdited(i, field) => {
+            let draft = &mut app.custom_types_draft[i];
+            match field {
+                CustomTypeField::Name(name) => draft.name = name,
+                CustomTypeField::Prefix(prefix) => draft.prefix = prefix,
+                CustomTypeField::Color(color) => draft.color = color,
+            }
+        }
+        Message::CustomTypeAdded => {
+            app.custom_types_draft.push(CustomTypeDraft {
+                color: rsff::style::Color::BLACK.to_string(),
+                ..Default::default()
             });
         }
+        Message::CustomTypeRemoved(i) => {
+            app.custom_types_draft.remove(i);
+        }
+        Message::ApplyCustomTypes => {
+            if let Err(e) = apply_custom_types(app) {
+                return Task::future(async move {
+                    dialog_windows::show_error_dialog(
+                        t!("errors.balloon_types_error.title"),
+                        t!("errors.balloon_types_error.description", e = e),
+                    )
+                    .await
+                })
+                .then(|_| Task::none());
+            }
+            app.show_modal = None;
+            select_balloon(app, app.current_balloon);
+        }
+        Message::TypesetPreviewToggled(on) => {
+            app.typeset_preview = false;
+            if !on {
+                return Task::none();
+            }
+
+            // Reload the font every time, it may have been changed in the settings
+            match pages::load_typesetter(&app.settings.typeset_font) {
+                Ok(typesetter) => {
+                    save_editors(app);
+                    app.typesetter = Some(typesetter);
+                    app.typeset_preview = true;
+                    app.typeset_pages = vec![None; app.page_sizes.len()];
+                    return pages::render_typeset(app, 0..app.page_sizes.len());
+                }
+                Err(e) => {
+                    return Task::future(async move {
+                        dialog_windows::show_error_dialog(
+                            t!("errors.typeset_error.title"),
+                            t!("errors.typeset_error.description", e = e),
+                        )
+                        .await
+                    })
+                    .then(|_| Task::none());
+                }
+            }
+        }
+        Message::TypesetRendered(page, tiles) => match tiles {
+            Ok(tiles) if page < app.typeset_pages.len() => {
+                app.typeset_pages[page] = Some(
+                    tiles
+                        .into_iter()
+                        .map(|(w, h, raw)| widget::image::Handle::from_rgba(w, h, raw))
+                        .collect(),
+                );
+            }
+            Ok(_) => {}
+            Err(_e) => {
+                #[cfg(debug_assertions)]
+                println!("Could not typeset page {}: {}", page, _e);
+            }
+        },
+        Message::ExportTypesetPages => {
+            save_editors(app);
+            let typesetter = match pages::load_typesetter(&app.settings.typeset_font) {
+                Ok(typesetter) => typesetter,
+                Err(e) => return Task::done(Message::TypesetExported(Err(e))),
+            };
+            let img_paths = app.translation_document.images.clone().unwrap_or_default();
+            let texts = (0..img_paths.len())
+                .map(|page| rsff::typeset::page_texts(&app.translation_document, page))
+                .collect::<Vec<_>>();
+
+            return Task::perform(
+                async move {
+                    let Some(folder) = rfd::AsyncFileDialog::new()
+                        .set_title(t!("dialog_windows.export_typeset.title"))
+                        .set_can_create_directories(true)
+                        .pick_folder()
+                        .await
+                    else {
+                        return Ok(());
+                    };
+
+                    for (path, texts) in img_paths.iter().zip(texts) {
+                        let stem = std::path::Path::new(path)
+                            .file_stem()
+                            .map(|s| s.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        typesetter
+                            .render_page_file(path, &texts, &Default::default())
+                            .map_err(|e| e.to_string())?
+                            .save(folder.path().join(format!("{}_typeset.png", stem)))
+                            .map_err(|e| e.to_string())?;
+                    }
+                    Ok(())
+                },
+                Message::TypesetExported,
+            );
+        }
+        Message::TypesetExported(res) => {
+            if let Err(e) = res {
+                return Task::future(async move {
+                    dialog_windows::show_error_dialog(
+                        t!("errors.typeset_error.title"),
+                        t!("errors.typeset_error.description", e = e),
+                    )
+                    .await
+                })
+                .then(|_| Task::none());
+            }
+        }
+        Message::ExportTypesetScript => {
+            save_editors(app);
+            let jsx = app.translation_document.to_jsx();
+            let gimp_script = app.translation_document.to_gimp_script();
+
+            return Task::perform(
+                async move {
+                    let Some(file) = rfd::AsyncFileDialog::new()
+                        .set_title(t!("dialog_windows.export_typeset_script.title"))
+                        .add_filter(
+                            t!("dialog_windows.export_typeset_script.filter_jsx"),
+                            &["jsx"],
+                        )
+                        .add_filter(
+                            t!("dialog_windows.export_typeset_script.filter_py"),
+                            &["py"],
+                        )
+                        .set_file_name("typeset.jsx")
+                        .save_file()
+                        .await
+                    else {
+                        return Ok(());
+                    };
+                    let script = match file.path().extension() {
+                        Some(ext) if ext == "py" => gimp_script,
+                        _ => jsx,
+                    };
+                    file.write(script.as_bytes())
+                        .await
+                        .map_err(|e| e.to_string())
+                },
+                Message::TypesetScriptExported,
+            );
+        }
+        Message::TypesetScriptExported(res) => {
+            if let Err(e) = res {
+                return Task::future(async move {
+                    dialog_windows::show_error_dialog(
+                        t!("errors.typeset_script_error.title"),
+                        t!("errors.typeset_script_error.description", e = e),
+                    )
+                    .await
+                })
+                .then(|_| Task::none());
+            }
+        }
+        Message::ExportXliff => {
+            let (source, target) = (
+                app.settings.mt_source_lang.clone(),
+                app.settings.mt_target_lang.clone(),
+            );
+            return export_translation_file(
+                app,
+                |d| d.to_xliff(&source, &target),
+                t!("dialog_windows.export_xliff.title").to_string(),
+                xliff_filter(),
+                Message::XliffExported,
+            );
+        }
+        Message::MergeXliff => {
+            return pick_translation_file(
+                t!("dialog_windows.merge_xliff.title").to_string(),
+                xliff_filter(),
+                Message::XliffRead,
+            );
+        }
+        Message::XliffRead(Ok(Some(xliff))) => {
+            return merge_translation_file(
+                app,
+                &xliff,
+                rsff::Document::merge_xliff,
+                Message::XliffRead,
+            );
+        }
+        Message::XliffRead(Ok(None)) => {}
+        Message::XliffExported(Err(e)) | Message::XliffRead(Err(e)) => {
+            return Task::future(async move {
+                dialog_windows::show_error_dialog(
+                    t!("errors.xliff_error.title"),
+                    t!("errors.xliff_error.description", e = e),
+                )
+                .await
+            })
+            .then(|_| Task::none());
+        }
+        Message::XliffExported(Ok(())) => {}
+        Message::ExportPo => {
+            let language = app.settings.mt_target_lang.clone();
+            return export_translation_file(
+                app,
+                |d| Ok(d.to_po(&language)),
+                t!("dialog_windows.export_po.title").to_string(),
+                po_filter(),
+                Message::PoExported,
+            );
+        }
+        Message::MergePo => {
+            return pick_translation_file(
+                t!("dialog_windows.merge_po.title").to_string(),
+                po_filter(),
+                Message::PoRead,
+            );
+        }
+        Message::PoRead(Ok(Some(po))) => {
+            return merge_translation_file(app, &po, rsff::Document::merge_po, Message::PoRead);
+        }
+        Message::PoRead(Ok(None)) => {}
+        Message::PoExported(Err(e)) | Message::PoRead(Err(e)) => {
+            return Task::future(async move {
+                dialog_windows::show_error_dialog(
+                    t!("errors.po_error.title"),
+                    t!("errors.po_error.description", e = e),
+                )
+                .await
+            })
+            .then(|_| Task::none());
+        }
+        Message::PoExported(Ok(())) => {}
         Message::ShowModal(modal_type) => {
             app.show_modal = Some(modal_type);
         }
@@ -420,6 +1017,39 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
                 SettingsMenuContentChanged::GeneralSettingsLanguage(lang) => {
                     app.settings_menu_contents.language = Some(lang);
                 }
+                SettingsMenuContentChanged::AdvancedSettingsCropFormat(format) => {
+                    app.settings_menu_contents.crop_format = format;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsSffzCodec(codec) => {
+                    app.settings_menu_contents.sffz_codec = codec;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsDetector(idx, value) => {
+                    app.settings_menu_contents.detector[idx] = value;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsOcrCommand(cmd) => {
+                    app.settings_menu_contents.ocr_command = cmd;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsMtEndpoint(endpoint) => {
+                    app.settings_menu_contents.mt_endpoint = endpoint;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsMtApiKey(key) => {
+                    app.settings_menu_contents.mt_api_key = key;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsMtSourceLang(lang) => {
+                    app.settings_menu_contents.mt_source_lang = lang;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsMtTargetLang(lang) => {
+                    app.settings_menu_contents.mt_target_lang = lang;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsTypesetFont(path) => {
+                    app.settings_menu_contents.typeset_font = path;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsDocxColumns(columns) => {
+                    app.settings_menu_contents.docx_columns = columns;
+                }
+                SettingsMenuContentChanged::AdvancedSettingsSheetColumns(columns) => {
+                    app.settings_menu_contents.sheet_columns = columns;
+                }
             },
             SettingsMenu::ApplySettings => {
                 app.settings.apply_from_modal(&app.settings_menu_contents);
@@ -433,6 +1063,7 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
             let cache = crate::app_cache::AppCache {
                 last_document: app.document_file_location.clone(),
                 settings_file_path: app.settings.settings_file_path.clone(),
+                document_zooms: app.document_zooms.clone(),
             };
             cache.save();
             println!("Cache saved!");
@@ -443,7 +1074,79 @@ pub fn message_handler(msg: crate::message::Message, app: &mut LeScan) -> Task<M
 }
 
 pub fn handle_enter_key_press(app: &mut LeScan) {
-    // Save the content of the text editors to the current balloon
+    save_editors(app);
+
+    // Proceed to the next balloon
+
+    let max_len = app.translation_document.balloons.len();
+    let new = app.current_balloon + 1;
+
+    if new == max_len {
+        let new_balloon = Balloon::default();
+        app.translation_document.balloons.push(new_balloon);
+        app.current_balloon = new;
+
+        // Clear the text inputs
+        app.t1_content = text_editor::Content::default();
+        app.t2_content = text_editor::Content::default();
+        app.t3_content = text_editor::Content::default();
+        app.t4_content = text_editor::Content::default();
+    } else {
+        app.current_balloon = new;
+
+        let tl = &app.translation_document.balloons[new].tl_content;
+        app.t1_content = text_editor::Content::with_text(tl.join("\n//\n").as_str());
+
+        let pr = &app.translation_document.balloons[new].pr_content;
+        app.t2_content = text_editor::Content::with_text(pr.join("\n//\n").as_str());
+
+        let cmmnts = &app.translation_document.balloons[new].comments;
+        app.t3_content = text_editor::Content::with_text(cmmnts.join("\n//\n").as_str());
+
+        let src = &app.translation_document.balloons[new].source_content;
+        app.t4_content = text_editor::Content::with_text(src.join("\n//\n").as_str());
+    }
+}
+
+// Run OCR on the images of the given balloons, one after the other.
+fn ocr_balloons(app: &LeScan, balloons: Vec<usize>) -> Task<Message> {
+    let Some(ocr) = CommandOcr::from_command_line(&app.settings.ocr_command) else {
+        return Task::future(async move {
+            dialog_windows::show_error_dialog(
+                t!("errors.ocr_error.title"),
+                t!("errors.ocr_error.no_command"),
+            )
+            .await
+        })
+        .then(|_| Task::none());
+    };
+    let images = balloons
+        .into_iter()
+        .filter_map(|i| {
+            let img = app.translation_document.balloons[i].balloon_img.clone()?;
+            Some((i, img))
+        })
+        .collect::<Vec<_>>();
+    if images.is_empty() {
+        return Task::none();
+    }
+
+    Task::perform(
+        async move {
+            images
+                .into_iter()
+                .map(|(i, img)| {
+                    let res = ocr.recognize(&img).map_err(|e| e.to_string());
+                    (i, img, res)
+                })
+                .collect()
+        },
+        Message::BalloonsRecognized,
+    )
+}
+
+// Save the content of the text editors to the current balloon
+fn save_editors(app: &mut LeScan) {
     let tl = app
         .t1_content
         .text()
@@ -469,35 +1172,281 @@ pub fn handle_enter_key_press(app: &mut LeScan) {
         .map(|s| s.to_string())
         .collect();
 
+    let src = app.t4_content.text();
+    let src = src.trim_end_matches("\n//\n").trim_end_matches("\n");
+    // Don't fill every balloon with an empty source line
+    let src = if src.is_empty() {
+        Vec::new()
+    } else {
+        src.split("\n//\n").map(|s| s.to_string()).collect()
+    };
+
     app.translation_document.balloons[app.current_balloon].tl_content = tl;
     app.translation_document.balloons[app.current_balloon].pr_content = pr;
     app.translation_document.balloons[app.current_balloon].comments = cmmnts;
+    app.translation_document.balloons[app.current_balloon].source_content = src;
+}
 
-    // Proceed to the next balloon
+// Name and extensions of a file type in file dialogs.
+type FileFilter = (String, &'static [&'static str]);
 
-    let max_len = app.translation_document.balloons.len();
-    let new = app.current_balloon + 1;
+fn xliff_filter() -> FileFilter {
+    (
+        t!("dialog_windows.export_xliff.filter").to_string(),
+        &["xlf", "xliff"],
+    )
+}
 
-    if new == max_len {
-        let new_balloon = Balloon::default();
-        app.translation_document.balloons.push(new_balloon);
-        app.current_balloon = new;
+fn po_filter() -> FileFilter {
+    (t!("dialog_windows.export_po.filter").to_string(), &["po"])
+}
 
-        // Clear the text inputs
-        app.t1_content = text_editor::Content::default();
-        app.t2_content = text_editor::Content::default();
-        app.t3_content = text_editor::Content::default();
-    } else {
-        app.current_balloon = new;
+// Writes the document as an XLIFF or PO file with `to_file`, in a file the user picks.
+fn export_translation_file(
+    app: &mut LeScan,
+    to_file: impl FnOnce(&rsff::Document) -> Result<String, Box<dyn std::error::Error>>,
+    title: String,
+    (filter, extensions): FileFilter,
+    exported: fn(Result<(), String>) -> Message,
+) -> Task<Message> {
+    save_editors(app);
+    let contents = match to_file(&app.translation_document) {
+        Ok(contents) => contents,
+        Err(e) => return Task::done(exported(Err(e.to_string()))),
+    };
 
-        let tl = &app.translation_document.balloons[new].tl_content;
-        app.t1_content = text_editor::Content::with_text(tl.join("\n//\n").as_str());
+    Task::perform(
+        async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .set_title(title)
+                .add_filter(filter, extensions)
+                .save_file()
+                .await
+            else {
+                return Ok(());
+            };
+            file.write(contents.as_bytes())
+                .await
+                .map_err(|e| e.to_string())
+        },
+        exported,
+    )
+}
 
-        let pr = &app.translation_document.balloons[new].pr_content;
-        app.t2_content = text_editor::Content::with_text(pr.join("\n//\n").as_str());
+// Reads an XLIFF or PO file the user picks, `None` if the dialog is cancelled.
+fn pick_translation_file(
+    title: String,
+    (filter, extensions): FileFilter,
+    read: fn(Result<Option<String>, String>) -> Message,
+) -> Task<Message> {
+    Task::perform(
+        async move {
+            let Some(file) = rfd::AsyncFileDialog::new()
+                .set_title(title)
+                .add_filter(filter, extensions)
+                .pick_file()
+                .await
+            else {
+                return Ok(None);
+            };
+            String::from_utf8(file.read().await)
+                .map(Some)
+                .map_err(|e| e.to_string())
+        },
+        read,
+    )
+}
 
-        let cmmnts = &app.translation_document.balloons[new].comments;
-        app.t3_content = text_editor::Content::with_text(cmmnts.join("\n//\n").as_str());
+// Merges a read XLIFF or PO file with `merge` and warns about the balloons it didn't match
+// anymore. Errors are sent back with `read`.
+fn merge_translation_file(
+    app: &mut LeScan,
+    contents: &str,
+    merge: fn(&mut rsff::Document, &str) -> Result<MergeReport, Box<dyn std::error::Error>>,
+    read: fn(Result<Option<String>, String>) -> Message,
+) -> Task<Message> {
+    save_editors(app);
+    let report = match merge(&mut app.translation_document, contents) {
+        Ok(report) => report,
+        Err(e) => return Task::done(read(Err(e.to_string()))),
+    };
+    select_balloon(app, app.current_balloon);
+    if report.mismatched.is_empty() {
+        return Task::none();
+    }
+
+    let balloons = report
+        .mismatched
+        .iter()
+        .map(|i| (i + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Task::future(async move {
+        dialog_windows::show_warning_dialog(
+            t!("dialog_windows.merge_mismatch.title"),
+            t!(
+                "dialog_windows.merge_mismatch.description",
+                balloons = balloons
+            ),
+        )
+        .await
+    })
+    .then(|_| Task::none())
+}
+
+pub fn select_balloon(app: &mut LeScan, i: usize) {
+    app.current_balloon = i;
+    app.t1_content = text_editor::Content::with_text(
+        app.translation_document.balloons[i]
+            .tl_content
+            .join("\n//\n")
+            .as_str(),
+    );
+    app.t2_content = text_editor::Content::with_text(
+        app.translation_document.balloons[i]
+            .pr_content
+            .join("\n//\n")
+            .as_str(),
+    );
+    app.t3_content = text_editor::Content::with_text(
+        app.translation_document.balloons[i]
+            .comments
+            .join("\n//\n")
+            .as_str(),
+    );
+    app.t4_content = text_editor::Content::with_text(
+        app.translation_document.balloons[i]
+            .source_content
+            .join("\n//\n")
+            .as_str(),
+    );
+    app.selected_bln_type = Some(BlnTypes(app.translation_document.balloons[i].btype.clone()));
+}
+
+// Applies the balloon types window to the document.
+fn apply_custom_types(app: &mut LeScan) -> Result<(), String> {
+    let types = app
+        .custom_types_draft
+        .iter()
+        .map(|d| {
+            let color = d.color.parse()?;
+            let t = rsff::CustomType::new(d.name.trim(), d.prefix.trim(), color);
+            Ok((d.original.clone(), t))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // Check them all first so a bad row is reported before anything else
+    let mut check = rsff::Document::default();
+    for (_, t) in &types {
+        check
+            .add_custom_type(t.clone())
+            .map_err(|e| e.to_string())?;
+    }
+
+    // Changes can still conflict with each other depending on their order, like two
+    // types swapping names. They are applied to a copy so a failure doesn't leave half
+    // of the changes applied.
+    let mut doc = app.translation_document.clone();
+    let removed = doc
+        .custom_types
+        .iter()
+        .map(|t| t.name.clone())
+        .filter(|name| !types.iter().any(|(o, _)| o.as_ref() == Some(name)))
+        .collect::<Vec<_>>();
+    for name in removed {
+        doc.remove_custom_type(&name);
+    }
+    for (original, t) in types {
+        match original {
+            Some(name) => doc.update_custom_type(&name, t),
+            None => doc.add_custom_type(t),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+    app.translation_document = doc;
+    Ok(())
+}
+
+// Moves the current balloon's type `step` places in the document's type list.
+fn cycle_balloon_type(app: &mut LeScan, step: isize) {
+    let types = app.translation_document.balloon_types();
+    let next = match &app.selected_bln_type {
+        Some(current) => {
+            let i = types.iter().position(|t| *t == current.0).unwrap_or(0) as isize;
+            (i + step).rem_euclid(types.len() as isize) as usize
+        }
+        None => 0,
+    };
+    app.translation_document.balloons[app.current_balloon].btype = types[next].clone();
+    app.selected_bln_type = Some(BlnTypes(types[next].clone()));
+}
+
+// Types of the document and the `prefix:` typed into an editor to pick them.
+fn type_prefixes(doc: &rsff::Document) -> Vec<(TYPES, String)> {
+    doc.balloon_types()
+        .into_iter()
+        .map(|t| {
+            let prefix = format!("{}:", doc.type_prefix(&t));
+            (t, prefix)
+        })
+        .collect()
+}
+
+// Viewer rectangle of a balloon, if it's placed on a page
+fn balloon_rect(app: &LeScan, layout: &[PageRect], i: usize) -> Option<iced::Rectangle> {
+    let b = &app.translation_document.balloons[i];
+    region_rect(layout, b.page?, b.region.as_ref()?)
+}
+
+fn scroll_to_current_balloon(app: &mut LeScan) -> Task<Message> {
+    if app.current_img_tab != ImageTabs::Document {
+        return Task::none();
+    }
+
+    let (_, layout) = page_layout(&app.page_sizes, &app.page_zoom, app.img_viewport);
+    let Some(rect) = balloon_rect(app, &layout, app.current_balloon) else {
+        return Task::none();
+    };
+
+    let visible = iced::Rectangle::new(
+        iced::Point::new(app.img_scroll_offset.x, app.img_scroll_offset.y),
+        app.img_viewport,
+    );
+    if visible.contains(rect.position())
+        && visible.contains(iced::Point::new(rect.x + rect.width, rect.y + rect.height))
+    {
+        return Task::none();
+    }
+
+    // Leave some space above the balloon so its surroundings are visible too
+    let offset = widget::scrollable::AbsoluteOffset {
+        x: (rect.center_x() - app.img_viewport.width / 2.0).max(0.0),
+        y: (rect.y - app.img_viewport.height * 0.2).max(0.0),
+    };
+    app.img_programmatic_scroll = true;
+    widget::scrollable::scroll_to(app.img_scroller.clone(), offset)
+}
+
+// Selects the top most balloon in view, unless the current one is still visible
+fn follow_scroll(app: &mut LeScan) {
+    let (_, layout) = page_layout(&app.page_sizes, &app.page_zoom, app.img_viewport);
+    let top = app.img_scroll_offset.y;
+    let bottom = top + app.img_viewport.height;
+    let in_view = |r: &iced::Rectangle| r.y < bottom && r.y + r.height > top;
+
+    if balloon_rect(app, &layout, app.current_balloon).is_some_and(|r| in_view(&r)) {
+        return;
+    }
+
+    let first_in_view = (0..app.translation_document.balloons.len())
+        .filter_map(|i| balloon_rect(app, &layout, i).map(|r| (i, r)))
+        .filter(|(_, r)| in_view(r))
+        .min_by(|(_, a), (_, b)| a.y.total_cmp(&b.y))
+        .map(|(i, _)| i);
+
+    if let Some(i) = first_in_view {
+        save_editors(app);
+        select_balloon(app, i);
     }
 }
 
@@ -529,38 +1478,16 @@ fn handle_text_input_balloon_type_selection(
     text_editor_content: &mut text_editor::Content,
     current_balloon: &mut Balloon,
     selected_bln_type: &mut Option<BlnTypes>,
+    types: &[(TYPES, String)],
 ) {
     let editor_text = text_editor_content.text();
 
-    if editor_text.starts_with("ST:") {
-        if let Some(stripped) = editor_text.strip_prefix("ST:") {
-            *text_editor_content = text_editor::Content::with_text(stripped);
-            current_balloon.btype = TYPES::ST;
-            *selected_bln_type = Some(BlnTypes::ST);
-        }
-    } else if editor_text.starts_with("OT:") {
-        if let Some(stripped) = editor_text.strip_prefix("OT:") {
-            *text_editor_content = text_editor::Content::with_text(stripped);
-            current_balloon.btype = TYPES::OT;
-            *selected_bln_type = Some(BlnTypes::OT);
-        }
-    } else if editor_text.starts_with("[]:") {
-        if let Some(stripped) = editor_text.strip_prefix("[]:") {
-            *text_editor_content = text_editor::Content::with_text(stripped);
-            current_balloon.btype = TYPES::SQUARE;
-            *selected_bln_type = Some(BlnTypes::Square);
-        }
-    } else if editor_text.starts_with("():") {
-        if let Some(stripped) = editor_text.strip_prefix("():") {
-            *text_editor_content = text_editor::Content::with_text(stripped);
-            current_balloon.btype = TYPES::DIALOGUE;
-            *selected_bln_type = Some(BlnTypes::Dialogue);
-        }
-    } else if editor_text.starts_with("{}:") {
-        if let Some(stripped) = editor_text.strip_prefix("{}:") {
+    for (btype, prefix) in types {
+        if let Some(stripped) = editor_text.strip_prefix(prefix.as_str()) {
             *text_editor_content = text_editor::Content::with_text(stripped);
-            current_balloon.btype = TYPES::THINKING;
-            *selected_bln_type = Some(BlnTypes::Thinking);
+            current_balloon.btype = btype.clone();
+            *selected_bln_type = Some(BlnTypes(btype.clone()));
+            return;
         }
     }
 }
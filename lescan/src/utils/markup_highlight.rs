@@ -0,0 +1,68 @@
+use iced::advanced::text::highlighter::{Format, Highlighter};
+use iced::font::{Style, Weight};
+use iced::{Font, Theme};
+use rsff::markup::{self, TextStyle};
+use std::ops::Range;
+
+/// Highlights the inline markup of balloon lines in text editors.
+/// Tags are dimmed, the text between them is drawn in its style.
+pub struct MarkupHighlighter {
+    current_line: usize,
+}
+
+impl Highlighter for MarkupHighlighter {
+    type Settings = ();
+    type Highlight = Option<TextStyle>;
+
+    type Iterator<'a> = std::vec::IntoIter<(Range<usize>, Self::Highlight)>;
+
+    fn new(_settings: &Self::Settings) -> Self {
+        Self { current_line: 0 }
+    }
+
+    fn update(&mut self, _new_settings: &Self::Settings) {
+        self.current_line = 0;
+    }
+
+    fn change_line(&mut self, line: usize) {
+        self.current_line = self.current_line.min(line);
+    }
+
+    fn highlight_line(&mut self, line: &str) -> Self::Iterator<'_> {
+        self.current_line += 1;
+        markup::highlight(line).into_iter()
+    }
+
+    fn current_line(&self) -> usize {
+        self.current_line
+    }
+}
+
+pub fn markup_format(highlight: &Option<TextStyle>, theme: &Theme) -> Format<Font> {
+    let palette = theme.extended_palette();
+    let Some(style) = highlight else {
+        return Format {
+            color: Some(palette.background.strong.color),
+            font: None,
+        };
+    };
+
+    let font = (style.bold || style.italic).then(|| Font {
+        weight: if style.bold {
+            Weight::Bold
+        } else {
+            Weight::Normal
+        },
+        style: if style.italic {
+            Style::Italic
+        } else {
+            Style::Normal
+        },
+        ..Font::DEFAULT
+    });
+    // Underline, small caps and font switches can't be drawn by the editor, they are colored instead
+    let color = (style.underline || style.small_caps || style.font.is_some())
+        .then_some(palette.primary.strong.color);
+
+    Format { color, font }
+}
@@ -1,5 +1,8 @@
 pub mod bln;
 pub mod dialog_windows;
 pub mod handlers;
+pub mod markup_highlight;
+pub mod pages;
 pub mod panes;
 pub mod tabs;
+pub mod viewer;
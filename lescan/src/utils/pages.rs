@@ -0,0 +1,114 @@
+use iced::Task;
+use rsff::page_cache::{cache_page, prune_cache, tile_image, PageCacheOptions};
+use rsff::typeset::{page_texts, TypesetOptions, Typesetter};
+use rsff::Document;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::app::LeScan;
+use crate::message::Message;
+
+use rust_i18n::t;
+
+#[cfg(unix)]
+const PAGE_CACHE_PATH: &str = ".cache/lescan/pages";
+
+#[cfg(windows)]
+const PAGE_CACHE_PATH: &str = "AppData\\Local\\lescan\\pages";
+
+// Least recently used pages are removed above this size
+const PAGE_CACHE_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+
+pub fn page_sizes(doc: &Document) -> Vec<Option<(u32, u32)>> {
+    if let Some(img_paths) = &doc.images {
+        img_paths
+            .iter()
+            .map(|p| rsff::img_data::page_dimensions(p).ok())
+            .collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn page_cache_dir() -> PathBuf {
+    home::home_dir().unwrap().join(PAGE_CACHE_PATH)
+}
+
+/// Shrinks the page cache to its size limit in the background.
+/// Runs on startup, before any page is cached.
+pub fn prune_page_cache() -> Task<Message> {
+    Task::future(async {
+        let _ = prune_cache(page_cache_dir(), PAGE_CACHE_MAX_SIZE);
+    })
+    .then(|_| Task::none())
+}
+
+/// Refreshes the page data after the document's images change,
+/// and starts caching the pages in the background.
+pub fn reload_pages(app: &mut LeScan) -> Task<Message> {
+    app.page_sizes = page_sizes(&app.translation_document);
+    app.cached_pages = vec![None; app.page_sizes.len()];
+    app.typeset_pages = vec![None; app.page_sizes.len()];
+    app.img_scroll_offset = iced::widget::scrollable::AbsoluteOffset::default();
+
+    let Some(img_paths) = &app.translation_document.images else {
+        return Task::none();
+    };
+    let cache_dir = page_cache_dir();
+
+    let typeset = if app.typeset_preview {
+        render_typeset(app, 0..img_paths.len())
+    } else {
+        Task::none()
+    };
+
+    let cache = Task::batch(img_paths.iter().cloned().enumerate().map(|(i, path)| {
+        let cache_dir = cache_dir.clone();
+        Task::perform(
+            async move {
+                let cached = cache_page(&path, &cache_dir, &PageCacheOptions::default())
+                    .map_err(|e| e.to_string());
+                (path, cached)
+            },
+            move |(path, cached)| Message::PageCached(i, path, cached),
+        )
+    }));
+
+    Task::batch([cache, typeset])
+}
+
+/// Loads the typesetting font set in the settings.
+pub fn load_typesetter(font: &str) -> Result<Arc<Typesetter>, String> {
+    if font.trim().is_empty() {
+        return Err(t!("errors.typeset_error.no_font").into_owned());
+    }
+    Typesetter::from_file(font.trim())
+        .map(Arc::new)
+        .map_err(|e| e.to_string())
+}
+
+/// Typesets the given pages with the current texts in the background.
+pub fn render_typeset(app: &LeScan, pages: impl IntoIterator<Item = usize>) -> Task<Message> {
+    let (Some(typesetter), Some(img_paths)) = (&app.typesetter, &app.translation_document.images)
+    else {
+        return Task::none();
+    };
+
+    Task::batch(pages.into_iter().filter_map(|page| {
+        let path = img_paths.get(page)?.clone();
+        let texts = page_texts(&app.translation_document, page);
+        let typesetter = typesetter.clone();
+        Some(Task::perform(
+            async move {
+                let img = typesetter
+                    .render_page_file(&path, &texts, &TypesetOptions::default())
+                    .map_err(|e| e.to_string())?;
+                Ok(tile_image(img, &PageCacheOptions::default())
+                    .into_iter()
+                    .map(|tile| (tile.width(), tile.height(), tile.into_raw()))
+                    .collect())
+            },
+            move |tiles| Message::TypesetRendered(page, tiles),
+        ))
+    }))
+}
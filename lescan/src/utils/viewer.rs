@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+const ZOOM_STEP: f32 = 1.25;
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 8.0;
+
+/// How a page is sized before the zoom factor is applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FitMode {
+    #[default]
+    FitWidth,
+    FitHeight,
+    ActualSize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ZoomAction {
+    In,
+    Out,
+    Fit(FitMode),
+}
+
+/// Zoom state of the document page viewer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PageZoom {
+    pub fit: FitMode,
+    pub zoom: f32,
+}
+
+impl Default for PageZoom {
+    fn default() -> Self {
+        Self {
+            fit: FitMode::FitWidth,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl PageZoom {
+    pub fn apply(&mut self, action: ZoomAction) {
+        match action {
+            ZoomAction::In => self.zoom = (self.zoom * ZOOM_STEP).min(MAX_ZOOM),
+            ZoomAction::Out => self.zoom = (self.zoom / ZOOM_STEP).max(MIN_ZOOM),
+            ZoomAction::Fit(fit) => {
+                self.fit = fit;
+                self.zoom = 1.0;
+            }
+        }
+    }
+
+    /// Width to draw a page with, for the given page size (in pixels) and viewport.
+    pub fn page_width(&self, page_size: (u32, u32), viewport: iced::Size) -> f32 {
+        let (w, h) = (page_size.0.max(1) as f32, page_size.1.max(1) as f32);
+        let base = match self.fit {
+            FitMode::FitWidth => viewport.width,
+            FitMode::FitHeight => w * viewport.height / h,
+            FitMode::ActualSize => w,
+        };
+        base * self.zoom
+    }
+}
+
+/// Position of a page inside the viewer's scrollable content.
+#[derive(Debug, Clone, Copy)]
+pub struct PageRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    // Viewer pixels per page pixel
+    pub scale: f32,
+}
+
+/// Lays out the pages top to bottom, centered horizontally.
+/// Returns the width of the content and the rectangle of every page.
+pub fn page_layout(
+    page_sizes: &[Option<(u32, u32)>],
+    zoom: &PageZoom,
+    viewport: iced::Size,
+) -> (f32, Vec<PageRect>) {
+    let sizes = page_sizes
+        .iter()
+        .map(|s| {
+            // Unreadable pages are drawn as squares as their size is unknown
+            let s = s.unwrap_or((viewport.width as u32, viewport.width as u32));
+            let width = zoom.page_width(s, viewport);
+            let scale = width / s.0.max(1) as f32;
+            (width, s.1 as f32 * scale, scale)
+        })
+        .collect::<Vec<_>>();
+
+    let content_width = sizes
+        .iter()
+        .map(|s| s.0)
+        .fold(viewport.width, f32::max);
+
+    let mut y = 0.0;
+    let rects = sizes
+        .into_iter()
+        .map(|(width, height, scale)| {
+            let r = PageRect {
+                x: (content_width - width) / 2.0,
+                y,
+                width,
+                height,
+                scale,
+            };
+            y += height;
+            r
+        })
+        .collect();
+
+    (content_width, rects)
+}
+
+/// Rectangle of a balloon region inside the viewer's scrollable content.
+pub fn region_rect(
+    layout: &[PageRect],
+    page: usize,
+    region: &rsff::img_data::Region,
+) -> Option<iced::Rectangle> {
+    let p = layout.get(page)?;
+    Some(iced::Rectangle {
+        x: p.x + region.x as f32 * p.scale,
+        y: p.y + region.y as f32 * p.scale,
+        width: region.width as f32 * p.scale,
+        height: region.height as f32 * p.scale,
+    })
+}
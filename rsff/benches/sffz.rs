@@ -0,0 +1,82 @@
+//! Save and open times of sffz and sffb files with every codec.
+//! `sffb_text` only reads the text of the sffb file, without the images.
+//!
+//! The document is a chapter of 60 balloons with a cropped png image each,
+//! the images are noise so they don't compress much, like real page crops.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::{ImageFormat, RgbImage};
+use rsff::codec::Codec;
+use rsff::{sffb, Balloon, Document};
+
+fn chapter() -> Document {
+    let mut seed: u32 = 0x5eed;
+    let mut noise = move || {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (seed >> 24) as u8
+    };
+
+    let mut d = Document::default();
+    for i in 0..60 {
+        let img = RgbImage::from_fn(240, 160, |_, _| image::Rgb([noise(), noise(), noise()]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut png, ImageFormat::Png).unwrap();
+
+        let mut b = Balloon::default();
+        b.add_tl(format!("Translation of the balloon number {}.", i));
+        b.add_pr(format!(
+            "Proofread translation of the balloon number {}.",
+            i
+        ));
+        b.add_comment("Keep it short.");
+        b.page = Some(i / 6);
+        b.add_image("png".to_string(), png.into_inner());
+        d.balloons.push(b);
+    }
+    d
+}
+
+fn codecs(c: &mut Criterion) {
+    let d = chapter();
+    let json_len = d.to_json().len() as u64;
+    for codec in Codec::ALL {
+        println!(
+            "{}: sffz {} bytes, sffb {} bytes",
+            codec,
+            d.to_sffz(codec).unwrap().len(),
+            d.to_sffb(codec).unwrap().len()
+        );
+    }
+
+    let mut save = c.benchmark_group("save");
+    save.throughput(Throughput::Bytes(json_len));
+    for codec in Codec::ALL {
+        save.bench_with_input(BenchmarkId::new("sffz", codec), &codec, |b, &codec| {
+            b.iter(|| d.to_sffz(codec).unwrap())
+        });
+        save.bench_with_input(BenchmarkId::new("sffb", codec), &codec, |b, &codec| {
+            b.iter(|| d.to_sffb(codec).unwrap())
+        });
+    }
+    save.finish();
+
+    let mut open = c.benchmark_group("open");
+    open.throughput(Throughput::Bytes(json_len));
+    for codec in Codec::ALL {
+        let sffz = d.to_sffz(codec).unwrap();
+        open.bench_with_input(BenchmarkId::new("sffz", codec), &sffz, |b, sffz| {
+            b.iter(|| Document::from_sffz(sffz).unwrap())
+        });
+        let sffb = d.to_sffb(codec).unwrap();
+        open.bench_with_input(BenchmarkId::new("sffb", codec), &sffb, |b, sffb| {
+            b.iter(|| Document::from_sffb(sffb).unwrap())
+        });
+        open.bench_with_input(BenchmarkId::new("sffb_text", codec), &sffb, |b, sffb| {
+            b.iter(|| sffb::read_text(&mut &sffb[..]).unwrap())
+        });
+    }
+    open.finish();
+}
+
+criterion_group!(benches, codecs);
+criterion_main!(benches);
@@ -1,5 +1,7 @@
-use crate::consts::TYPES;
-use crate::img_data::BalloonImage;
+use crate::consts::{BalloonStatus, TYPES};
+use crate::img_data::{BalloonImage, Region};
+use crate::sfx::Sfx;
+use crate::style::BalloonStyle;
 use serde::{Deserialize, Serialize};
 
 /// A struct represents a balloon.
@@ -18,8 +20,28 @@ pub struct Balloon {
     pub tl_content: Vec<String>,
     pub pr_content: Vec<String>,
     pub comments: Vec<String>,
+    /// Original text of the balloon, typed in or recognized with OCR.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_content: Vec<String>,
     pub btype: TYPES,
+    #[serde(default, skip_serializing_if = "BalloonStatus::is_pending")]
+    pub status: BalloonStatus,
     pub balloon_img: Option<BalloonImage>,
+    /// Index of the page (in `Document.images`) this balloon is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+    /// Balloon's area on its page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+    /// Name of the document's style preset to use instead of the type's preset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Style attributes overriding the preset's.
+    #[serde(default, skip_serializing_if = "BalloonStyle::is_empty")]
+    pub style: BalloonStyle,
+    /// Sound effect data, the balloon is a sound effect if it's set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sfx: Option<Sfx>,
 }
 
 impl Balloon {
@@ -49,6 +71,29 @@ impl Balloon {
         self.balloon_img = None;
     }
 
+    /// Places the balloon on a page.
+    /// `page` is the index of the page in `Document.images`, `region` is in page pixels.
+    pub fn set_region(&mut self, page: usize, region: Region) {
+        self.page = Some(page);
+        self.region = Some(region);
+    }
+
+    /// Removes the balloon's page placement.
+    pub fn remove_region(&mut self) {
+        self.page = None;
+        self.region = None;
+    }
+
+    /// Balloon has no text, sound effect, image or page placement.
+    /// Empty lines left by the editors don't count as text.
+    pub fn is_blank(&self) -> bool {
+        self.tl_chars() + self.pr_chars() + self.comments_chars() == 0
+            && self.source_content.iter().all(|l| l.is_empty())
+            && self.sfx.is_none()
+            && self.balloon_img.is_none()
+            && self.region.is_none()
+    }
+
     /// Total character count of all translation content.
     /// *(Spaces included.)*
     pub fn tl_chars(&self) -> usize {
@@ -98,6 +143,16 @@ impl Balloon {
     pub fn add_comment(&mut self, comment: impl Into<String>) {
         self.comments.push(comment.into());
     }
+
+    /// Balloon text on a single line, without the type prefix.
+    /// Uses pr content if balloon has pr content, otherwise uses tl content.
+    pub fn content_line(&self) -> String {
+        if self.pr_chars() != 0 {
+            self.pr_content.join(" || ")
+        } else {
+            self.tl_content.join(" || ")
+        }
+    }
 }
 
 /// Generates stringified version of the balloon.
@@ -106,21 +161,18 @@ impl Balloon {
 /// **IMPORTANT NOTE:** ***Metadata and balloon_img are lost during the creation of the text!!!***
 impl std::fmt::Display for Balloon {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let type_str = match self.btype {
-            TYPES::DIALOGUE => "(): ",
-            TYPES::OT => "OT: ",
-            TYPES::SQUARE => "[]: ",
-            TYPES::ST => "ST: ",
-            TYPES::THINKING => "{}: ",
+        if let Some(sfx) = &self.sfx {
+            return write!(f, "SFX: {}", sfx);
+        }
+
+        // Custom type prefixes are kept by the document, a lone balloon only knows
+        // the type's name. Use `Document::balloon_line` for a line the document can read.
+        let type_str = match &self.btype {
+            TYPES::CUSTOM(name) => name.as_str(),
+            t => t.builtin_prefix().unwrap_or_default(),
         };
 
-        // If balloon has pr content, generate balloon text from pr content
-        // else, generate balloon text from tl content
-        if self.pr_chars() != 0 {
-            write!(f, "{}{}", type_str, self.pr_content.join(" || "))
-        } else {
-            write!(f, "{}{}", type_str, self.tl_content.join(" || "))
-        }
+        write!(f, "{}: {}", type_str, self.content_line())
     }
 }
 
@@ -144,6 +196,49 @@ mod ballon_tests {
         b.remove_img();
     }
 
+    #[test]
+    fn balloon_is_blank() {
+        let mut b = Balloon::default();
+        b.add_tl("");
+        assert!(b.is_blank());
+
+        b.add_comment("note");
+        assert!(!b.is_blank());
+    }
+
+    #[test]
+    fn balloon_is_blank_source() {
+        let mut b = Balloon {
+            source_content: vec![String::new()],
+            ..Default::default()
+        };
+        assert!(b.is_blank());
+
+        b.source_content.push("おい!".to_string());
+        assert!(!b.is_blank());
+    }
+
+    #[test]
+    fn balloon_is_blank_sfx() {
+        let b = Balloon {
+            sfx: Some(crate::sfx::Sfx::default()),
+            ..Default::default()
+        };
+        assert!(!b.is_blank());
+    }
+
+    #[test]
+    fn balloon_set_region() {
+        let mut b = Balloon::default();
+        b.set_region(2, crate::img_data::Region::new(1, 2, 3, 4));
+        assert_eq!(b.page, Some(2));
+        assert_eq!(b.region, Some(crate::img_data::Region::new(1, 2, 3, 4)));
+
+        b.remove_region();
+        assert_eq!(b.page, None);
+        assert_eq!(b.region, None);
+    }
+
     #[test]
     fn balloon_get_tl_chars() {
         let mut b = Balloon::default();
@@ -0,0 +1,157 @@
+//! Compression codecs of sffz files.
+//!
+//! zlib files are plain zlib streams of the JSON document, like the ones of older versions,
+//! so they are still readable by them. Other codecs start with the [`SFFZ_MAGIC`] bytes and
+//! a byte naming the codec, followed by the compressed document. A zlib stream can't start
+//! with the magic bytes, so the two are told apart.
+
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+/// First bytes of an sffz file with a codec header.
+pub const SFFZ_MAGIC: &[u8; 4] = b"SFFZ";
+
+/// Codecs an sffz file can be compressed with. Default value is `ZlibBest`.
+///
+/// `ZlibFast`, `ZlibBest`: zlib with the fastest or the smallest output, readable by older versions\
+/// `Zstd`: zstd with its default level, much faster than zlib at a similar size.
+/// Versions without codec headers can't read it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Codec {
+    ZlibFast,
+    #[default]
+    ZlibBest,
+    Zstd,
+}
+
+impl Codec {
+    pub const ALL: [Self; 3] = [Codec::ZlibFast, Codec::ZlibBest, Codec::Zstd];
+
+    /// Byte of the codec in the sffz header.
+    fn id(&self) -> u8 {
+        match self {
+            Codec::ZlibFast => 0,
+            Codec::ZlibBest => 1,
+            Codec::Zstd => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.id() == id)
+    }
+}
+
+impl std::fmt::Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Codec::ZlibFast => write!(f, "zlib (fast)"),
+            Codec::ZlibBest => write!(f, "zlib (best)"),
+            Codec::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Codec of the sffz data with a header, `None` for zlib data, which has no header.
+pub fn detect(data: &[u8]) -> Option<Codec> {
+    match data.strip_prefix(SFFZ_MAGIC) {
+        Some(rest) => rest.first().copied().and_then(Codec::from_id),
+        None => None,
+    }
+}
+
+/// Compresses `data` with the codec. Codecs other than zlib get the sffz header.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::codec::{compress, decompress, detect, Codec};
+///
+/// let sffz = compress(b"{}", Codec::Zstd).unwrap();
+/// assert_eq!(detect(&sffz), Some(Codec::Zstd));
+/// assert_eq!(decompress(&sffz).unwrap(), b"{}");
+///
+/// let sffz = compress(b"{}", Codec::ZlibFast).unwrap();
+/// assert_eq!(detect(&sffz), None);
+/// assert_eq!(decompress(&sffz).unwrap(), b"{}");
+/// ```
+pub fn compress(data: &[u8], codec: Codec) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() / 2);
+    match codec {
+        Codec::ZlibFast | Codec::ZlibBest => {
+            let level = match codec {
+                Codec::ZlibFast => Compression::fast(),
+                _ => Compression::best(),
+            };
+            let mut enc = ZlibEncoder::new(out, level);
+            enc.write_all(data)?;
+            enc.finish()
+        }
+        Codec::Zstd => {
+            out.extend_from_slice(SFFZ_MAGIC);
+            out.push(codec.id());
+            zstd::stream::copy_encode(data, &mut out, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+            Ok(out)
+        }
+    }
+}
+
+/// Decompresses sffz data, the codec is read from the header.
+/// Data without a header is read as zlib.
+pub fn decompress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    if !data.starts_with(SFFZ_MAGIC) {
+        ZlibDecoder::new(data).read_to_end(&mut out)?;
+        return Ok(out);
+    }
+
+    let codec = detect(data).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "Unknown sffz codec!")
+    })?;
+    let body = &data[SFFZ_MAGIC.len() + 1..];
+    match codec {
+        Codec::ZlibFast | Codec::ZlibBest => {
+            ZlibDecoder::new(body).read_to_end(&mut out)?;
+        }
+        Codec::Zstd => zstd::stream::copy_decode(body, &mut out)?,
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod codec_tests {
+    use super::*;
+
+    #[test]
+    fn codec_round_trip() {
+        let data = "{\"balloons\":[]}".repeat(100);
+        for codec in Codec::ALL {
+            let sffz = compress(data.as_bytes(), codec).unwrap();
+            let header = (codec == Codec::Zstd).then_some(codec);
+            assert_eq!(detect(&sffz), header);
+            assert!(sffz.len() < data.len());
+            assert_eq!(decompress(&sffz).unwrap(), data.as_bytes());
+        }
+    }
+
+    #[test]
+    fn codec_legacy_zlib() {
+        let mut enc = ZlibEncoder::new(Vec::new(), Compression::best());
+        enc.write_all(b"{}").unwrap();
+        let legacy = enc.finish().unwrap();
+
+        // zlib files are the same as the ones of older versions
+        assert_eq!(compress(b"{}", Codec::default()).unwrap(), legacy);
+        assert_eq!(detect(&legacy), None);
+        assert_eq!(decompress(&legacy).unwrap(), b"{}");
+        // zlib data with a header is read too
+        let mut headered = b"SFFZ\x01".to_vec();
+        headered.extend_from_slice(&legacy);
+        assert_eq!(decompress(&headered).unwrap(), b"{}");
+        assert!(decompress(b"SFFZ\x09").is_err());
+        assert!(decompress(b"SFFZ").is_err());
+    }
+}
@@ -0,0 +1,423 @@
+//! Importers for the script formats of other scanlation tools.
+//!
+//! `Paged txt`: Plain scripts with page markers like `Page 1` or `#01`, one balloon per line.
+//! Balloons can be numbered (`1.`, `2)`, `3:`, `4 -`), lines without a number then continue
+//! the previous balloon. Lines before the first marker or numbered balloon are the document's info.\
+//! `LabelPlus`: Txt files of LabelPlus, with `>>>>>>>>[001.jpg]<<<<<<<<` page headers.
+//! Balloons of the second group (outside of balloons) become over-texts.\
+//! `BallonsTranslator`: JSON projects of BallonsTranslator, with regions and text styles.
+//!
+//! [`Document::open`] picks the importer by the file's extension and contents.
+
+use serde::Deserialize;
+
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+use crate::img_data::Region;
+use crate::sfx::TXT_SFX_MARKER;
+use crate::style::{BalloonStyle, Color, TextAlignment};
+use crate::{Document, Result, TXT_PAGE_MARKER, TXT_STYLE_MARKER, TXT_TYPE_MARKER};
+
+const LABELPLUS_PAGE_START: &str = ">>>>>>>>[";
+const LABELPLUS_PAGE_END: &str = "]<<<<<<<<";
+const LABELPLUS_BALLOON_START: &str = "----------------[";
+
+/// Script formats of other tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptFormat {
+    PagedTxt,
+    LabelPlus,
+    BallonsTranslator,
+}
+
+/// Page number of a page marker line, starting from 1.
+///
+/// Markers are `Page <n>` or `#<n>`, case-insensitive, optionally followed by a colon and
+/// surrounded by decorations like `=== Page 1 ===`.
+pub fn page_marker(line: &str) -> Option<usize> {
+    let line = line.trim_matches(|c: char| c.is_whitespace() || "=-*[]<>".contains(c));
+    let lower = line.to_lowercase();
+    let number = match lower.strip_prefix("page") {
+        Some(rest) if rest.starts_with(|c: char| c.is_whitespace() || c.is_ascii_digit()) => rest,
+        Some(_) => return None,
+        None => lower.strip_prefix('#')?,
+    };
+    let number = number.trim().trim_end_matches([':', '.']).trim_end();
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Splits a numbered balloon line like `1. Hey!` into its number and text.
+pub fn numbered_line(line: &str) -> Option<(usize, &str)> {
+    let line = line.trim();
+    let digits = line.find(|c: char| !c.is_ascii_digit())?;
+    let number = line[..digits].parse().ok()?;
+    let rest = line[digits..].trim_start();
+    let text = rest
+        .strip_prefix(['.', ')', ':', '-'])
+        .filter(|text| text.is_empty() || text.starts_with(char::is_whitespace))?;
+    Some((number, text.trim()))
+}
+
+// Lines of rsff's own txt format, headers of the built-in types and section markers.
+fn is_native_line(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with(TXT_TYPE_MARKER)
+        || line.starts_with(TXT_PAGE_MARKER)
+        || line.starts_with(TXT_STYLE_MARKER)
+        || line == TXT_SFX_MARKER
+        || TYPES::BUILTIN.iter().any(|t| {
+            t.builtin_prefix()
+                .and_then(|prefix| line.strip_prefix(prefix))
+                .is_some_and(|rest| rest.starts_with(':'))
+        })
+}
+
+/// Detects the format of a txt script. `None` for rsff's own txt format.
+pub fn detect_txt(txt: &str) -> Option<ScriptFormat> {
+    let lines = || txt.lines().filter(|l| !l.trim().is_empty());
+    if lines().any(|l| l.trim().starts_with(LABELPLUS_PAGE_START)) {
+        return Some(ScriptFormat::LabelPlus);
+    }
+    if lines().any(is_native_line) {
+        return None;
+    }
+    lines()
+        .any(|l| page_marker(l).is_some() || numbered_line(l).is_some())
+        .then_some(ScriptFormat::PagedTxt)
+}
+
+/// Reads a plain script with page markers and/or numbered balloons.
+pub fn read_paged_txt(txt: &str) -> Document {
+    let mut d = Document::default();
+    let lines = Vec::from_iter(txt.lines().map(str::trim).filter(|l| !l.is_empty()));
+    let start = lines
+        .iter()
+        .position(|l| page_marker(l).is_some() || numbered_line(l).is_some())
+        .unwrap_or_default();
+    let info = &lines[..start];
+
+    let mut page = None;
+    // Numbered balloons are continued by lines without numbers
+    let mut numbered = false;
+
+    for line in &lines[start..] {
+        if let Some(number) = page_marker(line) {
+            page = Some(number.saturating_sub(1));
+            numbered = false;
+            continue;
+        }
+
+        match numbered_line(line) {
+            Some((_, text)) => {
+                numbered = true;
+                d.add_balloon(Balloon {
+                    tl_content: vec![text.to_string()],
+                    page,
+                    ..Default::default()
+                });
+            }
+            None if numbered => d.balloons.last_mut().unwrap().add_tl(*line),
+            None => d.add_balloon(Balloon {
+                tl_content: vec![line.to_string()],
+                page,
+                ..Default::default()
+            }),
+        }
+    }
+
+    if !info.is_empty() {
+        d.METADATA_INFO = info.join("\n");
+    }
+    d
+}
+
+/// Reads a LabelPlus script. Page images are the file names of the script.
+///
+/// Balloon positions are relative to the page sizes, which the script doesn't have,
+/// so balloons aren't placed.
+pub fn read_labelplus(txt: &str) -> Result<Document> {
+    let mut d = Document::default();
+    let mut images = Vec::new();
+    let mut header = Vec::new();
+    let mut in_header = true;
+
+    for line in txt.lines() {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed
+            .strip_prefix(LABELPLUS_PAGE_START)
+            .and_then(|rest| rest.strip_suffix(LABELPLUS_PAGE_END))
+        {
+            in_header = false;
+            images.push(name.to_string());
+        } else if let Some(rest) = trimmed.strip_prefix(LABELPLUS_BALLOON_START) {
+            if images.is_empty() {
+                return Err(format!("Balloon before the first page: {}", trimmed).into());
+            }
+            // ----------------[1]----------------[x,y,group]
+            let group = rest
+                .rsplit_once('[')
+                .and_then(|(_, pos)| pos.strip_suffix(']'))
+                .and_then(|pos| pos.rsplit(',').next())
+                .map(str::trim);
+            d.add_balloon(Balloon {
+                btype: match group {
+                    Some("2") => TYPES::OT,
+                    _ => TYPES::DIALOGUE,
+                },
+                page: Some(images.len() - 1),
+                ..Default::default()
+            });
+        } else if in_header {
+            header.push(trimmed);
+        } else if let Some(b) = d.balloons.last_mut() {
+            b.add_tl(trimmed);
+        }
+    }
+
+    for b in d.balloons.iter_mut() {
+        while b.tl_content.last().is_some_and(|l| l.is_empty()) {
+            b.tl_content.pop();
+        }
+    }
+    // The header is `1,0`, `-`, the group names, `-` and the comment of the script
+    let comment = header.iter().skip_while(|l| **l != "-").skip(1);
+    let comment = comment.skip_while(|l| **l != "-").skip(1);
+    let comment = comment.copied().collect::<Vec<_>>().join("\n");
+    if !comment.trim().is_empty() {
+        d.METADATA_INFO = comment.trim().to_string();
+    }
+    d.images = Some(images);
+    Ok(d)
+}
+
+#[derive(Deserialize)]
+struct BtProject {
+    #[serde(default)]
+    directory: String,
+    // Pages are in reading order in the project
+    #[serde(deserialize_with = "crate::serde_overwrites::ordered_map::deserialize")]
+    pages: Vec<(String, Vec<BtBlock>)>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct BtBlock {
+    xyxy: Option<[f64; 4]>,
+    text: Vec<String>,
+    translation: String,
+    font_family: String,
+    bold: bool,
+    italic: bool,
+    alignment: Option<u8>,
+    fg_colors: Option<[f64; 3]>,
+    fg_r: Option<f64>,
+    fg_g: Option<f64>,
+    fg_b: Option<f64>,
+    angle: f64,
+}
+
+impl BtBlock {
+    fn color(&self) -> Option<Color> {
+        let [r, g, b] = self
+            .fg_colors
+            .or_else(|| Some([self.fg_r?, self.fg_g?, self.fg_b?]))?;
+        Some(Color::new(r as u8, g as u8, b as u8))
+    }
+
+    fn style(&self) -> BalloonStyle {
+        BalloonStyle {
+            font_family: Some(self.font_family.clone()).filter(|f| !f.is_empty()),
+            bold: self.bold.then_some(true),
+            italic: self.italic.then_some(true),
+            alignment: match self.alignment {
+                Some(0) => Some(TextAlignment::Left),
+                Some(1) => Some(TextAlignment::Center),
+                Some(2) => Some(TextAlignment::Right),
+                _ => None,
+            },
+            color: self.color(),
+            // Counter-clockwise in BallonsTranslator
+            rotation: Some(-self.angle.round() as i32).filter(|r| *r != 0),
+            ..Default::default()
+        }
+    }
+}
+
+/// Reads a BallonsTranslator project. Pages keep the order of the project,
+/// projects without pages are an error.
+pub fn read_ballons_translator(json: &str) -> Result<Document> {
+    let project = serde_json::from_str::<BtProject>(json)?;
+    if project.pages.is_empty() {
+        return Err("The BallonsTranslator project has no pages.".into());
+    }
+    let mut d = Document::default();
+    let dir = std::path::Path::new(&project.directory);
+
+    let mut images = Vec::new();
+    for (page, (name, blocks)) in project.pages.into_iter().enumerate() {
+        images.push(dir.join(&name).to_string_lossy().to_string());
+        for block in blocks {
+            d.add_balloon(Balloon {
+                source_content: block.text.clone(),
+                tl_content: block.translation.lines().map(String::from).collect(),
+                page: Some(page),
+                region: block.xyxy.map(|[x1, y1, x2, y2]| {
+                    let corner = |x: f64, y: f64| (x.max(0.0) as u32, y.max(0.0) as u32);
+                    Region::from_corners(corner(x1, y1), corner(x2, y2))
+                }),
+                style: block.style(),
+                ..Default::default()
+            });
+        }
+    }
+    d.images = Some(images);
+    Ok(d)
+}
+
+#[cfg(test)]
+mod community_tests {
+    use super::*;
+
+    const PAGED: &str = include_str!("../tests/fixtures/paged_script.txt");
+    const NUMBERED: &str = include_str!("../tests/fixtures/numbered_script.txt");
+    const LABELPLUS: &str = include_str!("../tests/fixtures/labelplus.txt");
+    const BALLONS_TRANSLATOR: &str = include_str!("../tests/fixtures/ballons_translator.json");
+
+    fn tl(d: &Document) -> Vec<(Option<usize>, String)> {
+        d.balloons
+            .iter()
+            .map(|b| (b.page, b.tl_content.join("\n")))
+            .collect()
+    }
+
+    #[test]
+    fn community_markers() {
+        assert_eq!(page_marker("Page 1"), Some(1));
+        assert_eq!(page_marker("PAGE 02:"), Some(2));
+        assert_eq!(page_marker("=== Page 3 ==="), Some(3));
+        assert_eq!(page_marker("#04"), Some(4));
+        assert_eq!(page_marker("Pages 1"), None);
+        assert_eq!(page_marker("Page one"), None);
+        assert_eq!(page_marker("#TYPE HW #000000 Handwritten"), None);
+
+        assert_eq!(numbered_line("1. Hey!"), Some((1, "Hey!")));
+        assert_eq!(numbered_line("12) Hey!"), Some((12, "Hey!")));
+        assert_eq!(numbered_line("3 - Hey!"), Some((3, "Hey!")));
+        assert_eq!(numbered_line("1.5 seconds"), None);
+        assert_eq!(numbered_line("100% sure"), None);
+
+        assert_eq!(detect_txt(PAGED), Some(ScriptFormat::PagedTxt));
+        assert_eq!(detect_txt(NUMBERED), Some(ScriptFormat::PagedTxt));
+        assert_eq!(detect_txt(LABELPLUS), Some(ScriptFormat::LabelPlus));
+        assert_eq!(detect_txt("(): Hey!\n\n1. Not a list"), None);
+        assert_eq!(detect_txt("Hey!\nWhat?"), None);
+        assert_eq!(detect_txt("#PAGE 1\n\nHey!\n\n#PAGE 2\n\nWhat?"), None);
+        assert_eq!(tl(&read_paged_txt("Hey!\nWhat?")).len(), 2);
+    }
+
+    #[test]
+    fn community_paged_txt() {
+        let d = read_paged_txt(PAGED);
+        assert_eq!(d.METADATA_INFO, "Chapter 12 - translated by Team Num");
+        assert_eq!(
+            tl(&d),
+            vec![
+                (Some(0), "Hey!".to_string()),
+                (Some(0), "What are you doing here?".to_string()),
+                (Some(1), "I could ask you the same.".to_string()),
+                (Some(1), "Whatever.".to_string()),
+                (Some(2), "...".to_string()),
+            ]
+        );
+
+        let d = read_paged_txt(NUMBERED);
+        assert_eq!(d.METADATA_INFO, "Chapter 12");
+        assert_eq!(
+            tl(&d),
+            vec![
+                (Some(0), "Hey!".to_string()),
+                (Some(0), "What are you\ndoing here?".to_string()),
+                (Some(0), "I could ask you the same.".to_string()),
+                (Some(1), "Whatever.".to_string()),
+                (Some(1), "[sfx] BAM".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn community_labelplus() {
+        let d = read_labelplus(LABELPLUS).unwrap();
+        assert_eq!(d.METADATA_INFO, "Translated by Team Num");
+        assert_eq!(
+            d.images,
+            Some(vec!["001.jpg".to_string(), "002.jpg".to_string()])
+        );
+        assert_eq!(
+            tl(&d),
+            vec![
+                (Some(0), "Hey!\nWhat are you doing here?".to_string()),
+                (Some(0), "Narration".to_string()),
+                (Some(1), "Whatever.".to_string()),
+            ]
+        );
+        assert_eq!(d.balloons[0].btype, TYPES::DIALOGUE);
+        assert_eq!(d.balloons[1].btype, TYPES::OT);
+
+        assert!(read_labelplus("----------------[1]----------------[0.5,0.5,1]\nHey!").is_err());
+    }
+
+    #[test]
+    fn community_ballons_translator() {
+        let d = read_ballons_translator(BALLONS_TRANSLATOR).unwrap();
+        let images = d.images.clone().unwrap();
+        assert!(images[0].ends_with("001.jpg") && images[0].starts_with("/scans/chapter12"));
+        assert!(images[1].ends_with("002.jpg"));
+
+        assert_eq!(
+            tl(&d),
+            vec![
+                (Some(0), "Hey!\nWhat are you doing here?".to_string()),
+                (Some(0), "".to_string()),
+                (Some(1), "Whatever.".to_string()),
+            ]
+        );
+        let first = &d.balloons[0];
+        assert_eq!(first.source_content, vec!["おい!", "ここで何してる?"]);
+        assert_eq!(first.region, Some(Region::new(10, 20, 100, 70)));
+        assert_eq!(first.style.alignment, Some(TextAlignment::Center));
+        assert_eq!(first.style.color, Some(Color::BLACK));
+        assert_eq!(first.style.font_family, None);
+
+        let last = &d.balloons[2].style;
+        assert_eq!(last.font_family.as_deref(), Some("CC Wild Words"));
+        assert_eq!(last.bold, Some(true));
+        assert_eq!(last.italic, None);
+        assert_eq!(last.alignment, Some(TextAlignment::Left));
+        assert_eq!(last.color, Some(Color::new(255, 0, 0)));
+
+        assert!(read_ballons_translator("{\"pages\": 1}").is_err());
+        assert!(read_ballons_translator("{}").is_err());
+        assert!(read_ballons_translator("{\"pages\": {}}").is_err());
+        assert!(read_ballons_translator("{\"theme\": \"dark\"}").is_err());
+
+        // Pages aren't sorted by their names
+        let d = read_ballons_translator("{\"pages\": {\"10.jpg\": [], \"2.jpg\": []}}").unwrap();
+        assert_eq!(
+            d.images,
+            Some(vec!["10.jpg".to_string(), "2.jpg".to_string()])
+        );
+    }
+
+    #[test]
+    fn community_open() {
+        let d = Document::open("tests/fixtures/numbered_script.txt").unwrap();
+        assert_eq!(d.len(), 5);
+        let d = Document::open("tests/fixtures/labelplus.txt").unwrap();
+        assert_eq!(d.balloons[1].btype, TYPES::OT);
+        let d = Document::open("tests/fixtures/ballons_translator.json").unwrap();
+        assert_eq!(d.images.unwrap().len(), 2);
+    }
+}
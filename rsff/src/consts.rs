@@ -1,22 +1,34 @@
 use serde::{Deserialize, Serialize};
 
+use crate::style::Color;
+
 /// Supported output file types.
 ///
 /// `RAW`: Raw XML string
-/// `ZLIB`: Compressed XML
-/// `TXT`: Raw, lossy .txt file
+/// `ZLIB`: Compressed XML\
+/// `BIN`: Compressed JSON with raw balloon images, see `sffb`\
+/// `TXT`: Raw, lossy .txt file\
+/// `DOCX`: Word document with a table of balloons for every page\
+/// `CSV`, `XLSX`: Spreadsheet with a row for every balloon\
+/// `HTML`, `MD`: Script for reviewers with a table of balloons for every page
 #[derive(Clone, Serialize, Deserialize)]
 pub enum OUT {
     RAW,
     ZLIB,
+    BIN,
     TXT,
     DOCX,
+    CSV,
+    XLSX,
+    HTML,
+    MD,
 }
 
 /// Balloon types. Default value is `DIALOGUE`.
 ///
 /// ST: Sub-text\
-/// OT: Over-text
+/// OT: Over-text\
+/// CUSTOM: A type defined in `Document.custom_types`, by name
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize, Hash, Eq)]
 pub enum TYPES {
     DIALOGUE,
@@ -24,6 +36,52 @@ pub enum TYPES {
     THINKING,
     ST,
     OT,
+    CUSTOM(String),
+}
+
+impl TYPES {
+    /// Types every document has, in pick list order.
+    pub const BUILTIN: [Self; 5] = [
+        Self::DIALOGUE,
+        Self::SQUARE,
+        Self::THINKING,
+        Self::ST,
+        Self::OT,
+    ];
+
+    /// Prefix of the type in txt files. `None` for custom types,
+    /// their prefixes are kept by the document.
+    pub fn builtin_prefix(&self) -> Option<&'static str> {
+        match self {
+            Self::DIALOGUE => Some("()"),
+            Self::SQUARE => Some("[]"),
+            Self::THINKING => Some("{}"),
+            Self::ST => Some("ST"),
+            Self::OT => Some("OT"),
+            Self::CUSTOM(_) => None,
+        }
+    }
+}
+
+/// A balloon type defined by a document, like SFX or handwritten.
+///
+/// `prefix` marks the type's balloons in txt files, like `()` marks dialogues.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize, Hash, Eq)]
+pub struct CustomType {
+    pub name: String,
+    pub prefix: String,
+    #[serde(default)]
+    pub color: Color,
+}
+
+impl CustomType {
+    pub fn new(name: impl Into<String>, prefix: impl Into<String>, color: Color) -> Self {
+        Self {
+            name: name.into(),
+            prefix: prefix.into(),
+            color,
+        }
+    }
 }
 
 impl Default for TYPES {
@@ -31,3 +89,31 @@ impl Default for TYPES {
         Self::DIALOGUE
     }
 }
+
+/// Workflow status of a balloon. Default value is `Pending`.
+///
+/// `Pending`: No status set yet\
+/// `MachineDraft`: Translation is a machine translation waiting for review\
+/// `Translated`: Translated by a human\
+/// `Proofread`: Translation is proofread
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize, Hash, Eq)]
+pub enum BalloonStatus {
+    #[default]
+    Pending,
+    MachineDraft,
+    Translated,
+    Proofread,
+}
+
+impl BalloonStatus {
+    pub const ALL: [Self; 4] = [
+        Self::Pending,
+        Self::MachineDraft,
+        Self::Translated,
+        Self::Proofread,
+    ];
+
+    pub fn is_pending(&self) -> bool {
+        *self == Self::Pending
+    }
+}
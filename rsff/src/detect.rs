@@ -0,0 +1,552 @@
+//! Speech balloon detection on page images.
+//!
+//! Uses classical image processing, no models involved:
+//! the page is thresholded, white areas are split into connected components,
+//! the holes inside each component (the text) are filled and components that
+//! don't look like a balloon are filtered out.
+//!
+//! Panels are found by cutting the page along white gutters, first into rows
+//! then into columns, recursively.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use image::{DynamicImage, GrayImage};
+use serde::{Deserialize, Serialize};
+
+use crate::balloon::Balloon;
+use crate::img_data::{crop_image, CropFormat, Region};
+use crate::reading_order::{sort_regions, ReadingDirection};
+
+/// Tunable parameters of the balloon detector.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectorParams {
+    /// Grayscale value (0-255) a pixel must reach to count as white.
+    pub white_threshold: u8,
+    /// Minimum area of a balloon, in pixels.
+    pub min_area: u32,
+    /// Maximum area of a balloon, as a fraction of the page area.
+    pub max_area_ratio: f32,
+    /// Minimum filled area / bounding box area. Ellipses are around 0.78.
+    pub min_fill_ratio: f32,
+    /// Fraction of the filled balloon that must be text (holes in the white area).
+    pub min_text_ratio: f32,
+    /// Maximum fraction of the filled balloon that can be text.
+    pub max_text_ratio: f32,
+    /// Maximum width / height (or height / width) of a balloon.
+    pub max_aspect_ratio: f32,
+    /// Pixels added around each detected region.
+    pub padding: u32,
+    /// Minimum width and height of a panel, in pixels.
+    pub min_panel_size: u32,
+}
+
+impl Default for DetectorParams {
+    fn default() -> Self {
+        Self {
+            white_threshold: 200,
+            min_area: 900,
+            max_area_ratio: 0.25,
+            min_fill_ratio: 0.4,
+            min_text_ratio: 0.01,
+            max_text_ratio: 0.6,
+            max_aspect_ratio: 8.0,
+            padding: 4,
+            min_panel_size: 40,
+        }
+    }
+}
+
+impl DetectorParams {
+    /// Names of the parameters, as used by `value` and `set`.
+    pub const FIELDS: [&'static str; 9] = [
+        "white_threshold",
+        "min_area",
+        "max_area_ratio",
+        "min_fill_ratio",
+        "min_text_ratio",
+        "max_text_ratio",
+        "max_aspect_ratio",
+        "padding",
+        "min_panel_size",
+    ];
+
+    /// Value of a parameter as text, `None` for unknown names.
+    pub fn value(&self, field: &str) -> Option<String> {
+        Some(match field {
+            "white_threshold" => self.white_threshold.to_string(),
+            "min_area" => self.min_area.to_string(),
+            "max_area_ratio" => self.max_area_ratio.to_string(),
+            "min_fill_ratio" => self.min_fill_ratio.to_string(),
+            "min_text_ratio" => self.min_text_ratio.to_string(),
+            "max_text_ratio" => self.max_text_ratio.to_string(),
+            "max_aspect_ratio" => self.max_aspect_ratio.to_string(),
+            "padding" => self.padding.to_string(),
+            "min_panel_size" => self.min_panel_size.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Sets a parameter from text. Unknown names and invalid values are rejected
+    /// and the parameter is left unchanged.
+    pub fn set(&mut self, field: &str, value: &str) -> std::result::Result<(), String> {
+        fn parse<T: FromStr>(field: &str, value: &str) -> std::result::Result<T, String> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid value for {}: {}", field, value))
+        }
+
+        match field {
+            "white_threshold" => self.white_threshold = parse(field, value)?,
+            "min_area" => self.min_area = parse(field, value)?,
+            "max_area_ratio" => self.max_area_ratio = parse(field, value)?,
+            "min_fill_ratio" => self.min_fill_ratio = parse(field, value)?,
+            "min_text_ratio" => self.min_text_ratio = parse(field, value)?,
+            "max_text_ratio" => self.max_text_ratio = parse(field, value)?,
+            "max_aspect_ratio" => self.max_aspect_ratio = parse(field, value)?,
+            "padding" => self.padding = parse(field, value)?,
+            "min_panel_size" => self.min_panel_size = parse(field, value)?,
+            _ => return Err(format!("Unknown detector parameter: {}", field)),
+        }
+        Ok(())
+    }
+}
+
+/// Finds balloon candidates on a page. Regions are returned in reading order.
+pub fn detect_balloons(
+    page: &DynamicImage,
+    params: &DetectorParams,
+    direction: ReadingDirection,
+) -> Vec<Region> {
+    let gray = page.to_luma8();
+    let (w, h) = gray.dimensions();
+    let page_area = w as u64 * h as u64;
+
+    let mut regions = white_components(&gray, params.white_threshold)
+        .into_iter()
+        .filter(|c| !c.touches_border(w, h))
+        .filter(|c| c.area >= params.min_area as u64)
+        .filter(|c| (c.area as f64) <= page_area as f64 * params.max_area_ratio as f64)
+        .filter(|c| {
+            let (bw, bh) = (c.bbox.width as f32, c.bbox.height as f32);
+            bw.max(bh) / bw.min(bh).max(1.0) <= params.max_aspect_ratio
+        })
+        .filter_map(|c| {
+            let filled = c.filled_area() as f32;
+            let fill_ratio = filled / c.bbox_area() as f32;
+            let text_ratio = (filled - c.area as f32) / filled;
+
+            if fill_ratio >= params.min_fill_ratio
+                && text_ratio >= params.min_text_ratio
+                && text_ratio <= params.max_text_ratio
+            {
+                Some(pad_region(&c.bbox, params.padding, w, h))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    sort_regions(&mut regions, direction);
+    regions
+}
+
+/// Finds the panels of a page, in no particular order.
+///
+/// Only white gutters are recognized, pages without any gutter are a single panel.
+pub fn detect_panels(page: &DynamicImage, params: &DetectorParams) -> Vec<Region> {
+    let gray = page.to_luma8();
+    let (w, h) = gray.dimensions();
+    let white = gray
+        .as_raw()
+        .iter()
+        .map(|p| *p >= params.white_threshold)
+        .collect::<Vec<_>>();
+
+    let mut panels = Vec::new();
+    let mut stack = vec![Region::new(0, 0, w, h)];
+    while let Some(area) = stack.pop() {
+        let Some(area) = trim_white(&white, w as usize, &area) else {
+            continue;
+        };
+
+        let mut pieces = gutter_cut(&white, w as usize, &area, false);
+        if pieces.len() < 2 {
+            pieces = gutter_cut(&white, w as usize, &area, true);
+        }
+        let pieces = pieces
+            .into_iter()
+            .filter(|p| p.width >= params.min_panel_size && p.height >= params.min_panel_size)
+            .collect::<Vec<_>>();
+
+        if pieces.len() < 2 {
+            panels.push(area);
+        } else {
+            stack.extend(pieces);
+        }
+    }
+
+    panels
+}
+
+/// Detects the panels of a page file. See [`detect_panels`].
+pub fn panels_from_page<P: AsRef<Path>>(
+    page_path: P,
+    params: &DetectorParams,
+) -> image::ImageResult<Vec<Region>> {
+    Ok(detect_panels(&image::open(page_path)?, params))
+}
+
+/// Detects balloons on a page file and creates balloons placed on the page,
+/// with their cropped images.
+///
+/// `page` is the index of the page in `Document.images`.
+pub fn balloons_from_page<P: AsRef<Path>>(
+    page_path: P,
+    page: usize,
+    params: &DetectorParams,
+    direction: ReadingDirection,
+    format: CropFormat,
+) -> image::ImageResult<Vec<Balloon>> {
+    let img = image::open(page_path)?;
+
+    detect_balloons(&img, params, direction)
+        .into_iter()
+        .map(|region| {
+            let mut b = Balloon::default();
+            b.set_region(page, region);
+            b.balloon_img = Some(crop_image(&img, &region, format)?);
+            Ok(b)
+        })
+        .collect()
+}
+
+// Bounding box of the non-white pixels inside an area.
+fn trim_white(white: &[bool], w: usize, area: &Region) -> Option<Region> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for y in area.y..area.y + area.height {
+        let row = y as usize * w;
+        for x in area.x..area.x + area.width {
+            if !white[row + x as usize] {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+    }
+
+    (min_x != u32::MAX).then(|| Region::from_corners((min_x, min_y), (max_x + 1, max_y + 1)))
+}
+
+// Splits an area at the fully white lines crossing it.
+// `vertical` cuts along columns, otherwise along rows.
+fn gutter_cut(white: &[bool], w: usize, area: &Region, vertical: bool) -> Vec<Region> {
+    let is_gutter = |line: u32| {
+        if vertical {
+            (area.y..area.y + area.height).all(|y| white[y as usize * w + line as usize])
+        } else {
+            let row = line as usize * w;
+            (area.x..area.x + area.width).all(|x| white[row + x as usize])
+        }
+    };
+    let (start, end) = if vertical {
+        (area.x, area.x + area.width)
+    } else {
+        (area.y, area.y + area.height)
+    };
+
+    let mut pieces = Vec::new();
+    let mut piece_start = None;
+    for line in start..=end {
+        let gutter = line == end || is_gutter(line);
+        match (gutter, piece_start) {
+            (false, None) => piece_start = Some(line),
+            (true, Some(s)) => {
+                pieces.push(if vertical {
+                    Region::new(s, area.y, line - s, area.height)
+                } else {
+                    Region::new(area.x, s, area.width, line - s)
+                });
+                piece_start = None;
+            }
+            _ => {}
+        }
+    }
+    pieces
+}
+
+fn pad_region(r: &Region, padding: u32, w: u32, h: u32) -> Region {
+    let x = r.x.saturating_sub(padding);
+    let y = r.y.saturating_sub(padding);
+    Region::new(
+        x,
+        y,
+        r.right().saturating_add(padding).min(w) - x,
+        r.bottom().saturating_add(padding).min(h) - y,
+    )
+}
+
+struct Component {
+    bbox: Region,
+    area: u64,
+    // Membership mask of the component inside its bounding box
+    mask: Vec<bool>,
+}
+
+impl Component {
+    fn touches_border(&self, w: u32, h: u32) -> bool {
+        self.bbox.x == 0
+            || self.bbox.y == 0
+            || self.bbox.x + self.bbox.width >= w
+            || self.bbox.y + self.bbox.height >= h
+    }
+
+    fn bbox_area(&self) -> u64 {
+        self.bbox.width as u64 * self.bbox.height as u64
+    }
+
+    // Area of the component with its holes filled: everything in the bounding box
+    // that can't be reached from the box's edges without crossing the component.
+    fn filled_area(&self) -> u64 {
+        let (bw, bh) = (self.bbox.width as usize, self.bbox.height as usize);
+        let mut outside = vec![false; bw * bh];
+        let mut stack = Vec::new();
+
+        for x in 0..bw {
+            stack.push((x, 0));
+            stack.push((x, bh - 1));
+        }
+        for y in 0..bh {
+            stack.push((0, y));
+            stack.push((bw - 1, y));
+        }
+
+        while let Some((x, y)) = stack.pop() {
+            let i = y * bw + x;
+            if self.mask[i] || outside[i] {
+                continue;
+            }
+            outside[i] = true;
+            if x > 0 {
+                stack.push((x - 1, y));
+            }
+            if x + 1 < bw {
+                stack.push((x + 1, y));
+            }
+            if y > 0 {
+                stack.push((x, y - 1));
+            }
+            if y + 1 < bh {
+                stack.push((x, y + 1));
+            }
+        }
+
+        self.bbox_area() - outside.iter().filter(|o| **o).count() as u64
+    }
+}
+
+// 4-connected components of the white pixels.
+fn white_components(gray: &GrayImage, threshold: u8) -> Vec<Component> {
+    let (w, h) = (gray.width() as usize, gray.height() as usize);
+    let white = gray
+        .as_raw()
+        .iter()
+        .map(|p| *p >= threshold)
+        .collect::<Vec<_>>();
+    let mut seen = vec![false; w * h];
+    let mut components = Vec::new();
+    let mut stack = Vec::new();
+    let mut pixels = Vec::new();
+
+    for start in 0..w * h {
+        if !white[start] || seen[start] {
+            continue;
+        }
+
+        seen[start] = true;
+        stack.push(start);
+        pixels.clear();
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (w, h, 0, 0);
+
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % w, i / w);
+            pixels.push(i);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+
+            let mut visit = |n: usize| {
+                if white[n] && !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            };
+            if x > 0 {
+                visit(i - 1);
+            }
+            if x + 1 < w {
+                visit(i + 1);
+            }
+            if y > 0 {
+                visit(i - w);
+            }
+            if y + 1 < h {
+                visit(i + w);
+            }
+        }
+
+        let (bw, bh) = (max_x - min_x + 1, max_y - min_y + 1);
+        let mut mask = vec![false; bw * bh];
+        for i in &pixels {
+            mask[(i / w - min_y) * bw + (i % w - min_x)] = true;
+        }
+
+        components.push(Component {
+            bbox: Region::new(min_x as u32, min_y as u32, bw as u32, bh as u32),
+            area: pixels.len() as u64,
+            mask,
+        });
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod detect_tests {
+    use super::*;
+    use image::{Luma, RgbImage};
+
+    #[test]
+    fn detector_params_fields() {
+        let mut params = DetectorParams::default();
+        for field in DetectorParams::FIELDS {
+            let value = params.value(field).unwrap();
+            params.set(field, &value).unwrap();
+        }
+        assert_eq!(params, DetectorParams::default());
+
+        params.set("min_area", " 1200 ").unwrap();
+        params.set("max_area_ratio", "0.5").unwrap();
+        assert_eq!((params.min_area, params.max_area_ratio), (1200, 0.5));
+        assert!(params.set("white_threshold", "300").is_err());
+        assert!(params.set("padding", "-1").is_err());
+        assert!(params.set("unknown", "1").is_err());
+        assert_eq!(params.white_threshold, 200);
+    }
+
+    // Gray page with white ellipses, some of them with "text" inside.
+    fn test_page(balloons: &[(u32, u32, u32, u32, bool)]) -> DynamicImage {
+        let mut img = GrayImage::from_pixel(400, 300, Luma([90]));
+
+        for &(cx, cy, rx, ry, has_text) in balloons {
+            for y in cy - ry..=cy + ry {
+                for x in cx - rx..=cx + rx {
+                    let dx = (x as f32 - cx as f32) / rx as f32;
+                    let dy = (y as f32 - cy as f32) / ry as f32;
+                    if dx * dx + dy * dy <= 1.0 {
+                        img.put_pixel(x, y, Luma([255]));
+                    }
+                }
+            }
+            if has_text {
+                for line in 0..3 {
+                    let y = cy - 8 + line * 8;
+                    for x in cx - rx / 2..cx + rx / 2 {
+                        img.put_pixel(x, y, Luma([0]));
+                        img.put_pixel(x, y + 1, Luma([0]));
+                    }
+                }
+            }
+        }
+
+        DynamicImage::ImageLuma8(img)
+    }
+
+    #[test]
+    fn detect_finds_balloons_with_text() {
+        let page = test_page(&[(100, 80, 50, 30, true), (300, 200, 50, 30, false)]);
+        let regions = detect_balloons(
+            &page,
+            &DetectorParams::default(),
+            ReadingDirection::RightToLeft,
+        );
+
+        assert_eq!(regions.len(), 1);
+        assert!(regions[0].contains(100, 80));
+        assert!(!regions[0].contains(300, 200));
+    }
+
+    #[test]
+    fn detect_ignores_page_background() {
+        let mut page = RgbImage::from_pixel(200, 200, image::Rgb([255, 255, 255]));
+        for x in 50..150 {
+            page.put_pixel(x, 100, image::Rgb([0, 0, 0]));
+        }
+        let regions = detect_balloons(
+            &DynamicImage::ImageRgb8(page),
+            &DetectorParams::default(),
+            ReadingDirection::RightToLeft,
+        );
+
+        assert!(regions.is_empty());
+    }
+
+    #[test]
+    fn detect_reading_order() {
+        // Right balloon of the first row comes first, then the left one, then the next row
+        let page = test_page(&[
+            (80, 60, 50, 30, true),
+            (300, 70, 50, 30, true),
+            (200, 220, 50, 30, true),
+        ]);
+        let regions = detect_balloons(
+            &page,
+            &DetectorParams::default(),
+            ReadingDirection::RightToLeft,
+        );
+
+        assert_eq!(regions.len(), 3);
+        assert!(regions[0].contains(300, 70));
+        assert!(regions[1].contains(80, 60));
+        assert!(regions[2].contains(200, 220));
+    }
+
+    #[test]
+    fn detect_panels_along_gutters() {
+        // Tall panel on the right, two stacked panels on the left
+        let mut img = GrayImage::from_pixel(200, 300, Luma([255]));
+        for (x0, y0, x1, y1) in [(10, 10, 95, 145), (10, 155, 95, 290), (105, 10, 190, 290)] {
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    img.put_pixel(x, y, Luma([120]));
+                }
+            }
+        }
+
+        let mut panels = detect_panels(&DynamicImage::ImageLuma8(img), &DetectorParams::default());
+        panels.sort_by_key(|p| (p.x, p.y));
+
+        assert_eq!(
+            panels,
+            vec![
+                Region::new(10, 10, 85, 135),
+                Region::new(10, 155, 85, 135),
+                Region::new(105, 10, 85, 280),
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_pad_region_large_padding() {
+        let r = Region::new(10, 20, 30, 40);
+        assert_eq!(pad_region(&r, 5, 400, 300), Region::new(5, 15, 40, 50));
+        assert_eq!(
+            pad_region(&r, u32::MAX, 400, 300),
+            Region::new(0, 0, 400, 300)
+        );
+    }
+}
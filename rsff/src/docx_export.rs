@@ -0,0 +1,254 @@
+//! Writing documents as Word tables.
+//!
+//! The exported file starts with a title block made of the document's metadata,
+//! followed by a table of balloons for every page:
+//!
+//! | No | Page | Type | TL   | PR    | Comments | Style     | Image |
+//! |----|------|------|------|-------|----------|-----------|-------|
+//! | 1  | 1    | ()   | Hey! | Hey!! | Loud     | bold=true | ...   |
+//!
+//! The style column holds the preset and style attributes of a balloon, like the
+//! `#STYLE` lines of txt files. Sound effects aren't in the tables, they follow them
+//! in a `#SFX` section written like the one of txt files.
+//!
+//! Files exported this way can be read back with [`Document::open`].
+
+use crate::{Document, TXT_TYPE_MARKER};
+
+/// Balloon images are scaled down to fit in a square of this size, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 120;
+
+/// Column names of the balloon tables, the image column is added when thumbnails are enabled.
+pub const HEADER: [&str; 7] = ["No", "Page", "Type", "TL", "PR", "Comments", "Style"];
+const IMAGE_HEADER: &str = "Image";
+
+/// Options of the docx exporter.
+///
+/// `thumbnails`: Embeds balloon images in an image column.\
+/// `page_breaks`: Starts the table of every page on a new Word page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DocxExportOptions {
+    pub thumbnails: bool,
+    pub page_breaks: bool,
+}
+
+impl Default for DocxExportOptions {
+    fn default() -> Self {
+        Self {
+            thumbnails: true,
+            page_breaks: true,
+        }
+    }
+}
+
+/// A balloon's table row. Cells follow `HEADER`, each cell is a list of lines.
+/// `thumbnail` is a png image.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct DocxRow {
+    pub cells: Vec<Vec<String>>,
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+/// Consecutive balloons of the same page.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct DocxSection {
+    pub heading: String,
+    pub rows: Vec<DocxRow>,
+}
+
+/// Contents of an exported Word document, before they are written as docx.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct DocxLayout {
+    pub title: String,
+    pub info: Vec<String>,
+    /// `#TYPE` lines of the custom types, so their prefixes can be read back.
+    pub type_decls: Vec<String>,
+    pub header: Vec<String>,
+    pub sections: Vec<DocxSection>,
+    /// Lines of the sound effect section, without its `#SFX` marker.
+    pub sfx: Vec<String>,
+    pub page_breaks: bool,
+}
+
+impl DocxLayout {
+    /// The export has an image column.
+    pub fn has_images(&self) -> bool {
+        self.header.len() > HEADER.len()
+    }
+}
+
+/// Lays out a document for the docx exporter.
+pub(crate) fn layout(d: &Document, options: &DocxExportOptions) -> DocxLayout {
+    let mut info = vec![d.METADATA_SCRIPT_VERSION.clone()];
+    if !d.METADATA_APP_VERSION.is_empty() {
+        info.push(d.METADATA_APP_VERSION.clone());
+    }
+    info.push(format!(
+        "{} balloons, {} translation characters",
+        d.len(),
+        d.tl_chars()
+    ));
+
+    let mut header = Vec::from(HEADER.map(String::from));
+    if options.thumbnails {
+        header.push(IMAGE_HEADER.to_string());
+    }
+
+    let mut sections: Vec<(Option<usize>, DocxSection)> = Vec::new();
+    // Rows are numbered by balloon positions, like the sound effect lines
+    for (i, b) in d
+        .balloons
+        .iter()
+        .enumerate()
+        .filter(|(_, b)| b.sfx.is_none())
+    {
+        let row = DocxRow {
+            cells: vec![
+                vec![(i + 1).to_string()],
+                b.page.map(|p| (p + 1).to_string()).into_iter().collect(),
+                vec![d.type_prefix(&b.btype)],
+                b.tl_content.clone(),
+                b.pr_content.clone(),
+                b.comments.clone(),
+                Document::txt_style(b).into_iter().collect(),
+            ],
+            thumbnail: b
+                .balloon_img
+                .as_ref()
+                .filter(|_| options.thumbnails)
+                .and_then(|img| img.thumbnail(THUMBNAIL_SIZE).ok())
+                .map(|img| img.img_data),
+        };
+
+        match sections.last_mut() {
+            Some((page, section)) if *page == b.page => section.rows.push(row),
+            _ => sections.push((
+                b.page,
+                DocxSection {
+                    heading: section_heading(d, b.page),
+                    rows: vec![row],
+                },
+            )),
+        }
+    }
+
+    DocxLayout {
+        title: d.METADATA_INFO.clone(),
+        info,
+        type_decls: d
+            .custom_types
+            .iter()
+            .map(|t| format!("{} {} {} {}", TXT_TYPE_MARKER, t.prefix, t.color, t.name))
+            .collect(),
+        header,
+        sections: sections.into_iter().map(|(_, s)| s).collect(),
+        sfx: d.txt_sfx_section(),
+        page_breaks: options.page_breaks,
+    }
+}
+
+pub(crate) fn section_heading(d: &Document, page: Option<usize>) -> String {
+    let Some(page) = page else {
+        return "Unplaced balloons".to_string();
+    };
+    let file = d
+        .images
+        .as_ref()
+        .and_then(|imgs| imgs.get(page))
+        .and_then(|p| std::path::Path::new(p).file_name())
+        .map(|name| name.to_string_lossy().to_string());
+    match file {
+        Some(file) => format!("Page {} ({})", page + 1, file),
+        None => format!("Page {}", page + 1),
+    }
+}
+
+#[cfg(test)]
+mod docx_export_tests {
+    use super::*;
+    use crate::docx_handlers::{layout_to_docx, parse_docx_blocks};
+    use crate::docx_import::{blocks_to_doc, DocxColumns, DocxImportOptions};
+    use crate::test_util::sample_document;
+
+    #[test]
+    fn docx_export_layout() {
+        let d = sample_document();
+        let layout = layout(&d, &DocxExportOptions::default());
+
+        assert_eq!(layout.title, "Chapter <12>");
+        assert_eq!(layout.type_decls, vec!["#TYPE HW #010203 Handwritten"]);
+        assert!(layout.has_images());
+
+        let headings = layout
+            .sections
+            .iter()
+            .map(|s| s.heading.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(headings, vec!["Page 1 (001.png)", "Unplaced balloons"]);
+
+        let first = &layout.sections[0].rows[0];
+        assert_eq!(first.cells[0], vec!["1"]);
+        assert_eq!(first.cells[1], vec!["1"]);
+        assert_eq!(first.cells[2], vec!["()"]);
+        let thumb = image::load_from_memory(first.thumbnail.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            (thumb.width(), thumb.height()),
+            (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2)
+        );
+
+        assert_eq!(layout.sections[0].rows[1].cells[2], vec!["HW"]);
+        assert!(layout.sections[0].rows[1].cells[6].is_empty());
+        assert_eq!(layout.sections[1].rows[0].cells[0], vec!["4"]);
+        assert!(layout.sections[1].rows[0].cells[1].is_empty());
+        assert_eq!(
+            layout.sfx,
+            vec![
+                "#PAGE 2",
+                "#STYLE bold=true; italic=true; align=left; color=#ff0010; rotation=-15",
+                "3: ドン | don | BOOM | replace"
+            ]
+        );
+
+        let no_images = super::layout(
+            &d,
+            &DocxExportOptions {
+                thumbnails: false,
+                page_breaks: false,
+            },
+        );
+        assert!(!no_images.has_images());
+        assert!(no_images.sections[0].rows[0].thumbnail.is_none());
+    }
+
+    #[test]
+    fn docx_export_round_trip() {
+        let d = sample_document();
+        let mut docx = std::io::Cursor::new(Vec::new());
+        layout_to_docx(layout(&d, &DocxExportOptions::default()))
+            .build()
+            .pack(&mut docx)
+            .unwrap();
+        docx.set_position(0);
+        let blocks = parse_docx_blocks(docx).unwrap();
+        let read = blocks_to_doc(blocks, &DocxImportOptions::default()).unwrap();
+
+        assert_eq!(read.custom_types, d.custom_types);
+        assert_eq!(read.len(), d.len());
+        for (read, b) in read.balloons.iter().zip(&d.balloons) {
+            assert_eq!(read.btype, b.btype);
+            assert_eq!(read.page, b.page);
+            assert_eq!(read.tl_content, b.tl_content);
+            assert_eq!(read.pr_content, b.pr_content);
+            assert_eq!(read.comments, b.comments);
+            assert_eq!(read.sfx, b.sfx);
+            assert_eq!(read.preset, b.preset);
+            assert_eq!(read.style, b.style);
+        }
+        // The importer finds the exported columns by their names
+        let columns = DocxColumns::from_header(&HEADER.map(String::from)).unwrap();
+        assert_eq!(
+            columns.to_string(),
+            "-, page, type, tl, pr, comments, style"
+        );
+    }
+}
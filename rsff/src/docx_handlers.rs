@@ -1,52 +1,144 @@
-use docx_rs::{Docx as DocxBuilder, Paragraph, Run};
+use docx_rs::{
+    BreakType, Docx as DocxBuilder, Paragraph, Pic, Run, RunFonts, Table, TableCell as DocxCell,
+    TableRow,
+};
 use docx_rust::{
-    document::{BodyContent, ParagraphContent, RunContent},
+    document::{
+        BodyContent, ParagraphContent, RunContent, TableCell, TableCellContent, TableRowContent,
+    },
     DocxFile as DocxReader,
 };
+use std::collections::HashMap;
+
+use crate::docx_export::DocxLayout;
+use crate::docx_import::{DocxBlock, DocxText};
+use crate::markup::{self, Span, TextStyle};
+use crate::sfx::TXT_SFX_MARKER;
 
-pub fn parse_docx_to_string<R: std::io::Read + std::io::Seek>(
+pub fn parse_docx_blocks<R: std::io::Read + std::io::Seek>(
     r: R,
-) -> Result<String, Box<dyn std::error::Error>> {
+) -> Result<Vec<DocxBlock>, Box<dyn std::error::Error>> {
     let docx = DocxReader::from_reader(r)?;
     let docx = docx.parse()?;
-    let mut doc_strings: Vec<String> = Vec::with_capacity(200);
+
+    // Comment texts by their ids, prefixed with their authors
+    let mut comments = HashMap::new();
+    for comment in docx.comments.iter().flat_map(|c| c.comments.iter()) {
+        let Some(id) = comment.id.map(|id| id.to_string()) else {
+            continue;
+        };
+        let text = markup::strip(&paragraph_text(comment.content.clone(), &HashMap::new()).text);
+        let text = match comment.author.trim() {
+            "" => text,
+            author => format!("{}: {}", author, text),
+        };
+        comments.insert(id, text);
+    }
+
+    let mut blocks: Vec<DocxBlock> = Vec::with_capacity(200);
     for element in docx.document.body.content {
-        let mut text = String::with_capacity(200);
         match element {
             BodyContent::Paragraph(p) => {
-                let p_contents = p.content;
-                for pc in p_contents {
-                    match pc {
-                        ParagraphContent::Run(r) => {
-                            let run_cont = handle_docx_run(r);
-                            text.push_str(&run_cont);
-                        }
-                        ParagraphContent::Link(hyperlink) => {
-                            if let Some(r) = hyperlink.content {
-                                let run_cont = handle_docx_run(r);
-                                text.push_str(&run_cont);
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+                blocks.push(DocxBlock::Paragraph(paragraph_text(p, &comments)));
             }
             BodyContent::Run(r) => {
-                let run_cont = handle_docx_run(r);
-                text.push_str(&run_cont);
+                blocks.push(DocxBlock::Paragraph(DocxText {
+                    text: handle_docx_run(r),
+                    comments: Vec::new(),
+                }));
+            }
+            BodyContent::Table(t) => {
+                let rows = t
+                    .rows
+                    .into_iter()
+                    .map(|row| {
+                        row.cells
+                            .into_iter()
+                            .filter_map(|cell| match cell {
+                                TableRowContent::TableCell(cell) => {
+                                    Some(cell_text(cell, &comments))
+                                }
+                                _ => None,
+                            })
+                            .collect()
+                    })
+                    .collect();
+                blocks.push(DocxBlock::Table(rows));
             }
             _ => {}
         }
+    }
+
+    Ok(blocks)
+}
+
+// Paragraphs of a cell are its lines.
+fn cell_text(cell: TableCell, comments: &HashMap<String, String>) -> DocxText {
+    let mut lines = Vec::new();
+    let mut cell_comments = Vec::new();
+    for content in cell.content {
+        if let TableCellContent::Paragraph(p) = content {
+            let p = paragraph_text(p, comments);
+            lines.push(p.text);
+            cell_comments.extend(p.comments);
+        }
+    }
+    DocxText {
+        text: lines.join("\n"),
+        comments: cell_comments,
+    }
+}
 
-        if !text.is_empty() {
-            doc_strings.push(text);
+fn paragraph_text(
+    p: docx_rust::document::Paragraph,
+    comments: &HashMap<String, String>,
+) -> DocxText {
+    let mut text = String::with_capacity(200);
+    let mut p_comments = Vec::new();
+    for pc in p.content {
+        match pc {
+            ParagraphContent::Run(r) => {
+                let run_cont = handle_docx_run(r);
+                text.push_str(&run_cont);
+            }
+            ParagraphContent::Link(hyperlink) => {
+                if let Some(r) = hyperlink.content {
+                    let run_cont = handle_docx_run(r);
+                    text.push_str(&run_cont);
+                }
+            }
+            ParagraphContent::CommentRangeStart(start) => {
+                let comment = start.id.and_then(|id| comments.get(&id.to_string()));
+                p_comments.extend(comment.cloned());
+            }
+            _ => {}
         }
     }
 
-    Ok(doc_strings.join("\n//\n"))
+    DocxText {
+        // Adjacent runs with the same formatting are written as one
+        text: markup::to_markup(&markup::parse_lossy(&text)),
+        comments: p_comments,
+    }
 }
 
 fn handle_docx_run(run: docx_rust::document::Run) -> String {
+    let style = run
+        .property
+        .as_ref()
+        .map(|p| TextStyle {
+            bold: p.bold.as_ref().is_some_and(|b| b.value.unwrap_or(true)),
+            italic: p.italics.as_ref().is_some_and(|i| i.value.unwrap_or(true)),
+            underline: p.underline.is_some(),
+            small_caps: false,
+            font: p
+                .fonts
+                .as_ref()
+                .and_then(|f| f.ascii.as_ref())
+                .map(|f| f.to_string()),
+        })
+        .unwrap_or_default();
+
     let mut runstring = String::new();
 
     for element in run.content {
@@ -72,15 +164,151 @@ fn handle_docx_run(run: docx_rust::document::Run) -> String {
             _ => {}
         }
     }
-    runstring
+
+    if style.is_plain() || runstring.is_empty() {
+        runstring
+    } else {
+        markup::to_markup(&[Span::new(runstring, style)])
+    }
 }
 
-pub fn string_to_docx(s: &str) -> DocxBuilder {
-    let mut docx = DocxBuilder::new();
-    for line in s.lines() {
-        let paragraph = Paragraph::new().add_run(Run::new().add_text(line));
-        docx = docx.add_paragraph(paragraph);
+pub fn layout_to_docx(layout: DocxLayout) -> DocxBuilder {
+    let mut docx = DocxBuilder::new().add_paragraph(
+        Paragraph::new().add_run(Run::new().add_text(&layout.title).bold().size(TITLE_SIZE)),
+    );
+    for line in layout.info.iter().chain(&layout.type_decls) {
+        docx = docx.add_paragraph(markup_paragraph(line, false));
+    }
+
+    let has_images = layout.has_images();
+    for (i, section) in layout.sections.into_iter().enumerate() {
+        let mut heading = Paragraph::new();
+        if i > 0 && layout.page_breaks {
+            heading = heading.add_run(Run::new().add_break(BreakType::Page));
+        }
+        heading = heading.add_run(
+            Run::new()
+                .add_text(&section.heading)
+                .bold()
+                .size(HEADING_SIZE),
+        );
+        docx = docx.add_paragraph(heading);
+
+        let header = layout
+            .header
+            .iter()
+            .map(|h| text_cell(std::slice::from_ref(h), true))
+            .collect();
+        let mut rows = vec![TableRow::new(header)];
+        for row in section.rows {
+            let mut cells: Vec<DocxCell> = row.cells.iter().map(|c| text_cell(c, false)).collect();
+            if has_images {
+                let mut image = Paragraph::new();
+                if let Some(png) = &row.thumbnail {
+                    image = image.add_run(Run::new().add_image(Pic::new(png)));
+                }
+                cells.push(DocxCell::new().add_paragraph(image));
+            }
+            rows.push(TableRow::new(cells));
+        }
+        docx = docx.add_table(Table::new(rows));
+    }
+
+    if !layout.sfx.is_empty() {
+        let mut marker = Paragraph::new();
+        if layout.page_breaks {
+            marker = marker.add_run(Run::new().add_break(BreakType::Page));
+        }
+        docx = docx.add_paragraph(marker.add_run(Run::new().add_text(TXT_SFX_MARKER)));
+        for line in &layout.sfx {
+            docx = docx.add_paragraph(markup_paragraph(line, false));
+        }
     }
 
     docx
 }
+
+// Lines of a cell are its paragraphs. Cells must have a paragraph even if they are empty.
+fn text_cell(lines: &[String], bold: bool) -> DocxCell {
+    let mut cell = DocxCell::new();
+    for line in lines {
+        cell = cell.add_paragraph(markup_paragraph(line, bold));
+    }
+    if lines.is_empty() {
+        cell = cell.add_paragraph(Paragraph::new());
+    }
+    cell
+}
+
+fn markup_paragraph(line: &str, bold: bool) -> Paragraph {
+    let mut paragraph = Paragraph::new();
+    for mut span in markup::parse_lossy(line) {
+        span.style.bold |= bold;
+        for run in span_to_runs(&span) {
+            paragraph = paragraph.add_run(run);
+        }
+    }
+    paragraph
+}
+
+// Half-points, sizes of the title and page headings.
+const TITLE_SIZE: usize = 36;
+const HEADING_SIZE: usize = 28;
+
+// Half-points, Word's default 11pt and the smaller size used for small caps.
+const RUN_SIZE: usize = 22;
+const SMALL_CAPS_SIZE: usize = 18;
+
+fn styled_run(text: &str, style: &TextStyle) -> Run {
+    let mut run = Run::new().add_text(text);
+    if style.bold {
+        run = run.bold();
+    }
+    if style.italic {
+        run = run.italic();
+    }
+    if style.underline {
+        run = run.underline("single");
+    }
+    if let Some(font) = &style.font {
+        run = run.fonts(
+            RunFonts::new()
+                .ascii(font)
+                .hi_ansi(font)
+                .east_asia(font)
+                .cs(font),
+        );
+    }
+    run
+}
+
+// docx-rs can't write small caps, so they are emulated: lowercase letters are
+// uppercased in a smaller size. Reading the file back gives plain uppercase text.
+fn span_to_runs(span: &Span) -> Vec<Run> {
+    if !span.style.small_caps {
+        return vec![styled_run(&span.text, &span.style)];
+    }
+
+    let mut runs = Vec::new();
+    let mut chunk = String::new();
+    let mut chunk_lower = false;
+    let mut flush = |chunk: &mut String, lower: bool| {
+        if chunk.is_empty() {
+            return;
+        }
+        let size = if lower { SMALL_CAPS_SIZE } else { RUN_SIZE };
+        runs.push(styled_run(&chunk.to_uppercase(), &span.style).size(size));
+        chunk.clear();
+    };
+
+    for c in span.text.chars() {
+        let lower = c.is_lowercase();
+        if lower != chunk_lower {
+            flush(&mut chunk, chunk_lower);
+            chunk_lower = lower;
+        }
+        chunk.push(c);
+    }
+    flush(&mut chunk, chunk_lower);
+    runs
+}
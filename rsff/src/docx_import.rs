@@ -0,0 +1,442 @@
+//! Reading balloons from Word documents.
+//!
+//! Paragraphs are read like the lines of a txt file. Tables are read row by row,
+//! with [`DocxColumns`] telling which column holds what. When a document has tables,
+//! its other paragraphs are taken as titles and notes and only the custom type and
+//! sound effect sections of them are read.
+//!
+//! | Page | Type | Text            |
+//! |------|------|-----------------|
+//! | 1    | ()   | Hey!            |
+//! | 1    | []   | Meanwhile...    |
+//!
+//! Paragraphs and the cells of a style column may style balloons like the `#STYLE`
+//! lines of txt files, invalid style cells are ignored.
+//!
+//! Bold, italic, underline and font runs become [inline markup](crate::markup),
+//! Word comments go to the comments of the balloon they are anchored in.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::balloon::Balloon;
+use crate::sfx::{Sfx, TXT_SFX_MARKER};
+use crate::{Document, Result, TXT_PAGE_MARKER, TXT_STYLE_MARKER, TXT_TYPE_MARKER};
+
+/// Separates the lines of a balloon written on a single line.
+const LINE_SEPARATOR: &str = " || ";
+
+/// Which table column holds which balloon field. Columns start from 0.
+///
+/// Written as a comma separated list of `page`, `type`, `tl`, `pr`, `comments` and
+/// `style`, with `-` for ignored columns.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::docx_import::DocxColumns;
+///
+/// let columns: DocxColumns = "-, page, tl, comments".parse().unwrap();
+/// assert_eq!(columns.page, Some(1));
+/// assert_eq!(columns.tl, Some(2));
+/// assert_eq!(columns.btype, None);
+/// assert_eq!(columns.to_string(), "-, page, tl, comments");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DocxColumns {
+    pub page: Option<usize>,
+    pub btype: Option<usize>,
+    pub tl: Option<usize>,
+    pub pr: Option<usize>,
+    pub comments: Option<usize>,
+    pub style: Option<usize>,
+}
+
+impl Default for DocxColumns {
+    /// `page, type, tl`
+    fn default() -> Self {
+        Self {
+            page: Some(0),
+            btype: Some(1),
+            tl: Some(2),
+            pr: None,
+            comments: None,
+            style: None,
+        }
+    }
+}
+
+impl DocxColumns {
+    /// Columns named by a header row, `None` if the row has no translation column.
+    pub fn from_header(cells: &[String]) -> Option<Self> {
+        let mut columns = Self {
+            page: None,
+            btype: None,
+            tl: None,
+            pr: None,
+            comments: None,
+            style: None,
+        };
+        for (i, cell) in cells.iter().enumerate() {
+            let slot = match crate::markup::strip(cell).trim().to_lowercase().as_str() {
+                "page" | "pg" => &mut columns.page,
+                "type" => &mut columns.btype,
+                "tl" | "text" | "translation" => &mut columns.tl,
+                "pr" | "proofread" | "proofreading" => &mut columns.pr,
+                "comment" | "comments" | "note" | "notes" => &mut columns.comments,
+                "style" => &mut columns.style,
+                _ => continue,
+            };
+            slot.get_or_insert(i);
+        }
+        columns.tl.map(|_| columns)
+    }
+
+    fn roles(&self) -> [(Option<usize>, &'static str); 6] {
+        [
+            (self.page, "page"),
+            (self.btype, "type"),
+            (self.tl, "tl"),
+            (self.pr, "pr"),
+            (self.comments, "comments"),
+            (self.style, "style"),
+        ]
+    }
+}
+
+impl FromStr for DocxColumns {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut columns = Self {
+            page: None,
+            btype: None,
+            tl: None,
+            pr: None,
+            comments: None,
+            style: None,
+        };
+        for (i, role) in s.split(',').map(|r| r.trim()).enumerate() {
+            let slot = match role {
+                "page" => &mut columns.page,
+                "type" => &mut columns.btype,
+                "tl" => &mut columns.tl,
+                "pr" => &mut columns.pr,
+                "comments" => &mut columns.comments,
+                "style" => &mut columns.style,
+                "-" | "" => continue,
+                _ => return Err(format!("Unknown column: {}", role)),
+            };
+            if slot.replace(i).is_some() {
+                return Err(format!("Column {} is given twice", role));
+            }
+        }
+        if columns.tl.is_none() {
+            return Err("No tl column".to_string());
+        }
+        Ok(columns)
+    }
+}
+
+impl fmt::Display for DocxColumns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let roles = self.roles();
+        let count = roles
+            .iter()
+            .filter_map(|(i, _)| *i)
+            .max()
+            .map_or(0, |i| i + 1);
+        let names = Vec::from_iter((0..count).map(|col| {
+            roles
+                .iter()
+                .find(|(i, _)| *i == Some(col))
+                .map_or("-", |(_, name)| name)
+        }));
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// Options of the docx importer.
+///
+/// `columns`: Column mapping of tables without a header row.\
+/// `detect_header`: Tables starting with a row of column names use those columns instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DocxImportOptions {
+    pub columns: DocxColumns,
+    pub detect_header: bool,
+}
+
+impl Default for DocxImportOptions {
+    fn default() -> Self {
+        Self {
+            columns: DocxColumns::default(),
+            detect_header: true,
+        }
+    }
+}
+
+/// Text of a paragraph or table cell, with inline markup.
+/// `comments` are the Word comments anchored in it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct DocxText {
+    pub text: String,
+    pub comments: Vec<String>,
+}
+
+impl DocxText {
+    fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.text
+            .split('\n')
+            .flat_map(|l| l.split(LINE_SEPARATOR))
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+    }
+}
+
+/// Body element of a Word document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DocxBlock {
+    Paragraph(DocxText),
+    /// Rows of cells.
+    Table(Vec<Vec<DocxText>>),
+}
+
+/// Builds a document from the body of a Word document.
+pub(crate) fn blocks_to_doc(
+    blocks: Vec<DocxBlock>,
+    options: &DocxImportOptions,
+) -> Result<Document> {
+    let mut d = Document::default();
+    // Balloons of documents with tables are in the tables,
+    // their other paragraphs are titles and notes.
+    let has_tables = blocks.iter().any(|b| matches!(b, DocxBlock::Table(_)));
+    let mut in_sfx_section = false;
+    let mut sfx_balloons = Vec::new();
+    let mut page = None;
+    // Preset and style of the next balloon
+    let mut style = None;
+
+    for block in blocks {
+        match block {
+            DocxBlock::Paragraph(p) => {
+                let line = p.text.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                if line == TXT_SFX_MARKER {
+                    in_sfx_section = true;
+                    page = None;
+                } else if let Some(number) = line.strip_prefix(TXT_PAGE_MARKER) {
+                    page = Document::parse_txt_page(&crate::markup::strip(number))?;
+                } else if let Some(attrs) = line.strip_prefix(TXT_STYLE_MARKER) {
+                    style = Some(Document::parse_txt_style(&crate::markup::strip(attrs))?);
+                } else if in_sfx_section {
+                    if let Some((n, sfx)) = Sfx::from_txt(&crate::markup::strip(line)) {
+                        let (preset, style) = style.take().unwrap_or_default();
+                        let b = Balloon {
+                            sfx: Some(sfx),
+                            page,
+                            comments: p.comments,
+                            preset,
+                            style,
+                            ..Default::default()
+                        };
+                        sfx_balloons.push((n, b));
+                    }
+                } else if let Some(decl) = line.strip_prefix(TXT_TYPE_MARKER) {
+                    d.add_custom_type(Document::parse_txt_type(&crate::markup::strip(decl))?)?;
+                } else if !has_tables {
+                    let (btype, text) = d.split_txt_header(line);
+                    let text = DocxText {
+                        text: text.to_string(),
+                        comments: Vec::new(),
+                    };
+                    let (preset, style) = style.take().unwrap_or_default();
+                    d.balloons.push(Balloon {
+                        btype,
+                        tl_content: text.lines().collect(),
+                        page,
+                        comments: p.comments,
+                        preset,
+                        style,
+                        ..Default::default()
+                    });
+                }
+            }
+            DocxBlock::Table(rows) => table_to_balloons(&mut d, rows, options),
+        }
+    }
+
+    // Put the sound effects back to their places
+    sfx_balloons.sort_by_key(|(n, _)| *n);
+    for (n, b) in sfx_balloons {
+        let i = n.saturating_sub(1).min(d.balloons.len());
+        d.balloons.insert(i, b);
+    }
+
+    Ok(d)
+}
+
+fn table_to_balloons(d: &mut Document, rows: Vec<Vec<DocxText>>, options: &DocxImportOptions) {
+    let mut rows = rows.into_iter().peekable();
+
+    let header = rows
+        .peek()
+        .filter(|_| options.detect_header)
+        .and_then(|row| {
+            let names = Vec::from_iter(row.iter().map(|c| c.text.clone()));
+            DocxColumns::from_header(&names)
+        });
+    let columns = match header {
+        Some(columns) => {
+            rows.next();
+            columns
+        }
+        None => options.columns,
+    };
+
+    for row in rows {
+        let cell = |col: Option<usize>| col.and_then(|i| row.get(i));
+        let lines =
+            |col: Option<usize>| Vec::from_iter(cell(col).into_iter().flat_map(|c| c.lines()));
+
+        let mut b = Balloon {
+            tl_content: lines(columns.tl),
+            pr_content: lines(columns.pr),
+            comments: lines(columns.comments),
+            ..Default::default()
+        };
+        b.comments
+            .extend(row.iter().flat_map(|c| c.comments.iter().cloned()));
+        match cell(columns.btype).map(|c| crate::markup::strip(&c.text)) {
+            // Sound effects are written like the lines of the txt sound effect section
+            Some(label) if label.trim() == TXT_SFX_MARKER => {
+                let fields = crate::markup::strip(&b.tl_content.join(" "));
+                b.sfx = Some(Sfx::from_txt_fields(&fields));
+                b.tl_content.clear();
+            }
+            Some(label) => b.btype = d.type_from_label(label.trim()).unwrap_or_default(),
+            None => {}
+        }
+        if let Some(Ok((preset, style))) =
+            cell(columns.style).map(|c| Document::parse_txt_style(&crate::markup::strip(&c.text)))
+        {
+            b.preset = preset;
+            b.style = style;
+        }
+        // Pages are numbered from 1 in documents
+        b.page = cell(columns.page)
+            .and_then(|c| crate::markup::strip(&c.text).trim().parse::<usize>().ok())
+            .and_then(|p| p.checked_sub(1));
+
+        if b.sfx.is_some() || b.tl_chars() + b.pr_chars() + b.comments_chars() != 0 {
+            d.balloons.push(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod docx_import_tests {
+    use super::*;
+    use crate::consts::TYPES;
+
+    fn cell(text: &str) -> DocxText {
+        DocxText {
+            text: text.to_string(),
+            comments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn docx_import_columns() {
+        let columns: DocxColumns = "type, -, tl, pr".parse().unwrap();
+        assert_eq!(columns.btype, Some(0));
+        assert_eq!(columns.pr, Some(3));
+        assert_eq!(columns.to_string(), "type, -, tl, pr");
+
+        assert!("page, page, tl".parse::<DocxColumns>().is_err());
+        assert!("page, type".parse::<DocxColumns>().is_err());
+        assert_eq!(DocxColumns::default().to_string(), "page, type, tl");
+    }
+
+    #[test]
+    fn docx_import_paragraphs() {
+        let blocks = vec![
+            DocxBlock::Paragraph(cell("(): [b]Hey[/b] || you")),
+            DocxBlock::Paragraph(cell("")),
+            DocxBlock::Paragraph(cell("#PAGE 2")),
+            DocxBlock::Paragraph(DocxText {
+                text: "[]: Meanwhile".to_string(),
+                comments: vec!["Ana: keep it short".to_string()],
+            }),
+            DocxBlock::Paragraph(cell(TXT_SFX_MARKER)),
+            DocxBlock::Paragraph(cell("#STYLE [b]preset=Over-text[/b]; size=30")),
+            DocxBlock::Paragraph(cell("2: ドン | don | BOOM | replace")),
+        ];
+        let d = blocks_to_doc(blocks, &DocxImportOptions::default()).unwrap();
+
+        assert_eq!(d.balloons.len(), 3);
+        assert_eq!(d.balloons[0].tl_content, vec!["[b]Hey[/b]", "you"]);
+        assert_eq!(d.balloons[1].sfx, Some(Sfx::new("ドン", "don", "BOOM")));
+        assert_eq!(d.balloons[1].preset.as_deref(), Some("Over-text"));
+        assert_eq!(d.balloons[1].style.font_size, Some(30));
+        assert!(d.balloons[0].style.is_empty());
+        assert_eq!(d.balloons[2].btype, TYPES::SQUARE);
+        assert_eq!(d.balloons[2].comments, vec!["Ana: keep it short"]);
+        assert_eq!(d.balloons[0].page, None);
+        assert_eq!(d.balloons[2].page, Some(1));
+    }
+
+    #[test]
+    fn docx_import_tables() {
+        let header = vec![
+            cell("Page"),
+            cell("Type"),
+            cell("Text"),
+            cell("Notes"),
+            cell("Style"),
+        ];
+        let mut commented = cell("[i]Later[/i]");
+        commented.comments.push("Check the tone".to_string());
+        let rows = vec![
+            header,
+            vec![
+                cell("1"),
+                cell("()"),
+                cell("Hey!\nYou!"),
+                cell(""),
+                cell("bold=true"),
+            ],
+            vec![cell(""), cell(""), cell(""), cell(""), cell("")],
+            vec![
+                cell("2"),
+                cell("ST"),
+                commented,
+                cell("small font"),
+                cell("big"),
+            ],
+        ];
+        let d = blocks_to_doc(vec![DocxBlock::Table(rows)], &DocxImportOptions::default()).unwrap();
+
+        assert_eq!(d.balloons.len(), 2);
+        assert_eq!(d.balloons[0].tl_content, vec!["Hey!", "You!"]);
+        assert_eq!(d.balloons[0].page, Some(0));
+        assert_eq!(d.balloons[0].style.bold, Some(true));
+        assert!(d.balloons[1].style.is_empty());
+        assert_eq!(d.balloons[1].btype, TYPES::ST);
+        assert_eq!(d.balloons[1].tl_content, vec!["[i]Later[/i]"]);
+        assert_eq!(d.balloons[1].comments, vec!["small font", "Check the tone"]);
+
+        // Without a header the configured columns are used
+        let options = DocxImportOptions {
+            columns: "tl, pr".parse().unwrap(),
+            detect_header: true,
+        };
+        let rows = vec![vec![cell("Hi"), cell("Hello")]];
+        let d = blocks_to_doc(vec![DocxBlock::Table(rows)], &options).unwrap();
+        assert_eq!(d.balloons[0].tl_content, vec!["Hi"]);
+        assert_eq!(d.balloons[0].pr_content, vec!["Hello"]);
+        assert_eq!(d.balloons[0].page, None);
+    }
+}
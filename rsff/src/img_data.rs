@@ -9,4 +9,266 @@ pub struct BalloonImage {
     pub img_data: Vec<u8>,
 }
 
+impl BalloonImage {
+    /// Scales the image down to fit in a `max_size` square and encodes it as png.
+    /// Smaller images keep their size.
+    pub fn thumbnail(&self, max_size: u32) -> image::ImageResult<BalloonImage> {
+        let img = image::load_from_memory(&self.img_data)?;
+        let img = if img.width() > max_size || img.height() > max_size {
+            img.thumbnail(max_size, max_size)
+        } else {
+            img
+        };
+
+        let mut img_data = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut img_data, image::ImageFormat::Png)?;
+        Ok(BalloonImage {
+            img_type: CropFormat::Png.extension().to_string(),
+            img_data: img_data.into_inner(),
+        })
+    }
+}
+
 pub type DocumentImage = Option<Vec<String>>;
+
+/// A rectangular area on a page image, in page pixels.
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Creates a region from two opposite corners, in any order.
+    pub fn from_corners(a: (u32, u32), b: (u32, u32)) -> Self {
+        Self {
+            x: a.0.min(b.0),
+            y: a.1.min(b.1),
+            width: a.0.abs_diff(b.0),
+            height: a.1.abs_diff(b.1),
+        }
+    }
+
+    /// Right edge of the region, exclusive. Saturates for regions reaching past `u32::MAX`.
+    pub fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    /// Bottom edge of the region, exclusive. Saturates for regions reaching past `u32::MAX`.
+    pub fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+
+    /// Center pixel of the region.
+    pub fn center(&self) -> (u32, u32) {
+        (
+            self.x.saturating_add(self.width / 2),
+            self.y.saturating_add(self.height / 2),
+        )
+    }
+
+    /// Checks if the given page pixel is inside the region.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// Area shared by two regions.
+    pub fn intersection_area(&self, other: &Region) -> u64 {
+        let w = self
+            .right()
+            .min(other.right())
+            .saturating_sub(self.x.max(other.x));
+        let h = self
+            .bottom()
+            .min(other.bottom())
+            .saturating_sub(self.y.max(other.y));
+        w as u64 * h as u64
+    }
+}
+
+/// Encodings a cropped balloon image can be stored as.
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub enum CropFormat {
+    #[default]
+    Png,
+    WebP,
+}
+
+impl CropFormat {
+    pub const ALL: [Self; 2] = [CropFormat::Png, CropFormat::WebP];
+
+    /// File extension of the format, used as `BalloonImage.img_type`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CropFormat::Png => "png",
+            CropFormat::WebP => "webp",
+        }
+    }
+
+    fn image_format(&self) -> image::ImageFormat {
+        match self {
+            CropFormat::Png => image::ImageFormat::Png,
+            CropFormat::WebP => image::ImageFormat::WebP,
+        }
+    }
+}
+
+impl std::fmt::Display for CropFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CropFormat::Png => write!(f, "PNG"),
+            CropFormat::WebP => write!(f, "WebP"),
+        }
+    }
+}
+
+/// Crops a region out of a page image file. See `crop_image`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rsff::img_data::{crop_page, CropFormat, Region};
+///
+/// let img = crop_page("page_01.png", &Region::new(10, 10, 200, 120), CropFormat::Png).unwrap();
+/// assert_eq!(img.img_type, "png");
+/// ```
+pub fn crop_page<P: AsRef<std::path::Path>>(
+    page: P,
+    region: &Region,
+    format: CropFormat,
+) -> image::ImageResult<BalloonImage> {
+    crop_image(&image::open(page)?, region, format)
+}
+
+/// Crops a region out of a decoded page and encodes it as a `BalloonImage`.
+/// Parts of the region outside of the page are ignored.
+pub fn crop_image(
+    page: &image::DynamicImage,
+    region: &Region,
+    format: CropFormat,
+) -> image::ImageResult<BalloonImage> {
+    let x = region.x.min(page.width());
+    let y = region.y.min(page.height());
+    let width = region.width.min(page.width() - x);
+    let height = region.height.min(page.height() - y);
+
+    if width == 0 || height == 0 {
+        return Err(image::ImageError::Parameter(
+            image::error::ParameterError::from_kind(
+                image::error::ParameterErrorKind::DimensionMismatch,
+            ),
+        ));
+    }
+
+    let mut img_data = std::io::Cursor::new(Vec::new());
+    page.crop_imm(x, y, width, height)
+        .write_to(&mut img_data, format.image_format())?;
+
+    Ok(BalloonImage {
+        img_type: format.extension().to_string(),
+        img_data: img_data.into_inner(),
+    })
+}
+
+/// Reads the dimensions of a page image without decoding the whole image.
+pub fn page_dimensions<P: AsRef<std::path::Path>>(path: P) -> image::ImageResult<(u32, u32)> {
+    image::image_dimensions(path)
+}
+
+#[cfg(test)]
+mod img_data_tests {
+    use super::{crop_image, BalloonImage, CropFormat, Region};
+
+    #[test]
+    fn region_from_corners() {
+        let r = Region::from_corners((40, 10), (10, 30));
+        assert_eq!(r, Region::new(10, 10, 30, 20));
+    }
+
+    #[test]
+    fn region_near_u32_max() {
+        let r = Region::new(u32::MAX - 10, u32::MAX - 10, 100, 100);
+        assert_eq!((r.right(), r.bottom()), (u32::MAX, u32::MAX));
+        assert!(r.contains(u32::MAX - 1, u32::MAX - 5));
+        assert!(!r.contains(10, 10));
+        assert_eq!(r.intersection_area(&r), 100);
+        assert_eq!(r.center(), (u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn crop_image_formats() {
+        let page = image::DynamicImage::ImageRgb8(image::RgbImage::new(100, 50));
+
+        for format in CropFormat::ALL {
+            let img = crop_image(&page, &Region::new(10, 10, 30, 20), format).unwrap();
+            assert_eq!(img.img_type, format.extension());
+
+            let decoded = image::load_from_memory(&img.img_data).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (30, 20));
+        }
+    }
+
+    #[test]
+    fn balloon_image_thumbnail() {
+        let page = image::DynamicImage::ImageRgb8(image::RgbImage::new(400, 100));
+        let img = crop_image(&page, &Region::new(0, 0, 400, 100), CropFormat::WebP).unwrap();
+
+        let thumb = img.thumbnail(80).unwrap();
+        assert_eq!(thumb.img_type, "png");
+        let decoded = image::load_from_memory(&thumb.img_data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (80, 20));
+
+        let broken = BalloonImage {
+            img_type: "png".to_string(),
+            img_data: vec![1, 2, 3],
+        };
+        assert!(broken.thumbnail(80).is_err());
+    }
+
+    #[test]
+    fn crop_image_clamps_to_page() {
+        let page = image::DynamicImage::ImageRgb8(image::RgbImage::new(100, 50));
+
+        let img = crop_image(&page, &Region::new(80, 40, 50, 50), CropFormat::Png).unwrap();
+        let decoded = image::load_from_memory(&img.img_data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (20, 10));
+
+        assert!(crop_image(&page, &Region::new(100, 0, 10, 10), CropFormat::Png).is_err());
+    }
+
+    #[test]
+    fn region_intersection_area() {
+        let r = Region::new(0, 0, 10, 10);
+        assert_eq!(r.intersection_area(&Region::new(5, 5, 10, 10)), 25);
+        assert_eq!(r.intersection_area(&Region::new(10, 0, 10, 10)), 0);
+        assert_eq!(r.intersection_area(&Region::new(2, 2, 2, 2)), 4);
+    }
+
+    #[test]
+    fn region_contains() {
+        let r = Region::new(10, 10, 30, 20);
+        assert!(r.contains(10, 10));
+        assert!(r.contains(39, 29));
+        assert!(!r.contains(40, 29));
+        assert!(!r.contains(5, 15));
+    }
+}
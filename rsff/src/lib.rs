@@ -4,24 +4,52 @@
 //! facilitate the work of teams translating content such as manga, manhwa, manhua, webtoons, etc.
 
 pub use balloon::Balloon;
-pub use consts::{OUT, TYPES};
+pub use consts::{BalloonStatus, CustomType, OUT, TYPES};
 
 use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{Cursor, Read, Write};
 use std::path::Path;
 
-use flate2::read::ZlibDecoder;
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
-
+use codec::Codec;
+use community::ScriptFormat;
+use docx_export::DocxExportOptions;
+use docx_import::DocxImportOptions;
+use img_data::Region;
+use reading_order::{page_order, ReadingDirection};
+use script_export::HtmlExportOptions;
 use serde::{Deserialize, Serialize};
+use sfx::{Sfx, TXT_SFX_MARKER};
+use spreadsheet::SheetImportOptions;
+use style::{BalloonStyle, StylePreset};
+use translate::TranslationProvider;
 
 pub mod balloon;
+pub mod codec;
+pub mod community;
 pub mod consts;
+pub mod detect;
+pub mod docx_export;
 mod docx_handlers;
+pub mod docx_import;
 pub mod img_data;
+pub mod markup;
+pub mod ocr;
+pub mod page_cache;
+pub mod po;
+pub mod reading_order;
+pub mod script_export;
 mod serde_overwrites;
+pub mod sffb;
+pub mod sfx;
+pub mod spreadsheet;
+pub mod style;
+#[cfg(test)]
+mod test_util;
+pub mod translate;
+pub mod typeset;
+pub mod typeset_script;
+pub mod xliff;
 
 #[cfg(feature = "async-io")]
 use tokio::{
@@ -31,6 +59,30 @@ use tokio::{
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Declares a custom balloon type in txt files: `#TYPE <prefix> <color> <name>`
+const TXT_TYPE_MARKER: &str = "#TYPE";
+
+/// Starts the balloons of a page in txt files: `#PAGE <number>`, numbers start from 1.
+/// `#PAGE` alone starts unplaced balloons.
+const TXT_PAGE_MARKER: &str = "#PAGE";
+
+/// Styles the next balloon in txt files: `#STYLE preset=<name>; <attributes>`,
+/// both parts are optional. Attributes are written like [`BalloonStyle`]'s.
+const TXT_STYLE_MARKER: &str = "#STYLE";
+
+/// Outcome of merging a translation file into a document, see [`xliff`] and [`po`].
+///
+/// `merged`: Number of merged entries.\
+/// `mismatched`: Positions of the balloons whose entries have another source text or type,
+/// the balloons were changed since the export so they are left as they are.\
+/// `unmatched`: Number of entries without a balloon, they are skipped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub merged: usize,
+    pub mismatched: Vec<usize>,
+    pub unmatched: usize,
+}
+
 /// A document containing all of your translation data.
 ///
 /// # Examples
@@ -52,7 +104,7 @@ type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 /// d.balloons.push(b);
 /// ```
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document {
     /// sff (Scanlation File Format) version. No big changes expected.
     pub METADATA_SCRIPT_VERSION: String,
@@ -64,6 +116,15 @@ pub struct Document {
     pub balloons: Vec<Balloon>,
     /// Optional image paths for the work
     pub images: img_data::DocumentImage,
+    /// Reading direction of the work, used when sorting balloons.
+    #[serde(default)]
+    pub reading_direction: ReadingDirection,
+    /// Named typesetting styles of the work.
+    #[serde(default = "style::default_presets")]
+    pub style_presets: Vec<StylePreset>,
+    /// Balloon types of the work besides the built-in ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_types: Vec<CustomType>,
 }
 
 impl Default for Document {
@@ -80,12 +141,17 @@ impl Default for Document {
             METADATA_INFO: String::from("Num"),
             balloons: Vec::new(),
             images: None,
+            reading_direction: ReadingDirection::default(),
+            style_presets: style::default_presets(),
+            custom_types: Vec::new(),
         }
     }
 }
 
 impl Document {
-    /// Open a supported sffx, sffz or txt file and generate a document.
+    /// Open a supported sffx, sffz, sffb or txt file and generate a document.
+    /// Scripts of other tools, txt files and BallonsTranslator's json projects,
+    /// are read with [`community`].
     ///
     /// `fp`: full path for the file.
     ///
@@ -114,25 +180,28 @@ impl Document {
             Some(e) => {
                 if e == OsStr::new("txt") {
                     let text = Self::read_file_to_string(p)?;
-                    Ok(Self::txt_to_doc(text).map_err(|e| {
+                    Ok(Self::script_txt_to_doc(text).map_err(|e| {
                         std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
                     })?)
+                } else if e == OsStr::new("json") {
+                    let jsn = Self::read_file_to_string(p)?;
+                    community::read_ballons_translator(&jsn).map_err(|e| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
+                    })
                 } else if e == OsStr::new("sffx") {
                     let jsn = Self::read_file_to_string(p)?;
                     Ok(Self::json_to_doc(jsn).map_err(|e| {
                         std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
                     })?)
                 } else if e == OsStr::new("sffz") {
-                    let compressed = Self::read_file_to_vecu8(p)?;
-                    let mut jsn = String::new();
-                    let mut decoder = ZlibDecoder::new(&*compressed);
-                    decoder.read_to_string(&mut jsn)?;
-                    Ok(Self::json_to_doc(jsn).map_err(|e| {
-                        std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
-                    })?)
+                    Self::from_sffz(&Self::read_file_to_vecu8(p)?)
+                } else if e == OsStr::new("sffb") {
+                    sffb::read(std::io::BufReader::new(File::open(p)?))
+                } else if e == OsStr::new("csv") || e == OsStr::new("xlsx") {
+                    Self::open_sheet(p, &SheetImportOptions::default())
                 } else if e == OsStr::new("docx") {
                     let f = File::open(p)?;
-                    Self::docx_to_doc(f).map_err(|e| {
+                    Self::docx_to_doc(f, &DocxImportOptions::default()).map_err(|e| {
                         std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())
                     })
                 } else {
@@ -146,7 +215,7 @@ impl Document {
     }
 
     /// Async version of `open` function.
-    /// Open a supported sffx, sffz or txt file and generate a document.
+    /// Open a supported sffx, sffz, sffb or txt file and generate a document.
     ///
     /// `fp`: full path for the file.
     ///
@@ -176,29 +245,35 @@ impl Document {
             Some(e) => {
                 if e == OsStr::new("txt") {
                     let text = Self::async_read_file_to_string(p).await?;
-                    Ok(Self::txt_to_doc(text).map_err(|e| {
+                    Ok(Self::script_txt_to_doc(text).map_err(|e| {
                         tokio::io::Error::new(tokio::io::ErrorKind::InvalidData, e.to_string())
                     })?)
+                } else if e == OsStr::new("json") {
+                    let jsn = Self::async_read_file_to_string(p).await?;
+                    community::read_ballons_translator(&jsn).map_err(|e| {
+                        tokio::io::Error::new(tokio::io::ErrorKind::InvalidData, e.to_string())
+                    })
                 } else if e == OsStr::new("sffx") {
                     let jsn = Self::async_read_file_to_string(p).await?;
                     Ok(Self::json_to_doc(jsn).map_err(|e| {
                         tokio::io::Error::new(tokio::io::ErrorKind::InvalidData, e.to_string())
                     })?)
                 } else if e == OsStr::new("sffz") {
-                    let compressed = Self::async_read_file_to_vecu8(p).await?;
-                    let mut jsn = String::new();
-                    let mut decoder = ZlibDecoder::new(&*compressed);
-                    decoder.read_to_string(&mut jsn)?;
-                    Ok(Self::json_to_doc(jsn).map_err(|e| {
+                    Self::from_sffz(&Self::async_read_file_to_vecu8(p).await?)
+                } else if e == OsStr::new("sffb") {
+                    Self::from_sffb(&Self::async_read_file_to_vecu8(p).await?)
+                } else if e == OsStr::new("csv") || e == OsStr::new("xlsx") {
+                    let bytes = Self::async_read_file_to_vecu8(p).await?;
+                    Self::sheet_to_doc(bytes, e, &SheetImportOptions::default()).map_err(|e| {
                         tokio::io::Error::new(tokio::io::ErrorKind::InvalidData, e.to_string())
-                    })?)
+                    })
                 } else if e == OsStr::new("docx") {
                     let mut f = fs::File::open(p).await?;
                     let mut uwu = Vec::new();
                     f.read_to_end(&mut uwu).await?;
-                    Self::docx_to_doc(Cursor::new(uwu)).map_err(|e| {
-                        tokio::io::Error::new(tokio::io::ErrorKind::InvalidData, e.to_string())
-                    })
+                    Self::docx_to_doc(Cursor::new(uwu), &DocxImportOptions::default()).map_err(
+                        |e| tokio::io::Error::new(tokio::io::ErrorKind::InvalidData, e.to_string()),
+                    )
                 } else {
                     Err(tokio::io::Error::new(
                         tokio::io::ErrorKind::InvalidInput,
@@ -209,10 +284,76 @@ impl Document {
         }
     }
 
-    fn docx_to_doc<R: Read + std::io::Seek>(r: R) -> Result<Document> {
-        let docx_str = docx_handlers::parse_docx_to_string(r)?;
+    /// Open a docx file with the given import options.
+    /// `open` uses the default options.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rsff::Document;
+    /// use rsff::docx_import::DocxImportOptions;
+    ///
+    /// let options = DocxImportOptions {
+    ///     columns: "page, tl, pr".parse().unwrap(),
+    ///     detect_header: false,
+    /// };
+    /// let d = Document::open_docx("delivery.docx", &options).unwrap();
+    /// ```
+    pub fn open_docx<P: ?Sized + AsRef<Path>>(
+        file_path: &P,
+        options: &DocxImportOptions,
+    ) -> std::io::Result<Document> {
+        let f = File::open(file_path)?;
+        Self::docx_to_doc(f, options)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    /// Open a csv or xlsx file with the given import options.
+    /// `open` uses the default options.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rsff::Document;
+    /// use rsff::spreadsheet::SheetImportOptions;
+    ///
+    /// let options = SheetImportOptions {
+    ///     columns: "index, source, tl".parse().unwrap(),
+    ///     detect_header: true,
+    /// };
+    /// let d = Document::open_sheet("qc.xlsx", &options).unwrap();
+    /// ```
+    pub fn open_sheet<P: ?Sized + AsRef<Path>>(
+        file_path: &P,
+        options: &SheetImportOptions,
+    ) -> std::io::Result<Document> {
+        let p = file_path.as_ref();
+        let bytes = Self::read_file_to_vecu8(p)?;
+        Self::sheet_to_doc(bytes, p.extension().unwrap_or_default(), options)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    fn sheet_to_doc(bytes: Vec<u8>, ext: &OsStr, options: &SheetImportOptions) -> Result<Document> {
+        if ext == OsStr::new("xlsx") {
+            spreadsheet::read_xlsx_bytes(bytes, options)
+        } else {
+            spreadsheet::read_csv(&*bytes, options)
+        }
+    }
+
+    fn docx_to_doc<R: Read + std::io::Seek>(r: R, options: &DocxImportOptions) -> Result<Document> {
+        let blocks = docx_handlers::parse_docx_blocks(r)?;
+
+        docx_import::blocks_to_doc(blocks, options)
+    }
 
-        Self::txt_to_doc(docx_str)
+    // Reads rsff's own txt format, or the txt scripts of other tools.
+    fn script_txt_to_doc(txt: String) -> Result<Document> {
+        match community::detect_txt(&txt) {
+            Some(ScriptFormat::LabelPlus) => community::read_labelplus(&txt),
+            Some(_) => Ok(community::read_paged_txt(&txt)),
+            None => Self::txt_to_doc(txt),
+        }
     }
 
     // Generate a document from lossy text.
@@ -222,46 +363,90 @@ impl Document {
         let mut d = Document::default();
         let mut texts: Vec<String> = Vec::with_capacity(200);
 
-        let splitted = txt
-            .split("\n")
-            .filter(|s| !s.is_empty())
-            .collect::<Vec<&str>>();
+        // Lines with the page they are on
+        let mut splitted = Vec::new();
+        let mut sfx_lines = Vec::new();
+        let mut in_sfx_section = false;
+        let mut page = None;
+        // Style of the next balloon
+        let mut style = None;
+        for line in txt.split("\n").filter(|s| !s.trim().is_empty()) {
+            if line.trim() == TXT_SFX_MARKER {
+                in_sfx_section = true;
+                page = None;
+            } else if let Some(number) = line.trim().strip_prefix(TXT_PAGE_MARKER) {
+                page = Self::parse_txt_page(number)?;
+            } else if let Some(attrs) = line.trim().strip_prefix(TXT_STYLE_MARKER) {
+                style = Some(Self::parse_txt_style(attrs)?);
+            } else if in_sfx_section {
+                sfx_lines.extend(Sfx::from_txt(line).map(|(n, sfx)| (n, page, sfx, style.take())));
+            } else if let Some(decl) = line.trim().strip_prefix(TXT_TYPE_MARKER) {
+                d.add_custom_type(Self::parse_txt_type(decl)?)?;
+            } else {
+                splitted.push((page, line, style.take()));
+            }
+        }
         let mut is_previous_double_slash: bool = false;
+        // Style of the balloon being read, multi-line balloons are styled before their first line
+        let mut style = None;
 
         for i in 0..splitted.len() {
-            if splitted[i].contains("//") {
+            let (page, line) = (splitted[i].0, splitted[i].1);
+            if line.contains("//") {
                 continue;
             }
+            if let Some(s) = splitted[i].2.take() {
+                style = Some(s);
+            }
 
-            let current = splitted[i];
+            let (btype, current) = d.split_txt_header(line);
 
             let mut b = Balloon {
-                btype: Self::decide_b_type_from_txt_line_headers(current),
+                btype,
+                page,
                 ..Default::default()
             };
 
-            let next = splitted.get(i + 1).unwrap_or(&"");
+            let next = splitted.get(i + 1).map_or("", |(_, next, _)| next);
 
             if !next.contains("//") {
+                if let Some((preset, style)) = style.take() {
+                    b.preset = preset;
+                    b.style = style;
+                }
                 if is_previous_double_slash {
-                    texts.push(current[4..current.len()].trim().to_string());
+                    texts.push(current.to_string());
                     b.tl_content = texts.clone();
                     d.balloons.push(b);
                     is_previous_double_slash = false;
                     continue;
                 } else {
-                    b.tl_content
-                        .push(current[4..current.len()].trim().to_string());
+                    b.tl_content.push(current.to_string());
                     d.balloons.push(b);
                     is_previous_double_slash = false;
                     continue;
                 }
             } else {
-                texts.push(current[4..current.len()].trim().to_string());
+                texts.push(current.to_string());
                 is_previous_double_slash = true;
             }
         }
 
+        // Put the sound effects back to their places
+        sfx_lines.sort_by_key(|(n, _, _, _)| *n);
+        for (n, page, sfx, style) in sfx_lines {
+            let (preset, style) = style.unwrap_or_default();
+            let b = Balloon {
+                sfx: Some(sfx),
+                page,
+                preset,
+                style,
+                ..Default::default()
+            };
+            let i = n.saturating_sub(1).min(d.balloons.len());
+            d.balloons.insert(i, b);
+        }
+
         Ok(d)
     }
 
@@ -272,19 +457,105 @@ impl Document {
         Ok(d)
     }
 
-    fn decide_b_type_from_txt_line_headers(ln: &str) -> TYPES {
-        let s = &ln[0..2];
+    // Splits a txt line into its balloon type and text.
+    // Lines without a known `<prefix>:` header are dialogues.
+    fn split_txt_header<'a>(&self, ln: &'a str) -> (TYPES, &'a str) {
+        let mut types = self
+            .balloon_types()
+            .into_iter()
+            .map(|t| (self.type_prefix(&t), t))
+            .collect::<Vec<_>>();
+        // "SFX" must be tried before "S"
+        types.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        for (prefix, t) in types {
+            if let Some(text) = ln
+                .strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_prefix(':'))
+            {
+                return (t, text.trim());
+            }
+        }
+        // Untagged lines are dialogues, keep all of their text
+        (TYPES::DIALOGUE, ln.trim())
+    }
+
+    // Preset and own style of a balloon as the attributes of a `#STYLE` line.
+    // `None` if the balloon has neither.
+    pub(crate) fn txt_style(b: &Balloon) -> Option<String> {
+        let mut attrs = Vec::from_iter(b.preset.iter().map(|p| format!("preset={}", p)));
+        if !b.style.is_empty() {
+            attrs.push(b.style.to_string());
+        }
+        (!attrs.is_empty()).then(|| attrs.join("; "))
+    }
+
+    // Parses the attributes of a `#STYLE` line into a preset name and a style.
+    pub(crate) fn parse_txt_style(attrs: &str) -> Result<(Option<String>, BalloonStyle)> {
+        let mut preset = None;
+        let mut style = Vec::new();
+        for attr in attrs.split(';').map(|a| a.trim()).filter(|a| !a.is_empty()) {
+            match attr.strip_prefix("preset=") {
+                Some(name) => preset = Some(name.trim().to_string()),
+                None => style.push(attr),
+            }
+        }
+        Ok((preset, style.join(";").parse()?))
+    }
 
-        match s {
-            "()" => TYPES::DIALOGUE,
-            "OT" => TYPES::OT,
-            "[]" => TYPES::SQUARE,
-            "ST" => TYPES::ST,
-            "{}" => TYPES::THINKING,
-            _ => TYPES::DIALOGUE,
+    // Parses the `<prefix> <color> <name>` part of a custom type declaration.
+    fn parse_txt_type(decl: &str) -> Result<CustomType> {
+        let mut parts = decl.trim().splitn(3, char::is_whitespace);
+        let (Some(prefix), Some(color), Some(name)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("Invalid type declaration: {}", decl.trim()).into());
+        };
+        Ok(CustomType::new(name.trim(), prefix, color.parse()?))
+    }
+
+    // Parses the `<number>` part of a page marker, `None` if it has no number.
+    fn parse_txt_page(number: &str) -> Result<Option<usize>> {
+        let number = number.trim();
+        if number.is_empty() {
+            return Ok(None);
+        }
+        match number.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(Some(n - 1)),
+            _ => Err(format!("Invalid page number: {}", number).into()),
         }
     }
 
+    // Page marker line of a page, pages of txt files start from 1.
+    fn txt_page_marker(page: Option<usize>) -> String {
+        match page {
+            Some(page) => format!("{} {}", TXT_PAGE_MARKER, page + 1),
+            None => TXT_PAGE_MARKER.to_string(),
+        }
+    }
+
+    // Lines of the sound effect section after its marker, numbered by their balloon positions.
+    // Styled sound effects are preceded by their `#STYLE` lines.
+    // Empty if there are no sound effects.
+    pub(crate) fn txt_sfx_section(&self) -> Vec<String> {
+        let paged = self.balloons.iter().any(|b| b.page.is_some());
+        let mut lines = Vec::new();
+        let mut page = None;
+        for (i, b) in self.balloons.iter().enumerate() {
+            let Some(sfx) = &b.sfx else {
+                continue;
+            };
+            if paged && b.page != page {
+                lines.push(Self::txt_page_marker(b.page));
+                page = b.page;
+            }
+            if let Some(style) = Self::txt_style(b) {
+                lines.push(format!("{} {}", TXT_STYLE_MARKER, style));
+            }
+            lines.push(format!("{}: {}", i + 1, sfx.to_txt()));
+        }
+        lines
+    }
+
     // Generate text of the whole document.
     fn read_file_to_string(p: &Path) -> std::io::Result<String> {
         let mut s = String::new();
@@ -361,6 +632,351 @@ impl Document {
         self.balloons.push(b);
     }
 
+    /// Iterates over the balloons placed on the given page, with their indexes.
+    pub fn balloons_on_page(&self, page: usize) -> impl Iterator<Item = (usize, &Balloon)> {
+        self.balloons
+            .iter()
+            .enumerate()
+            .filter(move |(_, b)| b.page == Some(page))
+    }
+
+    /// Adds balloons found by the balloon detector for a single page.
+    ///
+    /// Balloons overlapping one already placed on the same page are skipped,
+    /// the rest are inserted after the balloons of the previous pages.
+    /// Returns the indexes of the inserted balloons.
+    pub fn add_detected_balloons(
+        &mut self,
+        page: usize,
+        detected: Vec<Balloon>,
+    ) -> std::ops::Range<usize> {
+        let existing = self
+            .balloons_on_page(page)
+            .filter_map(|(_, b)| b.region)
+            .collect::<Vec<_>>();
+
+        let new = detected
+            .into_iter()
+            .filter(|d| {
+                d.region.is_some_and(|r| {
+                    !existing
+                        .iter()
+                        .any(|e| r.intersection_area(e) * 2 > r.area().min(e.area()))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let at = self
+            .balloons
+            .iter()
+            .position(|b| b.page.is_some_and(|p| p > page))
+            .unwrap_or(self.balloons.len());
+        let count = new.len();
+        self.balloons.splice(at..at, new);
+
+        at..at + count
+    }
+
+    /// Source texts of the balloons waiting for a machine translation draft:
+    /// balloons with source text but no translation. One string per balloon,
+    /// source lines joined with newlines.
+    pub fn machine_translation_sources(&self) -> Vec<(usize, String)> {
+        self.balloons
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.tl_chars() == 0 && !b.source_content.is_empty())
+            .map(|(i, b)| (i, b.source_content.join("\n")))
+            .collect()
+    }
+
+    /// Fills the translations with machine translation drafts and marks them as
+    /// `BalloonStatus::MachineDraft`. Drafts are `(index, source, draft)`, `source` is
+    /// the text of [`Document::machine_translation_sources`] the draft was made from.
+    ///
+    /// Balloons translated in the meantime are left alone, so are balloons whose source text
+    /// isn't the draft's anymore, as balloons may be added or moved while waiting for drafts.
+    /// Returns how many balloons got a draft.
+    pub fn apply_machine_drafts(&mut self, drafts: Vec<(usize, String, String)>) -> usize {
+        let mut applied = 0;
+        for (i, source, draft) in drafts {
+            let Some(b) = self.balloons.get_mut(i) else {
+                continue;
+            };
+            if b.tl_chars() != 0 || b.source_content.join("\n") != source {
+                continue;
+            }
+            b.tl_content = draft.lines().map(|l| l.to_string()).collect();
+            b.status = BalloonStatus::MachineDraft;
+            applied += 1;
+        }
+        applied
+    }
+
+    /// Pre-fills empty translations using a machine translation provider,
+    /// in a single batch. See [`Document::apply_machine_drafts`].
+    pub fn prefill_machine_translation(
+        &mut self,
+        provider: &dyn TranslationProvider,
+    ) -> Result<usize> {
+        let pending = self.machine_translation_sources();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let sources = Vec::from_iter(pending.iter().map(|(_, s)| s.clone()));
+        let suggestions = provider.translate(&sources)?;
+        let drafts = pending
+            .into_iter()
+            .zip(suggestions)
+            .map(|((i, source), draft)| (i, source, draft))
+            .collect();
+        Ok(self.apply_machine_drafts(drafts))
+    }
+
+    /// Reading order of the balloons, by page then by position on the page
+    /// in the document's reading direction. `order[new_index] = old_index`.
+    ///
+    /// `panels[page]` holds the panels of a page, pages without panels are
+    /// ordered by balloon positions only. Balloons not placed on a page keep their indexes.
+    pub fn reading_order(&self, panels: &[Vec<Region>]) -> Vec<usize> {
+        let mut pages: std::collections::BTreeMap<usize, Vec<(usize, Region)>> =
+            std::collections::BTreeMap::new();
+        for (i, b) in self.balloons.iter().enumerate() {
+            if let (Some(page), Some(region)) = (b.page, b.region) {
+                pages.entry(page).or_default().push((i, region));
+            }
+        }
+
+        let mut placed = Vec::new();
+        for (page, balloons) in pages {
+            let regions = balloons.iter().map(|(_, r)| *r).collect::<Vec<_>>();
+            let page_panels = panels.get(page).map(|p| p.as_slice()).unwrap_or(&[]);
+            placed.extend(
+                page_order(&regions, page_panels, self.reading_direction)
+                    .into_iter()
+                    .map(|j| balloons[j].0),
+            );
+        }
+
+        let mut placed = placed.into_iter();
+        (0..self.balloons.len())
+            .map(|i| {
+                if self.balloons[i].page.is_some() && self.balloons[i].region.is_some() {
+                    placed.next().unwrap_or(i)
+                } else {
+                    i
+                }
+            })
+            .collect()
+    }
+
+    /// Reorders the balloons. `order[new_index] = old_index`, like the one
+    /// [`Document::reading_order`] returns. Orders that aren't a permutation of the
+    /// balloon indexes are rejected and the balloons are left untouched.
+    pub fn reorder_balloons(&mut self, order: &[usize]) -> Result<()> {
+        let mut seen = vec![false; self.balloons.len()];
+        let permutation = order.len() == self.balloons.len()
+            && order.iter().all(|i| {
+                seen.get_mut(*i)
+                    .is_some_and(|s| !std::mem::replace(s, true))
+            });
+        if !permutation {
+            return Err("Balloon order must be a permutation of the balloon indexes!".into());
+        }
+
+        let mut old = std::mem::take(&mut self.balloons)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.balloons = order.iter().filter_map(|i| old[*i].take()).collect();
+        Ok(())
+    }
+
+    /// Sorts the balloons into reading order. See [`Document::reading_order`].
+    pub fn sort_balloons(&mut self, panels: &[Vec<Region>]) {
+        let order = self.reading_order(panels);
+        self.reorder_balloons(&order)
+            .expect("reading order is a permutation");
+    }
+
+    /// Preset with the given name.
+    pub fn style_preset(&self, name: &str) -> Option<&StylePreset> {
+        self.style_presets.iter().find(|p| p.name == name)
+    }
+
+    /// Preset a balloon is styled with: the one it picked,
+    /// or the first one mapped to its type.
+    pub fn preset_of(&self, b: &Balloon) -> Option<&StylePreset> {
+        match &b.preset {
+            Some(name) => self.style_preset(name),
+            None => self
+                .style_presets
+                .iter()
+                .find(|p| p.types.contains(&b.btype)),
+        }
+    }
+
+    /// Full style of a balloon, its own style laid over its preset.
+    pub fn balloon_style(&self, b: &Balloon) -> BalloonStyle {
+        match self.preset_of(b) {
+            Some(preset) => b.style.over(&preset.style),
+            None => b.style.clone(),
+        }
+    }
+
+    /// Markup mistakes in translation and proofread lines, with their balloon indexes.
+    pub fn markup_errors(&self) -> Vec<(usize, markup::MarkupError)> {
+        self.balloons
+            .iter()
+            .enumerate()
+            .flat_map(|(i, b)| {
+                b.tl_content
+                    .iter()
+                    .chain(&b.pr_content)
+                    .flat_map(|l| markup::validate(l))
+                    .map(move |e| (i, e))
+            })
+            .collect()
+    }
+
+    /// Built-in types followed by the custom types of the document.
+    pub fn balloon_types(&self) -> Vec<TYPES> {
+        TYPES::BUILTIN
+            .into_iter()
+            .chain(
+                self.custom_types
+                    .iter()
+                    .map(|t| TYPES::CUSTOM(t.name.clone())),
+            )
+            .collect()
+    }
+
+    /// Type with the given txt prefix or name, names are case insensitive.
+    pub fn type_from_label(&self, label: &str) -> Option<TYPES> {
+        self.balloon_types().into_iter().find(|t| {
+            let name = match t {
+                TYPES::CUSTOM(name) => name.clone(),
+                t => format!("{:?}", t),
+            };
+            self.type_prefix(t) == label || name.eq_ignore_ascii_case(label)
+        })
+    }
+
+    /// Custom type with the given name.
+    pub fn custom_type(&self, name: &str) -> Option<&CustomType> {
+        self.custom_types.iter().find(|t| t.name == name)
+    }
+
+    /// Prefix of a type in txt files.
+    /// Custom types the document doesn't define use their names.
+    pub fn type_prefix(&self, t: &TYPES) -> String {
+        match t {
+            TYPES::CUSTOM(name) => self
+                .custom_type(name)
+                .map_or(name.as_str(), |t| t.prefix.as_str())
+                .to_string(),
+            t => t.builtin_prefix().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// The balloon as a txt line, with the prefix of its type.
+    /// Unlike the balloon's own `Display`, custom types are written with their prefix
+    /// so the line can be read back by the document.
+    pub fn balloon_line(&self, b: &Balloon) -> String {
+        match &b.sfx {
+            Some(sfx) => format!("SFX: {}", sfx),
+            None => format!("{}: {}", self.type_prefix(&b.btype), b.content_line()),
+        }
+    }
+
+    /// Adds a custom balloon type.
+    /// Fails if its name or prefix is empty or already taken.
+    pub fn add_custom_type(&mut self, t: CustomType) -> Result<()> {
+        self.check_custom_type(&t, None)?;
+        self.custom_types.push(t);
+        Ok(())
+    }
+
+    /// Replaces the custom type named `name`. Balloons and presets of the type
+    /// follow it if it's renamed.
+    pub fn update_custom_type(&mut self, name: &str, t: CustomType) -> Result<()> {
+        let i = self
+            .custom_types
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| format!("No balloon type named {}", name))?;
+        self.check_custom_type(&t, Some(name))?;
+
+        if t.name != name {
+            let (old, new) = (
+                TYPES::CUSTOM(name.to_string()),
+                TYPES::CUSTOM(t.name.clone()),
+            );
+            self.replace_type(&old, Some(new));
+        }
+        self.custom_types[i] = t;
+        Ok(())
+    }
+
+    /// Removes a custom type. Its balloons become dialogues.
+    /// Returns false if there is no type named `name`.
+    pub fn remove_custom_type(&mut self, name: &str) -> bool {
+        let len = self.custom_types.len();
+        self.custom_types.retain(|t| t.name != name);
+        if self.custom_types.len() == len {
+            return false;
+        }
+        self.replace_type(&TYPES::CUSTOM(name.to_string()), None);
+        true
+    }
+
+    // Replaces a type in balloons and presets, `None` removes it.
+    fn replace_type(&mut self, old: &TYPES, new: Option<TYPES>) {
+        for b in self.balloons.iter_mut().filter(|b| b.btype == *old) {
+            b.btype = new.clone().unwrap_or_default();
+        }
+        // Only the presets of the old type get the new one
+        for preset in self.style_presets.iter_mut() {
+            match &new {
+                Some(new) => {
+                    for t in preset.types.iter_mut().filter(|t| *t == old) {
+                        *t = new.clone();
+                    }
+                }
+                None => preset.types.retain(|t| t != old),
+            }
+        }
+    }
+
+    // `except` is the name of the type being replaced, if any.
+    fn check_custom_type(&self, t: &CustomType, except: Option<&str>) -> Result<()> {
+        if t.name.trim().is_empty() {
+            return Err("Balloon type name can't be empty".into());
+        }
+        if t.prefix.is_empty()
+            || t.prefix.contains(|c: char| c.is_whitespace() || c == ':')
+            || t.prefix.contains("//")
+        {
+            return Err(format!("Invalid balloon type prefix: {}", t.prefix).into());
+        }
+
+        let others = self
+            .custom_types
+            .iter()
+            .filter(|c| Some(c.name.as_str()) != except);
+        if others.clone().any(|c| c.name == t.name) {
+            return Err(format!("Balloon type {} already exists", t.name).into());
+        }
+        if others.clone().any(|c| c.prefix == t.prefix)
+            || TYPES::BUILTIN
+                .iter()
+                .any(|b| b.builtin_prefix() == Some(t.prefix.as_str()))
+        {
+            return Err(format!("Prefix {} is already used", t.prefix).into());
+        }
+        Ok(())
+    }
+
     /// Add an empty balloon to the document.
     pub fn add_balloon_empty(&mut self) {
         self.balloons.push(Balloon::default());
@@ -401,25 +1017,127 @@ impl Document {
         Ok(())
     }
 
-    // Save as a compressed JSON file.
-    fn save_zlib(&self, fp: impl Into<std::path::PathBuf>) -> std::io::Result<()> {
-        let mut f = File::create(fp.into())?;
-        let mut enc = ZlibEncoder::new(Vec::new(), Compression::best());
-        enc.write_all(self.to_json().as_bytes())?;
-        let encoded = enc.finish()?;
-        f.write_all(&encoded)?;
-        Ok(())
+    /// Compresses the JSON of the document with the codec, see [`codec`].
+    pub fn to_sffz(&self, codec: Codec) -> std::io::Result<Vec<u8>> {
+        codec::compress(self.to_json().as_bytes(), codec)
+    }
+
+    /// Reads a document from the contents of an sffz file.
+    /// The codec is detected from the header, files of older versions are read as zlib.
+    pub fn from_sffz(data: &[u8]) -> std::io::Result<Document> {
+        let jsn = String::from_utf8(codec::decompress(data)?)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+        Self::json_to_doc(jsn)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    /// Save as a compressed JSON file with the given codec.
+    /// `save` uses the default codec.
+    pub fn save_sffz(
+        &self,
+        fp: impl Into<std::path::PathBuf>,
+        codec: Codec,
+    ) -> std::io::Result<()> {
+        File::create(fp.into())?.write_all(&self.to_sffz(codec)?)
+    }
+
+    /// Writes the document as sffb, with balloon images stored as raw bytes.
+    /// The text is compressed with the codec, see [`sffb`].
+    pub fn to_sffb(&self, codec: Codec) -> std::io::Result<Vec<u8>> {
+        let mut sffb_file = Vec::new();
+        sffb::write(self, codec, &mut sffb_file)?;
+        Ok(sffb_file)
+    }
+
+    /// Reads a document from the contents of an sffb file.
+    pub fn from_sffb(data: &[u8]) -> std::io::Result<Document> {
+        sffb::read(data)
     }
 
-    // Save as a .docx file
-    fn save_docx(&self, fp: impl Into<std::path::PathBuf>) -> std::io::Result<()> {
+    /// Save as an sffb file with the given codec.
+    /// `save` uses the default codec.
+    pub fn save_sffb(
+        &self,
+        fp: impl Into<std::path::PathBuf>,
+        codec: Codec,
+    ) -> std::io::Result<()> {
+        let mut f = std::io::BufWriter::new(File::create(fp.into())?);
+        sffb::write(self, codec, &mut f)?;
+        f.flush()
+    }
+
+    /// Save as a .docx file with a table of balloons for every page.
+    /// `save` uses the default options.
+    pub fn save_docx(
+        &self,
+        fp: impl Into<std::path::PathBuf>,
+        options: &DocxExportOptions,
+    ) -> std::io::Result<()> {
         let f = File::create(fp.into())?;
-        docx_handlers::string_to_docx(&self.to_string())
+        docx_handlers::layout_to_docx(docx_export::layout(self, options))
             .build()
             .pack(f)?;
         Ok(())
     }
 
+    /// Writes the document as a self-contained HTML page for reviewers, see [`script_export`].
+    /// `save` uses the default options.
+    pub fn to_html(&self, options: &HtmlExportOptions) -> String {
+        script_export::to_html(self, options)
+    }
+
+    /// Writes the document as Markdown for reviewers, see [`script_export`].
+    pub fn to_markdown(&self) -> String {
+        script_export::to_markdown(self)
+    }
+
+    /// Writes a Photoshop script that adds a text layer for every placed balloon,
+    /// see [`typeset_script`].
+    pub fn to_jsx(&self) -> String {
+        typeset_script::to_jsx(self)
+    }
+
+    /// Writes a GIMP Python-Fu script that adds a text layer for every placed balloon,
+    /// see [`typeset_script`].
+    pub fn to_gimp_script(&self) -> String {
+        typeset_script::to_gimp_script(self)
+    }
+
+    /// Writes the document as XLIFF 2.0 for CAT tools, see [`xliff`].
+    /// `source_lang` and `target_lang` are BCP 47 language tags, like `ja` and `en`.
+    pub fn to_xliff(&self, source_lang: &str, target_lang: &str) -> Result<String> {
+        xliff::to_xliff(self, source_lang, target_lang)
+    }
+
+    /// Merges the translations of an XLIFF file exported with `to_xliff` back into the document.
+    /// Units whose balloons were changed since the export are reported, not merged.
+    pub fn merge_xliff(&mut self, xml: &str) -> Result<MergeReport> {
+        xliff::merge_xliff(self, xml)
+    }
+
+    /// Writes the document as a gettext PO file, see [`po`].
+    /// `language` is the language of the translations, like `en`.
+    pub fn to_po(&self, language: &str) -> String {
+        po::to_po(self, language)
+    }
+
+    /// Merges the translations of a PO file exported with `to_po` back into the document.
+    /// Entries whose balloons were changed since the export are reported, not merged.
+    pub fn merge_po(&mut self, po: &str) -> Result<MergeReport> {
+        po::merge_po(self, po)
+    }
+
+    // Save as a spreadsheet, `xlsx` or csv.
+    fn sheet_bytes(&self, xlsx: bool) -> std::io::Result<Vec<u8>> {
+        let bytes = if xlsx {
+            spreadsheet::write_xlsx(self)
+        } else {
+            let mut csv = Vec::new();
+            spreadsheet::write_csv(self, &mut csv).map(|_| csv)
+        };
+        bytes.map_err(|e| std::io::Error::other(e.to_string()))
+    }
+
     // Async save as a raw JSON file
     #[cfg(feature = "async-io")]
     async fn async_save_raw(&self, fp: impl Into<std::path::PathBuf>) -> std::io::Result<()> {
@@ -428,23 +1146,40 @@ impl Document {
         Ok(())
     }
 
-    // Async save as a compressed JSON file
+    /// Async version of `save_sffz`.
     #[cfg(feature = "async-io")]
-    async fn async_save_zlib(&self, fp: impl Into<std::path::PathBuf>) -> std::io::Result<()> {
+    pub async fn async_save_sffz(
+        &self,
+        fp: impl Into<std::path::PathBuf>,
+        codec: Codec,
+    ) -> std::io::Result<()> {
         let mut f = fs::File::create(fp.into()).await?;
-        let mut enc = ZlibEncoder::new(Vec::new(), Compression::best());
-        enc.write_all(self.to_json().as_bytes())?;
-        let encoded = enc.finish()?;
-        f.write_all(&encoded).await?;
+        f.write_all(&self.to_sffz(codec)?).await?;
         Ok(())
     }
 
-    // Async save as a .docx file
+    /// Async version of `save_sffb`.
     #[cfg(feature = "async-io")]
-    async fn async_save_docx(&self, fp: impl Into<std::path::PathBuf>) -> std::io::Result<()> {
+    pub async fn async_save_sffb(
+        &self,
+        fp: impl Into<std::path::PathBuf>,
+        codec: Codec,
+    ) -> std::io::Result<()> {
+        let mut f = fs::File::create(fp.into()).await?;
+        f.write_all(&self.to_sffb(codec)?).await?;
+        Ok(())
+    }
+
+    /// Async version of `save_docx`.
+    #[cfg(feature = "async-io")]
+    pub async fn async_save_docx(
+        &self,
+        fp: impl Into<std::path::PathBuf>,
+        options: &DocxExportOptions,
+    ) -> std::io::Result<()> {
         let mut f = fs::File::create(fp.into()).await?;
         let mut v: Cursor<Vec<u8>> = Cursor::new(Vec::new());
-        docx_handlers::string_to_docx(&self.to_string())
+        docx_handlers::layout_to_docx(docx_export::layout(self, options))
             .build()
             .pack(&mut v)?;
         f.write_all(&v.into_inner()).await?;
@@ -477,8 +1212,13 @@ impl Document {
                 match ext.to_str().unwrap() {
                     "txt" => OUT::TXT,
                     "docx" => OUT::DOCX,
+                    "csv" => OUT::CSV,
+                    "xlsx" => OUT::XLSX,
+                    "html" | "htm" => OUT::HTML,
+                    "md" => OUT::MD,
                     "sffx" => OUT::RAW,
                     "sffz" => OUT::ZLIB,
+                    "sffb" => OUT::BIN,
                     _ => return Err(std::io::Error::other("Unsupported Extension!")),
                 }
             } else {
@@ -493,8 +1233,18 @@ impl Document {
                 let mut f = File::create(pb.clone())?;
                 f.write_all(self.to_string().as_bytes())?;
             }
-            OUT::ZLIB => self.save_zlib(pb.clone())?,
-            OUT::DOCX => self.save_docx(pb.clone())?,
+            OUT::ZLIB => self.save_sffz(pb.clone(), Codec::default())?,
+            OUT::BIN => self.save_sffb(pb.clone(), Codec::default())?,
+            OUT::DOCX => self.save_docx(pb.clone(), &DocxExportOptions::default())?,
+            OUT::CSV | OUT::XLSX => {
+                let bytes = self.sheet_bytes(matches!(out_type, OUT::XLSX))?;
+                File::create(pb.clone())?.write_all(&bytes)?;
+            }
+            OUT::HTML => {
+                let html = self.to_html(&HtmlExportOptions::default());
+                File::create(pb.clone())?.write_all(html.as_bytes())?;
+            }
+            OUT::MD => File::create(pb.clone())?.write_all(self.to_markdown().as_bytes())?,
         }
         Ok(pb.display().to_string())
     }
@@ -527,8 +1277,13 @@ impl Document {
                 match ext.to_str().unwrap() {
                     "txt" => OUT::TXT,
                     "docx" => OUT::DOCX,
+                    "csv" => OUT::CSV,
+                    "xlsx" => OUT::XLSX,
+                    "html" | "htm" => OUT::HTML,
+                    "md" => OUT::MD,
                     "sffx" => OUT::RAW,
                     "sffz" => OUT::ZLIB,
+                    "sffb" => OUT::BIN,
                     _ => return Err(std::io::Error::other("Unsupported Extension!")),
                 }
             } else {
@@ -543,8 +1298,32 @@ impl Document {
                 let mut f = File::create(pb.clone())?;
                 f.write_all(self.to_string().as_bytes())?;
             }
-            OUT::ZLIB => self.async_save_zlib(pb.clone()).await?,
-            OUT::DOCX => self.async_save_docx(pb.clone()).await?,
+            OUT::ZLIB => self.async_save_sffz(pb.clone(), Codec::default()).await?,
+            OUT::BIN => self.async_save_sffb(pb.clone(), Codec::default()).await?,
+            OUT::DOCX => {
+                self.async_save_docx(pb.clone(), &DocxExportOptions::default())
+                    .await?
+            }
+            OUT::CSV | OUT::XLSX => {
+                let bytes = self.sheet_bytes(matches!(out_type, OUT::XLSX))?;
+                fs::File::create(pb.clone())
+                    .await?
+                    .write_all(&bytes)
+                    .await?;
+            }
+            OUT::HTML => {
+                let html = self.to_html(&HtmlExportOptions::default());
+                fs::File::create(pb.clone())
+                    .await?
+                    .write_all(html.as_bytes())
+                    .await?;
+            }
+            OUT::MD => {
+                fs::File::create(pb.clone())
+                    .await?
+                    .write_all(self.to_markdown().as_bytes())
+                    .await?;
+            }
         }
         Ok(pb.display().to_string())
     }
@@ -556,11 +1335,35 @@ impl Document {
 /// **IMPORTANT NOTE:** ***Metadata and balloon_img are lost during the creation of the text!!!***
 impl std::fmt::Display for Document {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            Vec::from_iter(self.balloons.iter().map(|b| b.to_string())).join("\n\n")
-        )
+        let mut blocks = Vec::from_iter(
+            self.custom_types
+                .iter()
+                .map(|t| format!("{} {} {} {}", TXT_TYPE_MARKER, t.prefix, t.color, t.name)),
+        );
+
+        // Page markers are written before the first balloon of every page,
+        // documents without placed balloons don't have any
+        let paged = self.balloons.iter().any(|b| b.page.is_some());
+        let mut page = None;
+        for b in self.balloons.iter().filter(|b| b.sfx.is_none()) {
+            if paged && b.page != page {
+                blocks.push(Self::txt_page_marker(b.page));
+                page = b.page;
+            }
+            if let Some(style) = Self::txt_style(b) {
+                blocks.push(format!("{} {}", TXT_STYLE_MARKER, style));
+            }
+            blocks.push(self.balloon_line(b));
+        }
+
+        // Sound effects get their own section
+        let sfx = self.txt_sfx_section();
+        if !sfx.is_empty() {
+            blocks.push(TXT_SFX_MARKER.to_string());
+            blocks.extend(sfx);
+        }
+
+        write!(f, "{}", blocks.join("\n\n"))
     }
 }
 
@@ -602,6 +1405,20 @@ mod document_related {
         assert_eq!(d.pr_chars(), 12)
     }
 
+    #[test]
+    fn document_sffz_codecs() {
+        let mut d = Document::default();
+        let mut b = Balloon::default();
+        b.tl_content.push(String::from("num"));
+        d.balloons.push(b);
+
+        for codec in crate::codec::Codec::ALL {
+            let sffz = d.to_sffz(codec).unwrap();
+            let opened = Document::from_sffz(&sffz).unwrap();
+            assert_eq!(opened.balloons, d.balloons);
+        }
+    }
+
     #[test]
     fn document_comment_chars() {
         let mut d = Document::default();
@@ -665,6 +1482,240 @@ mod document_related {
         assert_eq!(d.to_string(), String::from("OT: numnam\n\n(): num"))
     }
 
+    #[test]
+    fn document_custom_types() {
+        use crate::consts::CustomType;
+        use crate::style::Color;
+
+        let sfx = CustomType::new("Sound effect", "SFX", Color::new(255, 0, 0));
+        let mut d = Document::default();
+        d.add_custom_type(sfx.clone()).unwrap();
+        assert!(d.add_custom_type(sfx.clone()).is_err());
+        assert!(d
+            .add_custom_type(CustomType::new("Other", "ST", Color::BLACK))
+            .is_err());
+        assert!(d
+            .add_custom_type(CustomType::new("Other", "A B", Color::BLACK))
+            .is_err());
+
+        d.add_balloon(Balloon {
+            btype: TYPES::CUSTOM("Sound effect".to_string()),
+            tl_content: vec!["BOOM".to_string()],
+            ..Default::default()
+        });
+        d.add_balloon_with_tl("Hi");
+        d.add_balloon(Balloon {
+            btype: TYPES::ST,
+            tl_content: vec!["psst".to_string()],
+            ..Default::default()
+        });
+
+        let txt = d.to_string();
+        assert_eq!(
+            txt,
+            "#TYPE SFX #ff0000 Sound effect\n\nSFX: BOOM\n\n(): Hi\n\nST: psst"
+        );
+        assert_eq!(
+            d.split_txt_header("おいおい"),
+            (TYPES::DIALOGUE, "おいおい")
+        );
+        assert_eq!(
+            d.split_txt_header("XYZ: hey"),
+            (TYPES::DIALOGUE, "XYZ: hey")
+        );
+        let line = d.balloon_line(&d.balloons[0]);
+        assert_eq!(line, "SFX: BOOM");
+        assert_eq!(
+            d.split_txt_header(&line),
+            (d.balloons[0].btype.clone(), "BOOM")
+        );
+
+        let read = Document::txt_to_doc(txt).unwrap();
+        assert_eq!(read.custom_types, vec![sfx]);
+        let types = read.balloons.iter().map(|b| &b.btype).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                &TYPES::CUSTOM("Sound effect".to_string()),
+                &TYPES::DIALOGUE,
+                &TYPES::ST
+            ]
+        );
+        assert_eq!(read.balloons[0].tl_content, vec!["BOOM"]);
+        assert_eq!(
+            read.type_from_label("sound effect"),
+            Some(TYPES::CUSTOM("Sound effect".to_string()))
+        );
+        assert_eq!(read.type_from_label("{}"), Some(TYPES::THINKING));
+        assert_eq!(read.type_from_label("Square"), Some(TYPES::SQUARE));
+        assert_eq!(read.type_from_label("??"), None);
+
+        d.style_presets[4]
+            .types
+            .push(TYPES::CUSTOM("Sound effect".to_string()));
+        d.update_custom_type(
+            "Sound effect",
+            CustomType::new("SFX", "SFX", Color::new(0, 0, 255)),
+        )
+        .unwrap();
+        assert_eq!(d.balloons[0].btype, TYPES::CUSTOM("SFX".to_string()));
+        assert_eq!(d.balloon_types().len(), 6);
+        // The renamed type stays in its preset only
+        assert_eq!(d.style_presets[0].types, vec![TYPES::DIALOGUE]);
+        assert_eq!(
+            d.style_presets[4].types,
+            vec![TYPES::OT, TYPES::CUSTOM("SFX".to_string())]
+        );
+        assert_eq!(d.preset_of(&d.balloons[0]).unwrap().name, "Over-text");
+
+        assert!(d.remove_custom_type("SFX"));
+        assert_eq!(d.balloons[0].btype, TYPES::DIALOGUE);
+        assert!(!d.remove_custom_type("SFX"));
+    }
+
+    #[test]
+    fn document_sfx_section() {
+        use crate::sfx::{Sfx, SfxTreatment};
+
+        let sfx_balloon = |sfx: Sfx| Balloon {
+            sfx: Some(sfx),
+            ..Default::default()
+        };
+        let mut subtitle = Sfx::new("ザワ", "zawa", "MURMUR");
+        subtitle.treatment = SfxTreatment::Subtitle;
+
+        let mut d = Document::default();
+        d.add_balloon(sfx_balloon(Sfx::new("ドン", "don", "BOOM")));
+        d.add_balloon_with_tl("Hi");
+        d.add_balloon(sfx_balloon(subtitle.clone()));
+        d.add_balloon_with_tl("Bye");
+
+        let txt = d.to_string();
+        assert_eq!(
+            txt,
+            "(): Hi\n\n(): Bye\n\n#SFX\n\n1: ドン | don | BOOM | replace\n\n3: ザワ | zawa | MURMUR | subtitle"
+        );
+
+        let read = Document::txt_to_doc(txt).unwrap();
+        assert_eq!(read.balloons.len(), 4);
+        assert_eq!(read.balloons[0].sfx, Some(Sfx::new("ドン", "don", "BOOM")));
+        assert_eq!(read.balloons[1].tl_content, vec!["Hi"]);
+        assert_eq!(read.balloons[2].sfx, Some(subtitle));
+        assert_eq!(read.balloons[3].tl_content, vec!["Bye"]);
+    }
+
+    #[test]
+    fn document_txt_pages() {
+        use crate::consts::CustomType;
+        use crate::sfx::Sfx;
+        use crate::style::Color;
+
+        let mut d = Document::default();
+        d.add_custom_type(CustomType::new("Handwritten", "HW", Color::BLACK))
+            .unwrap();
+        let on_page = |page: Option<usize>, tl: &str| Balloon {
+            tl_content: vec![tl.to_string()],
+            page,
+            ..Default::default()
+        };
+        d.add_balloon(on_page(Some(0), "Hey!"));
+        d.add_balloon(Balloon {
+            sfx: Some(Sfx::new("ドン", "don", "BOOM")),
+            page: Some(0),
+            ..Default::default()
+        });
+        d.add_balloon(Balloon {
+            btype: TYPES::CUSTOM("Handwritten".to_string()),
+            ..on_page(Some(2), "note")
+        });
+        d.add_balloon(on_page(Some(2), "What?"));
+        d.add_balloon(on_page(None, "Later"));
+
+        let txt = d.to_string();
+        assert_eq!(
+            txt,
+            "#TYPE HW #000000 Handwritten\n\n#PAGE 1\n\n(): Hey!\n\n#PAGE 3\n\nHW: note\n\n\
+             (): What?\n\n#PAGE\n\n(): Later\n\n#SFX\n\n#PAGE 1\n\n2: ドン | don | BOOM | replace"
+        );
+
+        let read = Document::txt_to_doc(txt).unwrap();
+        assert_eq!(read.len(), d.len());
+        for (read, b) in read.balloons.iter().zip(&d.balloons) {
+            assert_eq!(read.page, b.page);
+            assert_eq!(read.btype, b.btype);
+            assert_eq!(read.tl_content, b.tl_content);
+            assert_eq!(read.sfx, b.sfx);
+        }
+
+        // Documents without pages are written as before
+        d.balloons.iter_mut().for_each(|b| b.page = None);
+        assert!(!d.to_string().contains("#PAGE"));
+
+        assert!(Document::txt_to_doc("#PAGE 0\n(): Hey!".to_string()).is_err());
+        assert!(Document::txt_to_doc("#PAGE one\n(): Hey!".to_string()).is_err());
+    }
+
+    #[test]
+    fn document_txt_styles() {
+        use crate::sfx::Sfx;
+        use crate::style::{BalloonStyle, TextAlignment};
+
+        let mut d = Document::default();
+        d.add_balloon(Balloon {
+            tl_content: vec!["Hmm".to_string()],
+            preset: Some("Thought".to_string()),
+            style: BalloonStyle {
+                bold: Some(true),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        d.add_balloon_with_tl("Hi");
+        d.add_balloon(Balloon {
+            sfx: Some(Sfx::new("ドン", "don", "BOOM")),
+            style: BalloonStyle {
+                alignment: Some(TextAlignment::Left),
+                rotation: Some(-15),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let txt = d.to_string();
+        assert_eq!(
+            txt,
+            "#STYLE preset=Thought; bold=true\n\n(): Hmm\n\n(): Hi\n\n#SFX\n\n\
+             #STYLE align=left; rotation=-15\n\n3: ドン | don | BOOM | replace"
+        );
+
+        let read = Document::txt_to_doc(txt).unwrap();
+        assert_eq!(read.len(), d.len());
+        for (read, b) in read.balloons.iter().zip(&d.balloons) {
+            assert_eq!(read.preset, b.preset);
+            assert_eq!(read.style, b.style);
+        }
+
+        // Lines of a multi-line balloon share the style written before the first one
+        let read = Document::txt_to_doc("#STYLE size=20\n(): a\n//\nb\n(): c".to_string()).unwrap();
+        assert_eq!(read.balloons[0].tl_content, vec!["a", "b"]);
+        assert_eq!(read.balloons[0].style.font_size, Some(20));
+        assert!(read.balloons[1].style.is_empty());
+
+        assert!(Document::txt_to_doc("#STYLE size=big\n(): a".to_string()).is_err());
+    }
+
+    #[test]
+    fn document_markup_errors() {
+        let mut d = Document::default();
+        d.add_balloon_with_tl("[b]fine[/b]");
+        d.add_balloon_with_tl("fine");
+        d.balloons[1].add_pr("[i]not closed");
+
+        let errors = d.markup_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 1);
+    }
+
     #[test]
     fn document_open_txt() {
         let d = Document::open("test.txt").unwrap();
@@ -677,6 +1728,157 @@ mod document_related {
         assert_eq!(d.balloons[1].tl_content[0], "num");
     }
 
+    #[test]
+    fn document_add_detected_balloons() {
+        use crate::img_data::Region;
+
+        let placed = |page, region| {
+            let mut b = Balloon::default();
+            b.set_region(page, region);
+            b
+        };
+
+        let mut d = Document::default();
+        d.add_balloon(placed(0, Region::new(0, 0, 10, 10)));
+        d.add_balloon(placed(2, Region::new(0, 0, 10, 10)));
+
+        let added = d.add_detected_balloons(
+            1,
+            vec![
+                placed(1, Region::new(0, 0, 10, 10)),
+                placed(1, Region::new(50, 50, 10, 10)),
+            ],
+        );
+        assert_eq!(added, 1..3);
+        assert_eq!(d.balloons[2].region, Some(Region::new(50, 50, 10, 10)));
+
+        // Overlaps the balloon already on page 0
+        let added = d.add_detected_balloons(0, vec![placed(0, Region::new(2, 2, 10, 10))]);
+        assert!(added.is_empty());
+        assert_eq!(d.len(), 4);
+    }
+
+    #[test]
+    fn document_prefill_machine_translation() {
+        use crate::translate::FakeProvider;
+        use crate::BalloonStatus;
+
+        let mut d = Document::default();
+        let mut b = Balloon {
+            source_content: vec!["猫".to_string(), "犬".to_string()],
+            ..Default::default()
+        };
+        d.add_balloon(b.clone());
+        b.add_tl("Human translation");
+        d.add_balloon(b);
+        d.add_balloon_empty();
+
+        let provider = FakeProvider::new("MT: ");
+        assert_eq!(d.prefill_machine_translation(&provider).unwrap(), 1);
+        assert_eq!(d.balloons[0].tl_content, vec!["MT: 猫", "犬"]);
+        assert_eq!(d.balloons[0].status, BalloonStatus::MachineDraft);
+        assert_eq!(d.balloons[1].tl_content, vec!["Human translation"]);
+        assert_eq!(d.balloons[1].status, BalloonStatus::Pending);
+
+        // Nothing left to translate, provider isn't called again
+        assert_eq!(d.prefill_machine_translation(&provider).unwrap(), 0);
+        assert_eq!(provider.calls(), 1);
+    }
+
+    #[test]
+    fn document_machine_drafts_moved_balloons() {
+        let mut d = Document::default();
+        for source in ["猫", "犬"] {
+            d.add_balloon(Balloon {
+                source_content: vec![source.to_string()],
+                ..Default::default()
+            });
+        }
+        let drafts = d
+            .machine_translation_sources()
+            .into_iter()
+            .map(|(i, source)| (i, source.clone(), format!("MT: {}", source)))
+            .collect::<Vec<_>>();
+
+        // A detected balloon is inserted while waiting for the drafts
+        d.balloons.insert(1, Balloon::default());
+        assert_eq!(d.apply_machine_drafts(drafts), 1);
+        assert_eq!(d.balloons[0].tl_content, vec!["MT: 猫"]);
+        assert!(d.balloons[1].tl_content.is_empty());
+        assert!(d.balloons[2].tl_content.is_empty());
+    }
+
+    #[test]
+    fn document_sort_balloons() {
+        use crate::img_data::Region;
+        use crate::reading_order::ReadingDirection;
+
+        let placed = |tl: &str, page, region| {
+            let mut b = Balloon::default();
+            b.add_tl(tl);
+            b.set_region(page, region);
+            b
+        };
+
+        let mut d = Document::default();
+        d.add_balloon(placed("p1 left", 1, Region::new(0, 0, 10, 10)));
+        d.add_balloon_with_tl("not placed");
+        d.add_balloon(placed("p0", 0, Region::new(0, 0, 10, 10)));
+        d.add_balloon(placed("p1 right", 1, Region::new(50, 0, 10, 10)));
+
+        assert_eq!(d.reading_order(&[]), vec![2, 1, 3, 0]);
+
+        d.reading_direction = ReadingDirection::LeftToRight;
+        d.sort_balloons(&[]);
+        let tls = d
+            .balloons
+            .iter()
+            .map(|b| b.tl_content[0].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(tls, vec!["p0", "not placed", "p1 left", "p1 right"]);
+
+        // Stale or broken orders don't touch the balloons
+        assert!(d.reorder_balloons(&[0, 1, 2]).is_err());
+        assert!(d.reorder_balloons(&[0, 1, 2, 2]).is_err());
+        assert!(d.reorder_balloons(&[0, 1, 2, 4]).is_err());
+        assert_eq!(d.balloons[0].tl_content[0], "p0");
+        d.reorder_balloons(&[3, 2, 1, 0]).unwrap();
+        assert_eq!(d.balloons[0].tl_content[0], "p1 right");
+    }
+
+    #[test]
+    fn document_balloon_style() {
+        use crate::style::{BalloonStyle, StylePreset};
+
+        let mut d = Document::default();
+        d.style_presets.push(StylePreset::new(
+            "Shout",
+            Vec::new(),
+            BalloonStyle {
+                font_size: Some(24),
+                bold: Some(true),
+                ..Default::default()
+            },
+        ));
+
+        let mut b = Balloon {
+            btype: TYPES::THINKING,
+            ..Default::default()
+        };
+        assert_eq!(d.preset_of(&b).unwrap().name, "Thought");
+        assert_eq!(d.balloon_style(&b).italic, Some(true));
+
+        b.preset = Some("Shout".to_string());
+        b.style.bold = Some(false);
+        let style = d.balloon_style(&b);
+        assert_eq!(style.font_size, Some(24));
+        assert_eq!(style.bold, Some(false));
+        assert_eq!(style.italic, None);
+
+        b.preset = Some("Missing".to_string());
+        assert_eq!(d.balloon_style(&b), b.style);
+    }
+
     #[test]
     fn document_unsupported_file_ext() {
         let r = Document::open("test.test");
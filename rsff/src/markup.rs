@@ -0,0 +1,395 @@
+//! Inline markup of balloon lines.
+//!
+//! | Markup                   | Meaning      |
+//! |--------------------------|--------------|
+//! | `[b]text[/b]`            | Bold         |
+//! | `[i]text[/i]`            | Italic       |
+//! | `[u]text[/u]`            | Underline    |
+//! | `[sc]text[/sc]`          | Small caps   |
+//! | `[font=Name]text[/font]` | Font switch  |
+//!
+//! Tags can be nested but must be closed in the line they are opened in, in reverse order.
+//! Brackets that don't form a known tag are plain text, so `[]: ` headers and notes like
+//! `[sic]` are left alone.
+
+use std::fmt;
+use std::ops::Range;
+
+/// Formatting of a piece of text.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub small_caps: bool,
+    pub font: Option<String>,
+}
+
+impl TextStyle {
+    /// No formatting.
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A piece of text with a single style.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub text: String,
+    pub style: TextStyle,
+}
+
+impl Span {
+    pub fn new(text: impl Into<String>, style: TextStyle) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+}
+
+/// A markup mistake. Positions are byte offsets in the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkupError {
+    /// Tag is never closed.
+    Unclosed { tag: String, at: usize },
+    /// Closing tag doesn't match the last opened tag.
+    UnexpectedClose { tag: String, at: usize },
+    /// `[font=]` without a font name.
+    EmptyFont { at: usize },
+}
+
+impl fmt::Display for MarkupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unclosed { tag, at } => write!(f, "[{}] at {} is never closed", tag, at),
+            Self::UnexpectedClose { tag, at } => {
+                write!(f, "[/{}] at {} doesn't close an open tag", tag, at)
+            }
+            Self::EmptyFont { at } => write!(f, "[font=] at {} has no font name", at),
+        }
+    }
+}
+
+impl std::error::Error for MarkupError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tag {
+    Bold,
+    Italic,
+    Underline,
+    SmallCaps,
+    Font(String),
+}
+
+impl Tag {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Bold => "b",
+            Self::Italic => "i",
+            Self::Underline => "u",
+            Self::SmallCaps => "sc",
+            Self::Font(_) => "font",
+        }
+    }
+
+    fn apply(&self, style: &mut TextStyle) {
+        match self {
+            Self::Bold => style.bold = true,
+            Self::Italic => style.italic = true,
+            Self::Underline => style.underline = true,
+            Self::SmallCaps => style.small_caps = true,
+            Self::Font(name) => style.font = Some(name.clone()),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Piece {
+    Text(Range<usize>),
+    Open(Range<usize>, Tag),
+    Close(Range<usize>, &'static str),
+}
+
+fn simple_tag(name: &str) -> Option<Tag> {
+    match name {
+        "b" => Some(Tag::Bold),
+        "i" => Some(Tag::Italic),
+        "u" => Some(Tag::Underline),
+        "sc" => Some(Tag::SmallCaps),
+        _ => None,
+    }
+}
+
+fn lex(line: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+
+    while let Some(open) = line[pos..].find('[').map(|i| pos + i) {
+        let Some(close) = line[open..].find(']').map(|i| open + i) else {
+            break;
+        };
+        let inner = &line[open + 1..close];
+
+        let piece = if let Some(name) = inner.strip_prefix('/') {
+            simple_tag(name)
+                .map(|t| t.name())
+                .or((name == "font").then_some("font"))
+                .map(|name| Piece::Close(open..close + 1, name))
+        } else if let Some(font) = inner.strip_prefix("font=") {
+            Some(Piece::Open(
+                open..close + 1,
+                Tag::Font(font.trim().to_string()),
+            ))
+        } else {
+            simple_tag(inner).map(|t| Piece::Open(open..close + 1, t))
+        };
+
+        match piece {
+            Some(piece) => {
+                if text_start < open {
+                    pieces.push(Piece::Text(text_start..open));
+                }
+                pieces.push(piece);
+                pos = close + 1;
+                text_start = pos;
+            }
+            // Not a tag, keep looking after the bracket
+            None => pos = open + 1,
+        }
+    }
+
+    if text_start < line.len() {
+        pieces.push(Piece::Text(text_start..line.len()));
+    }
+    pieces
+}
+
+fn style_of(stack: &[(Tag, usize)]) -> TextStyle {
+    let mut style = TextStyle::default();
+    for (tag, _) in stack {
+        tag.apply(&mut style);
+    }
+    style
+}
+
+// Walks the line, calling `f` for every text or tag range with the style of text ranges.
+// Mistakes are reported to `on_error`, the walk carries on as if they weren't there.
+fn walk(
+    line: &str,
+    mut f: impl FnMut(Range<usize>, Option<TextStyle>),
+    mut on_error: impl FnMut(MarkupError),
+) {
+    let mut stack: Vec<(Tag, usize)> = Vec::new();
+
+    for piece in lex(line) {
+        match piece {
+            Piece::Text(range) => f(range, Some(style_of(&stack))),
+            Piece::Open(range, tag) => {
+                if tag == Tag::Font(String::new()) {
+                    on_error(MarkupError::EmptyFont { at: range.start });
+                }
+                stack.push((tag, range.start));
+                f(range, None);
+            }
+            Piece::Close(range, name) => {
+                match stack.last() {
+                    Some((tag, _)) if tag.name() == name => {
+                        stack.pop();
+                    }
+                    _ => on_error(MarkupError::UnexpectedClose {
+                        tag: name.to_string(),
+                        at: range.start,
+                    }),
+                }
+                f(range, None);
+            }
+        }
+    }
+
+    for (tag, at) in stack {
+        on_error(MarkupError::Unclosed {
+            tag: tag.name().to_string(),
+            at,
+        });
+    }
+}
+
+/// Parses a line into styled spans. Fails on the first markup mistake.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::markup::{parse, Span, TextStyle};
+///
+/// let spans = parse("I [b]told[/b] you!").unwrap();
+/// assert_eq!(spans[1].text, "told");
+/// assert!(spans[1].style.bold);
+/// assert!(parse("I [b]told you!").is_err());
+/// ```
+pub fn parse(line: &str) -> Result<Vec<Span>, MarkupError> {
+    let mut error = None;
+    let spans = collect_spans(line, |e| {
+        error.get_or_insert(e);
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(spans),
+    }
+}
+
+/// Parses a line into styled spans, ignoring markup mistakes:
+/// unclosed tags last until the end of the line, stray closing tags are dropped.
+pub fn parse_lossy(line: &str) -> Vec<Span> {
+    collect_spans(line, |_| {})
+}
+
+fn collect_spans(line: &str, on_error: impl FnMut(MarkupError)) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    walk(
+        line,
+        |range, style| {
+            let Some(style) = style else {
+                return;
+            };
+            match spans.last_mut() {
+                Some(last) if last.style == style => last.text.push_str(&line[range]),
+                _ => spans.push(Span::new(&line[range], style)),
+            }
+        },
+        on_error,
+    );
+    spans
+}
+
+/// All markup mistakes of a line.
+pub fn validate(line: &str) -> Vec<MarkupError> {
+    let mut errors = Vec::new();
+    walk(line, |_, _| {}, |e| errors.push(e));
+    errors
+}
+
+/// Byte ranges of a line with the style of their text, `None` for the tags themselves.
+/// Meant for highlighting the markup in editors.
+pub fn highlight(line: &str) -> Vec<(Range<usize>, Option<TextStyle>)> {
+    let mut ranges = Vec::new();
+    walk(line, |range, style| ranges.push((range, style)), |_| {});
+    ranges
+}
+
+/// Text of a line without its markup.
+pub fn strip(line: &str) -> String {
+    parse_lossy(line).into_iter().map(|s| s.text).collect()
+}
+
+/// Writes spans back as a line with markup.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::markup::{to_markup, Span, TextStyle};
+///
+/// let bold = TextStyle { bold: true, ..Default::default() };
+/// let line = to_markup(&[Span::new("Run, ", TextStyle::default()), Span::new("now", bold)]);
+/// assert_eq!(line, "Run, [b]now[/b]");
+/// ```
+pub fn to_markup(spans: &[Span]) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < spans.len() {
+        // Adjacent spans with the same style are written once
+        let style = &spans[i].style;
+        let mut text = String::new();
+        while i < spans.len() && spans[i].style == *style {
+            text.push_str(&spans[i].text);
+            i += 1;
+        }
+
+        let mut tags = Vec::new();
+        if let Some(font) = &style.font {
+            out.push_str(&format!("[font={}]", font));
+            tags.push("font");
+        }
+        for (on, name) in [
+            (style.bold, "b"),
+            (style.italic, "i"),
+            (style.underline, "u"),
+            (style.small_caps, "sc"),
+        ] {
+            if on {
+                out.push_str(&format!("[{}]", name));
+                tags.push(name);
+            }
+        }
+        out.push_str(&text);
+        for name in tags.iter().rev() {
+            out.push_str(&format!("[/{}]", name));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod markup_tests {
+    use super::*;
+
+    #[test]
+    fn markup_parse_nested() {
+        let spans = parse("[font=Wild Words]Get [b][i]out[/i][/b]![/font] [sic]").unwrap();
+        let texts = spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["Get ", "out", "!", " [sic]"]);
+
+        assert_eq!(spans[0].style.font.as_deref(), Some("Wild Words"));
+        assert!(spans[1].style.bold && spans[1].style.italic);
+        assert!(!spans[2].style.bold);
+        assert!(spans[3].style.is_plain());
+    }
+
+    #[test]
+    fn markup_validate() {
+        assert!(validate("[]: [u]fine[/u]").is_empty());
+        assert_eq!(
+            validate("[b]a[i]b[/b]"),
+            vec![
+                MarkupError::UnexpectedClose {
+                    tag: "b".to_string(),
+                    at: 8
+                },
+                MarkupError::Unclosed {
+                    tag: "b".to_string(),
+                    at: 0
+                },
+                MarkupError::Unclosed {
+                    tag: "i".to_string(),
+                    at: 4
+                },
+            ]
+        );
+        assert_eq!(
+            validate("[font=]x[/font]"),
+            vec![MarkupError::EmptyFont { at: 0 }]
+        );
+    }
+
+    #[test]
+    fn markup_round_trip() {
+        let line = "[sc]Chapter[/sc] one, [b]really[/b] [u][b]loud[/b][/u]";
+        let spans = parse(line).unwrap();
+        assert_eq!(parse(&to_markup(&spans)).unwrap(), spans);
+        assert_eq!(strip(line), "Chapter one, really loud");
+
+        // Stray tags are dropped, unclosed ones run to the end
+        assert_eq!(to_markup(&parse_lossy("a[/b] [i]b")), "a [i]b[/i]");
+    }
+
+    #[test]
+    fn markup_highlight_ranges() {
+        let line = "x[b]y[/b]";
+        let ranges = highlight(line);
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[1], (1..4, None));
+        assert_eq!(&line[ranges[2].0.clone()], "y");
+        assert!(ranges[2].1.as_ref().unwrap().bold);
+    }
+}
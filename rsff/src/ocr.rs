@@ -0,0 +1,261 @@
+//! Optical character recognition of balloon images.
+//!
+//! OCR engines are plugged in through the [`OcrBackend`] trait. [`CommandOcr`] runs a
+//! local program (tesseract, manga-ocr etc.) on the balloon image, [`MockOcr`] returns
+//! fixed results and is meant for tests.
+
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::img_data::BalloonImage;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Placeholder replaced by the image path in [`CommandOcr`] arguments.
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+
+// How many names are tried before giving up on a temporary file.
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+/// Recognized text of a balloon image.
+///
+/// `confidence` is between 0 and 1, `None` if the backend doesn't report it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OcrResult {
+    pub text: String,
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+impl OcrResult {
+    /// Recognized text split into lines, empty lines removed.
+    pub fn lines(&self) -> Vec<String> {
+        self.text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect()
+    }
+}
+
+/// An OCR engine.
+pub trait OcrBackend: Send + Sync {
+    fn recognize(&self, img: &BalloonImage) -> Result<OcrResult>;
+}
+
+/// Runs a local program for every image.
+///
+/// The image is written to a temporary file and its path replaces every `{input}`
+/// argument. If there is no `{input}` argument, the path is appended to the arguments.
+/// The program's stdout is the result: either plain text, or a JSON object with
+/// `text` and `confidence` fields for programs that can report confidence.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::ocr::CommandOcr;
+///
+/// let ocr = CommandOcr::from_command_line("tesseract {input} stdout -l jpn_vert").unwrap();
+/// assert_eq!(ocr.program, "tesseract");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOcr {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl CommandOcr {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+        }
+    }
+
+    /// Creates the backend from a whitespace separated command line.
+    /// Returns `None` if the command line is empty.
+    pub fn from_command_line(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace().map(|p| p.to_string());
+        let program = parts.next()?;
+        Some(Self::new(program, parts.collect()))
+    }
+
+    fn args_for(&self, input: &str) -> Vec<String> {
+        if self.args.iter().any(|a| a.contains(INPUT_PLACEHOLDER)) {
+            self.args
+                .iter()
+                .map(|a| a.replace(INPUT_PLACEHOLDER, input))
+                .collect()
+        } else {
+            let mut args = self.args.clone();
+            args.push(input.to_string());
+            args
+        }
+    }
+}
+
+impl OcrBackend for CommandOcr {
+    fn recognize(&self, img: &BalloonImage) -> Result<OcrResult> {
+        let input = write_temp_image(img)?;
+
+        let output = Command::new(&self.program)
+            .args(self.args_for(&input.to_string_lossy()))
+            .output();
+        let _ = std::fs::remove_file(&input);
+        let output = output?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "{} exited with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(
+            serde_json::from_str::<OcrResult>(&stdout).unwrap_or(OcrResult {
+                text: stdout.trim().to_string(),
+                confidence: None,
+            }),
+        )
+    }
+}
+
+/// Returns the same result for every image and counts the calls.
+#[derive(Debug, Default)]
+pub struct MockOcr {
+    pub result: OcrResult,
+    calls: AtomicUsize,
+}
+
+impl MockOcr {
+    pub fn new(text: impl Into<String>, confidence: Option<f32>) -> Self {
+        Self {
+            result: OcrResult {
+                text: text.into(),
+                confidence,
+            },
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// How many images were recognized.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+}
+
+impl OcrBackend for MockOcr {
+    fn recognize(&self, _img: &BalloonImage) -> Result<OcrResult> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        Ok(self.result.clone())
+    }
+}
+
+// Writes the image to a new file in the temp dir. The name is unique per process and
+// call since OCR may run on many threads at once. Files are only ever created, never
+// opened, so a file or symlink left in the shared temp dir under the same name is skipped.
+fn write_temp_image(img: &BalloonImage) -> Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+
+    for _ in 0..TEMP_FILE_ATTEMPTS {
+        let path = std::env::temp_dir().join(format!(
+            "rsff_ocr_{}_{}_{}.{}",
+            std::process::id(),
+            nanos,
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            img.img_type.trim_start_matches('.')
+        ));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(&img.img_data) {
+                    let _ = std::fs::remove_file(&path);
+                    return Err(e.into());
+                }
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err("Couldn't create a temporary file for the OCR image.".into())
+}
+
+#[cfg(test)]
+mod ocr_tests {
+    use super::*;
+
+    fn test_img() -> BalloonImage {
+        BalloonImage {
+            img_type: "png".to_string(),
+            img_data: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn ocr_mock_backend() {
+        let ocr = MockOcr::new("こんにちは\n\n世界", Some(0.9));
+        let backend: &dyn OcrBackend = &ocr;
+
+        let res = backend.recognize(&test_img()).unwrap();
+        assert_eq!(res.lines(), vec!["こんにちは", "世界"]);
+        assert_eq!(res.confidence, Some(0.9));
+        assert_eq!(ocr.calls(), 1);
+    }
+
+    #[test]
+    fn ocr_command_args() {
+        let ocr = CommandOcr::from_command_line("tesseract {input} stdout").unwrap();
+        assert_eq!(ocr.args_for("a.png"), vec!["a.png", "stdout"]);
+
+        let ocr = CommandOcr::from_command_line("manga_ocr").unwrap();
+        assert_eq!(ocr.args_for("a.png"), vec!["a.png"]);
+
+        assert!(CommandOcr::from_command_line("  ").is_none());
+    }
+
+    #[test]
+    fn ocr_temp_image_is_new() {
+        let first = write_temp_image(&test_img()).unwrap();
+        let second = write_temp_image(&test_img()).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read(&first).unwrap(), vec![1, 2, 3]);
+        std::fs::remove_file(first).unwrap();
+        std::fs::remove_file(second).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ocr_command_backend() {
+        let plain = CommandOcr::new("sh", vec!["-c".into(), "echo hello".into()]);
+        let res = plain.recognize(&test_img()).unwrap();
+        assert_eq!(res.text, "hello");
+        assert_eq!(res.confidence, None);
+
+        let json = CommandOcr::new(
+            "sh",
+            vec![
+                "-c".into(),
+                r#"echo '{"text": "hi", "confidence": 0.5}'"#.into(),
+            ],
+        );
+        assert_eq!(json.recognize(&test_img()).unwrap().confidence, Some(0.5));
+
+        let failing = CommandOcr::new("sh", vec!["-c".into(), "exit 3".into()]);
+        assert!(failing.recognize(&test_img()).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! On-disk cache of downscaled and tiled page images.
+//!
+//! Decoding a 60 page chapter or a 20000px tall webtoon strip on every redraw is slow,
+//! and most GPUs can't upload such tall images as a single texture anyway. Pages are
+//! downscaled to a maximum width and cut into horizontal tiles, then stored in a cache
+//! directory keyed by the hash of the page file. [`prune_cache`] keeps the directory
+//! under a size limit by removing the least recently used pages.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Options for caching pages.
+///
+/// `max_width`: pages wider than this are downscaled, keeping the aspect ratio.\
+/// `tile_height`: height of a single tile, in pixels of the downscaled page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCacheOptions {
+    pub max_width: u32,
+    pub tile_height: u32,
+}
+
+impl Default for PageCacheOptions {
+    fn default() -> Self {
+        Self {
+            max_width: 2048,
+            tile_height: 4096,
+        }
+    }
+}
+
+/// A cached page. `tiles` are in top to bottom order and all have the same width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedPage {
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<PathBuf>,
+}
+
+/// FNV-1a hash of a file's content. Stable between runs and platforms.
+pub fn file_hash<P: AsRef<Path>>(path: P) -> std::io::Result<u64> {
+    let mut f = File::open(path)?;
+    let mut buff = [0u8; 64 * 1024];
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    loop {
+        let n = f.read(&mut buff)?;
+        if n == 0 {
+            break;
+        }
+        for b in &buff[..n] {
+            hash ^= *b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    Ok(hash)
+}
+
+/// Calculates the downscaled size of a page and the heights of its tiles.
+pub fn tile_layout(width: u32, height: u32, opts: &PageCacheOptions) -> (u32, u32, Vec<u32>) {
+    let (w, h) = if width > opts.max_width {
+        let h = (height as u64 * opts.max_width as u64 / width as u64).max(1) as u32;
+        (opts.max_width, h)
+    } else {
+        (width, height)
+    };
+
+    let tile_height = opts.tile_height.max(1);
+    let mut tiles = vec![tile_height; (h / tile_height) as usize];
+    if h % tile_height != 0 {
+        tiles.push(h % tile_height);
+    }
+
+    (w, h, tiles)
+}
+
+/// Downscales an image and cuts it into tiles, the same way pages are cached.
+pub fn tile_image(img: impl Into<DynamicImage>, opts: &PageCacheOptions) -> Vec<RgbaImage> {
+    tiles(img.into(), opts)
+        .into_iter()
+        .map(|t| t.into_rgba8())
+        .collect()
+}
+
+fn tiles(mut img: DynamicImage, opts: &PageCacheOptions) -> Vec<DynamicImage> {
+    let (w, h, tile_heights) = tile_layout(img.width(), img.height(), opts);
+    if w != img.width() {
+        img = img.resize_exact(w, h, FilterType::Triangle);
+    }
+
+    let mut y = 0;
+    tile_heights
+        .into_iter()
+        .map(|tile_height| {
+            let tile = img.crop_imm(0, y, w, tile_height);
+            y += tile_height;
+            tile
+        })
+        .collect()
+}
+
+/// Returns the cached version of a page, creating it if it doesn't exist yet.
+///
+/// This decodes the whole page, so call it off the UI thread.
+pub fn cache_page<P: AsRef<Path>, C: AsRef<Path>>(
+    page: P,
+    cache_dir: C,
+    opts: &PageCacheOptions,
+) -> Result<CachedPage> {
+    let page = page.as_ref();
+    let (width, height) = image::image_dimensions(page)?;
+    let (w, h, tile_heights) = tile_layout(width, height, opts);
+
+    let dir = cache_dir
+        .as_ref()
+        .join(format!("{:016x}", file_hash(page)?));
+    let tiles = (0..tile_heights.len())
+        .map(|i| dir.join(format!("w{}_t{}_{}.png", w, opts.tile_height, i)))
+        .collect::<Vec<_>>();
+
+    if tiles.iter().all(|t| t.is_file()) {
+        // Marks the page as recently used for [`prune_cache`]
+        if let Ok(f) = File::options().write(true).open(&tiles[0]) {
+            let _ = f.set_modified(SystemTime::now());
+        }
+        return Ok(CachedPage {
+            width: w,
+            height: h,
+            tiles,
+        });
+    }
+
+    std::fs::create_dir_all(&dir)?;
+    for (tile_path, tile) in tiles.iter().zip(self::tiles(image::open(page)?, opts)) {
+        save_tile(&tile, tile_path)?;
+    }
+
+    Ok(CachedPage {
+        width: w,
+        height: h,
+        tiles,
+    })
+}
+
+// Tiles are written to a temporary file next to them and renamed into place, so a tile
+// that exists is always complete, even if caching was interrupted or the same page is
+// cached by two calls at once.
+fn save_tile(tile: &DynamicImage, path: &Path) -> Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let tmp = path.with_extension(format!(
+        "{}_{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let res = tile
+        .save_with_format(&tmp, ImageFormat::Png)
+        .map_err(|e| e.into())
+        .and_then(|_| std::fs::rename(&tmp, path).map_err(|e| e.into()));
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    res
+}
+
+/// Removes the least recently used pages from the cache directory until its size is at
+/// most `max_bytes`. Returns the number of bytes removed.
+///
+/// Pages in use may be removed too, so call it before caching pages, e.g. on startup.
+pub fn prune_cache<C: AsRef<Path>>(cache_dir: C, max_bytes: u64) -> Result<u64> {
+    let read_dir = match std::fs::read_dir(cache_dir) {
+        Ok(r) => r,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    // (last use, size, directory) of every cached page
+    let mut pages = Vec::new();
+    for entry in read_dir {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let mut last_used = SystemTime::UNIX_EPOCH;
+        let mut size = 0;
+        for file in std::fs::read_dir(&path)? {
+            let meta = file?.metadata()?;
+            size += meta.len();
+            last_used = last_used.max(meta.modified().unwrap_or(SystemTime::UNIX_EPOCH));
+        }
+        pages.push((last_used, size, path));
+    }
+
+    let mut total: u64 = pages.iter().map(|(_, size, _)| size).sum();
+    let mut removed = 0;
+    pages.sort_by_key(|(last_used, _, _)| *last_used);
+    for (_, size, path) in pages {
+        if total <= max_bytes {
+            break;
+        }
+        std::fs::remove_dir_all(path)?;
+        total -= size;
+        removed += size;
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod page_cache_tests {
+    use super::*;
+
+    #[test]
+    fn page_cache_tile_layout() {
+        let opts = PageCacheOptions {
+            max_width: 800,
+            tile_height: 1000,
+        };
+
+        assert_eq!(tile_layout(400, 900, &opts), (400, 900, vec![900]));
+        assert_eq!(
+            tile_layout(1600, 5000, &opts),
+            (800, 2500, vec![1000, 1000, 500])
+        );
+        assert_eq!(tile_layout(800, 2000, &opts), (800, 2000, vec![1000, 1000]));
+    }
+
+    #[test]
+    fn page_cache_tile_image() {
+        let opts = PageCacheOptions {
+            max_width: 50,
+            tile_height: 50,
+        };
+        let tiles = tile_image(RgbaImage::new(100, 250), &opts);
+
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(tiles[0].dimensions(), (50, 50));
+        assert_eq!(tiles[2].dimensions(), (50, 25));
+    }
+
+    #[test]
+    fn page_cache_creates_tiles() {
+        let dir = std::env::temp_dir().join("rsff_page_cache_creates_tiles");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let page = dir.join("page.png");
+        image::RgbImage::from_pixel(100, 250, image::Rgb([255, 255, 255]))
+            .save(&page)
+            .unwrap();
+
+        let opts = PageCacheOptions {
+            max_width: 50,
+            tile_height: 50,
+        };
+        let cached = cache_page(&page, dir.join("cache"), &opts).unwrap();
+
+        assert_eq!((cached.width, cached.height), (50, 125));
+        assert_eq!(cached.tiles.len(), 3);
+        assert_eq!(image::image_dimensions(&cached.tiles[2]).unwrap(), (50, 25));
+
+        // Second call must find the same tiles
+        assert_eq!(cache_page(&page, dir.join("cache"), &opts).unwrap(), cached);
+        let page_dir = cached.tiles[0].parent().unwrap();
+        assert_eq!(std::fs::read_dir(page_dir).unwrap().count(), 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn page_cache_prune() {
+        let dir = std::env::temp_dir().join("rsff_page_cache_prune");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let opts = PageCacheOptions::default();
+        let mut cached = Vec::new();
+        for (i, name) in ["old.png", "new.png"].into_iter().enumerate() {
+            std::fs::create_dir_all(&dir).unwrap();
+            let page = dir.join(name);
+            image::RgbImage::from_pixel(20, 20, image::Rgb([i as u8, 0, 0]))
+                .save(&page)
+                .unwrap();
+            let c = cache_page(&page, dir.join("cache"), &opts).unwrap();
+            File::options()
+                .write(true)
+                .open(&c.tiles[0])
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(i as u64))
+                .unwrap();
+            cached.push(c);
+        }
+        let new_size = std::fs::metadata(&cached[1].tiles[0]).unwrap().len();
+
+        assert_eq!(prune_cache(dir.join("cache"), u64::MAX).unwrap(), 0);
+        assert!(prune_cache(dir.join("cache"), new_size).unwrap() > 0);
+        assert!(!cached[0].tiles[0].exists());
+        assert!(cached[1].tiles[0].exists());
+        assert_eq!(prune_cache(dir.join("missing"), 0).unwrap(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,383 @@
+//! Gettext PO export and import.
+//!
+//! Every balloon is an entry whose context is its number, starting from 1, and its type:
+//!
+//! ```notrust
+//! # Loud
+//! #. Page 1
+//! #, fuzzy
+//! msgctxt "1|()"
+//! msgid "おい!"
+//! msgstr "Hey!"
+//! ```
+//!
+//! Comments of the balloon are written as translator comments and machine drafts are
+//! marked as fuzzy. The translation is the proofread text if the balloon has any,
+//! the translation otherwise. Sound effects have the `#SFX` type and use their original
+//! and translation. When a file is merged back, entries are matched to balloons by
+//! their numbers.
+//! Entries whose source text or type don't match their balloons anymore are reported
+//! instead of merged.
+
+use std::fmt::Write;
+
+use crate::balloon::Balloon;
+use crate::consts::BalloonStatus;
+use crate::sfx::TXT_SFX_MARKER;
+use crate::{Document, MergeReport, Result};
+
+const FUZZY: &str = "fuzzy";
+
+/// An entry of a PO file. Plural forms are not supported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoEntry {
+    pub translator_comments: Vec<String>,
+    pub extracted_comments: Vec<String>,
+    pub flags: Vec<String>,
+    pub msgctxt: Option<String>,
+    pub msgid: String,
+    pub msgstr: String,
+}
+
+impl PoEntry {
+    pub fn is_fuzzy(&self) -> bool {
+        self.flags.iter().any(|f| f == FUZZY)
+    }
+
+    /// Balloon number of the entry's context, `None` if the context isn't `<number>|<type>`.
+    pub fn balloon_index(&self) -> Option<usize> {
+        let (number, _) = self.msgctxt.as_deref()?.split_once('|')?;
+        number.trim().parse::<usize>().ok()?.checked_sub(1)
+    }
+
+    /// Balloon type of the entry's context, `None` if the context isn't `<number>|<type>`.
+    pub fn balloon_type(&self) -> Option<&str> {
+        let (_, btype) = self.msgctxt.as_deref()?.split_once('|')?;
+        Some(btype)
+    }
+}
+
+// Type label and source text of a balloon's entry.
+fn entry_source(d: &Document, b: &Balloon) -> (String, String) {
+    match &b.sfx {
+        Some(sfx) => (TXT_SFX_MARKER.to_string(), sfx.original.clone()),
+        None => (d.type_prefix(&b.btype), b.source_content.join("\n")),
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// Multi-line strings start with an empty string, one line per string after it.
+fn write_string(po: &mut String, keyword: &str, s: &str) {
+    if !s.contains('\n') {
+        po.push_str(&format!("{} \"{}\"\n", keyword, escape(s)));
+        return;
+    }
+    po.push_str(&format!("{} \"\"\n", keyword));
+    for line in s.split_inclusive('\n') {
+        po.push_str(&format!("\"{}\"\n", escape(line)));
+    }
+}
+
+impl std::fmt::Display for PoEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut po = String::new();
+        for comment in &self.translator_comments {
+            for line in comment.lines() {
+                match line.is_empty() {
+                    true => po.push_str("#\n"),
+                    false => po.push_str(&format!("# {}\n", line)),
+                }
+            }
+        }
+        for comment in &self.extracted_comments {
+            po.push_str(&format!("#. {}\n", comment));
+        }
+        if !self.flags.is_empty() {
+            po.push_str(&format!("#, {}\n", self.flags.join(", ")));
+        }
+        if let Some(msgctxt) = &self.msgctxt {
+            write_string(&mut po, "msgctxt", msgctxt);
+        }
+        write_string(&mut po, "msgid", &self.msgid);
+        write_string(&mut po, "msgstr", &self.msgstr);
+        write!(f, "{}", po)
+    }
+}
+
+/// Writes a document as a PO file.
+/// `language` is the language of the translations, like `en`.
+pub fn to_po(d: &Document, language: &str) -> String {
+    let header = PoEntry {
+        msgstr: format!(
+            "Project-Id-Version: {}\nLanguage: {}\nMIME-Version: 1.0\n\
+             Content-Type: text/plain; charset=UTF-8\nContent-Transfer-Encoding: 8bit\n\
+             X-Generator: {}\n",
+            d.METADATA_INFO, language, d.METADATA_SCRIPT_VERSION
+        ),
+        ..Default::default()
+    };
+
+    let mut po = header.to_string();
+    for (i, b) in d.balloons.iter().enumerate() {
+        let (btype, msgid) = entry_source(d, b);
+        let msgstr = match &b.sfx {
+            Some(sfx) => sfx.translation.clone(),
+            None if b.pr_chars() != 0 => b.pr_content.join("\n"),
+            None => b.tl_content.join("\n"),
+        };
+
+        let entry = PoEntry {
+            translator_comments: b.comments.clone(),
+            extracted_comments: b
+                .page
+                .map(|p| format!("Page {}", p + 1))
+                .into_iter()
+                .collect(),
+            flags: match b.status {
+                BalloonStatus::MachineDraft => vec![FUZZY.to_string()],
+                _ => Vec::new(),
+            },
+            msgctxt: Some(format!("{}|{}", i + 1, btype)),
+            msgid,
+            msgstr,
+        };
+        let _ = write!(po, "\n{}", entry);
+    }
+    po
+}
+
+// Which string the continuation lines belong to.
+#[derive(Clone, Copy)]
+enum PoField {
+    Msgctxt,
+    Msgid,
+    Msgstr,
+    Ignored,
+}
+
+/// Reads the entries of a PO file, including the header.
+/// Obsolete entries (`#~`) and plural forms other than the first one are skipped.
+pub fn read_entries(po: &str) -> Result<Vec<PoEntry>> {
+    let mut entries = Vec::new();
+    let mut entry = PoEntry::default();
+    // The current entry has a msgid or msgstr, a comment or keyword starts a new entry
+    let mut has_strings = false;
+    let mut field = PoField::Ignored;
+
+    for (number, line) in po.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#~") {
+            continue;
+        }
+
+        let starts_entry = line.starts_with('#')
+            || line.starts_with("msgctxt")
+            || (line.starts_with("msgid") && !line.starts_with("msgid_plural"));
+        if starts_entry && has_strings {
+            entries.push(std::mem::take(&mut entry));
+            has_strings = false;
+        }
+
+        if let Some(comment) = line.strip_prefix("#.") {
+            entry.extracted_comments.push(comment.trim().to_string());
+        } else if let Some(flags) = line.strip_prefix("#,") {
+            entry
+                .flags
+                .extend(flags.split(',').map(|f| f.trim().to_string()));
+        } else if line.starts_with("#:") || line.starts_with("#|") {
+            // References and previous strings aren't kept
+        } else if let Some(comment) = line.strip_prefix('#') {
+            let comment = comment.strip_prefix(' ').unwrap_or(comment);
+            entry.translator_comments.push(comment.to_string());
+        } else {
+            let (keyword, rest) = match line.find('"') {
+                Some(i) => (line[..i].trim(), &line[i..]),
+                None => return Err(format!("Line {}: Missing string.", number + 1).into()),
+            };
+            let Some(s) = rest.strip_prefix('"').and_then(|r| r.strip_suffix('"')) else {
+                return Err(format!("Line {}: Unterminated string.", number + 1).into());
+            };
+            let s = unescape(s);
+
+            field = match keyword {
+                "" => field,
+                "msgctxt" => PoField::Msgctxt,
+                "msgid" => PoField::Msgid,
+                "msgstr" | "msgstr[0]" => PoField::Msgstr,
+                k if k == "msgid_plural" || k.starts_with("msgstr[") => PoField::Ignored,
+                k => return Err(format!("Line {}: Unknown keyword {}.", number + 1, k).into()),
+            };
+            match field {
+                PoField::Msgctxt => entry.msgctxt.get_or_insert_with(String::new).push_str(&s),
+                PoField::Msgid => entry.msgid.push_str(&s),
+                PoField::Msgstr => entry.msgstr.push_str(&s),
+                PoField::Ignored => {}
+            }
+            has_strings |= !matches!(field, PoField::Msgctxt);
+        }
+    }
+    if has_strings {
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Merges the translations, translator comments and fuzzy flags of a PO file into a document.
+///
+/// Entries are matched to balloons by the numbers in their contexts. A translation replaces
+/// the proofread text of balloons having one, and the translation of the rest.
+/// Fuzzy entries become machine drafts, translated entries of pending balloons and machine
+/// drafts become translated. Entries whose msgid or type isn't the source text or type of
+/// their balloon are reported as mismatched and not merged, entries without a matching
+/// balloon are skipped.
+pub fn merge_po(d: &mut Document, po: &str) -> Result<MergeReport> {
+    let mut report = MergeReport::default();
+    for entry in read_entries(po)? {
+        // The header has no context
+        if entry.msgctxt.is_none() && entry.msgid.is_empty() {
+            continue;
+        }
+        let Some(i) = entry.balloon_index().filter(|i| *i < d.balloons.len()) else {
+            report.unmatched += 1;
+            continue;
+        };
+        let (btype, source) = entry_source(d, &d.balloons[i]);
+        if entry.balloon_type() != Some(btype.as_str()) || !entry.msgid.lines().eq(source.lines()) {
+            report.mismatched.push(i);
+            continue;
+        }
+
+        let fuzzy = entry.is_fuzzy();
+        let b = &mut d.balloons[i];
+
+        let lines = Vec::from_iter(entry.msgstr.lines().map(String::from));
+        let has_pr = b.pr_chars() != 0;
+        match b.sfx.as_mut() {
+            Some(sfx) => sfx.translation = entry.msgstr.clone(),
+            None if has_pr => b.pr_content = lines,
+            None => b.tl_content = lines,
+        }
+
+        if fuzzy {
+            b.status = BalloonStatus::MachineDraft;
+        } else if !entry.msgstr.is_empty()
+            && matches!(
+                b.status,
+                BalloonStatus::Pending | BalloonStatus::MachineDraft
+            )
+        {
+            b.status = BalloonStatus::Translated;
+        }
+        b.comments = entry.translator_comments;
+        report.merged += 1;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod po_tests {
+    use super::*;
+    use crate::test_util::sample_document;
+
+    #[test]
+    fn po_export() {
+        let mut d = sample_document();
+        d.balloons[0].status = BalloonStatus::MachineDraft;
+        let po = to_po(&d, "en");
+
+        assert!(
+            po.starts_with("msgid \"\"\nmsgstr \"\"\n\"Project-Id-Version: Chapter <12>\\n\"\n")
+        );
+        assert!(po.contains("\"Language: en\\n\"\n"));
+        assert!(po.contains(
+            "# Loud | clear\n#. Page 1\n#, fuzzy\nmsgctxt \"1|()\"\nmsgid \"おい!\"\n\
+             msgstr \"\"\n\"Hey!!\\n\"\n\"[b]Say \\\"hi\\\"[/b] & go\"\n"
+        ));
+        assert!(po.contains("#. Page 1\nmsgctxt \"2|HW\"\nmsgid \"\"\nmsgstr \"ね…\"\n"));
+        assert!(po.contains("#. Page 2\nmsgctxt \"3|#SFX\"\nmsgid \"ドン\"\nmsgstr \"BOOM\"\n"));
+        assert!(po.contains("\nmsgctxt \"4|()\"\nmsgid \"\"\nmsgstr \"Later\"\n"));
+
+        let entries = read_entries(&po).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0].msgctxt, None);
+        assert_eq!(entries[1].balloon_index(), Some(0));
+        assert_eq!(entries[1].msgstr, "Hey!!\n[b]Say \"hi\"[/b] & go");
+        assert_eq!(entries[1].translator_comments, vec!["Loud | clear"]);
+        assert!(entries[1].is_fuzzy());
+        assert!(!entries[2].is_fuzzy());
+    }
+
+    #[test]
+    fn po_merge() {
+        let mut d = sample_document();
+        d.balloons[0].status = BalloonStatus::MachineDraft;
+        let po = to_po(&d, "en")
+            .replace("#, fuzzy\n", "")
+            .replace("# Loud | clear\n", "# Louder\n# Check the name\n")
+            .replace("\"Hey!!\\n\"", "\"Hey!!!\\n\"")
+            .replace("msgstr \"ね…\"", "msgstr \"ねえ…\"")
+            .replace("msgctxt \"3|#SFX\"", "msgctxt \"9|#SFX\"");
+
+        let report = merge_po(&mut d, &po).unwrap();
+        assert_eq!(report.merged, 3);
+        assert_eq!(report.unmatched, 1);
+        assert_eq!(d.balloons[0].status, BalloonStatus::Translated);
+        assert_eq!(d.balloons[0].comments, vec!["Louder", "Check the name"]);
+        assert_eq!(d.balloons[0].tl_content, vec!["Hey!", "Say \"hi\" & go"]);
+        assert_eq!(d.balloons[0].pr_content[0], "Hey!!!");
+        assert_eq!(d.balloons[1].tl_content, vec!["ねえ…"]);
+        assert_eq!(d.balloons[1].status, BalloonStatus::Translated);
+        // The sound effect's entry doesn't match a balloon anymore
+        assert_eq!(d.balloons[2].sfx.as_ref().unwrap().translation, "BOOM");
+
+        let po = "#, fuzzy\nmsgctxt \"3|#SFX\"\nmsgid \"ドン\"\nmsgstr \"BAM\"\n";
+        merge_po(&mut d, po).unwrap();
+        assert_eq!(d.balloons[2].sfx.as_ref().unwrap().translation, "BAM");
+        assert_eq!(d.balloons[2].status, BalloonStatus::MachineDraft);
+
+        // Entries of another type or source text are not merged
+        let po = "msgctxt \"2|()\"\nmsgid \"\"\nmsgstr \"Hm\"\n\n\
+                  msgctxt \"4|()\"\nmsgid \"あ\"\nmsgstr \"Ah\"\n";
+        let report = merge_po(&mut d, po).unwrap();
+        assert_eq!(report.merged, 0);
+        assert_eq!(report.mismatched, vec![1, 3]);
+        assert_eq!(d.balloons[1].tl_content, vec!["ねえ…"]);
+        assert_eq!(d.balloons[3].tl_content, vec!["Later"]);
+
+        assert!(read_entries("msgid \"unterminated\n").is_err());
+    }
+}
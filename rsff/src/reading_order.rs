@@ -0,0 +1,231 @@
+//! Reading order of balloons placed on pages.
+//!
+//! Regions are ordered with a recursive XY-cut: they are split into rows where there
+//! is a horizontal gap between them, rows are split into columns where there is a
+//! vertical gap, and columns are read in the document's reading direction. When the
+//! page's panels are known, balloons are grouped by panel first and panels are read
+//! the same way, so a tall panel is finished before moving on to its neighbour.
+
+use serde::{Deserialize, Serialize};
+
+use crate::img_data::Region;
+
+/// Reading direction of a work. Default value is `RightToLeft`.
+///
+/// `RightToLeft`: Manga\
+/// `LeftToRight`: Western comics, manhua\
+/// `TopToBottom`: Webtoons, read strictly by vertical position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ReadingDirection {
+    #[default]
+    RightToLeft,
+    LeftToRight,
+    TopToBottom,
+}
+
+impl ReadingDirection {
+    pub const ALL: [Self; 3] = [Self::RightToLeft, Self::LeftToRight, Self::TopToBottom];
+}
+
+/// Orders the regions of a single page.
+/// `panels` are the page's panel areas in any order, can be empty.
+///
+/// Returns the indexes of `regions` in reading order.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::img_data::Region;
+/// use rsff::reading_order::{page_order, ReadingDirection};
+///
+/// let regions = [Region::new(0, 0, 10, 10), Region::new(50, 0, 10, 10)];
+/// assert_eq!(page_order(&regions, &[], ReadingDirection::RightToLeft), vec![1, 0]);
+/// assert_eq!(page_order(&regions, &[], ReadingDirection::LeftToRight), vec![0, 1]);
+/// ```
+pub fn page_order(
+    regions: &[Region],
+    panels: &[Region],
+    direction: ReadingDirection,
+) -> Vec<usize> {
+    let items = regions.iter().copied().enumerate().collect::<Vec<_>>();
+
+    if direction == ReadingDirection::TopToBottom || panels.is_empty() {
+        let mut out = Vec::with_capacity(items.len());
+        xy_cut(items, direction, &mut out);
+        return out;
+    }
+
+    // Balloons outside of every panel are read after the panels
+    let mut groups = vec![Vec::new(); panels.len() + 1];
+    for (i, r) in items {
+        let panel = panel_of(&r, panels).unwrap_or(panels.len());
+        groups[panel].push((i, r));
+    }
+
+    let mut panel_order = Vec::with_capacity(panels.len());
+    xy_cut(
+        panels.iter().copied().enumerate().collect(),
+        direction,
+        &mut panel_order,
+    );
+    panel_order.push(panels.len());
+
+    let mut out = Vec::with_capacity(regions.len());
+    for p in panel_order {
+        xy_cut(std::mem::take(&mut groups[p]), direction, &mut out);
+    }
+    out
+}
+
+/// Sorts regions of a single page into reading order.
+pub fn sort_regions(regions: &mut Vec<Region>, direction: ReadingDirection) {
+    let order = page_order(regions, &[], direction);
+    *regions = order.into_iter().map(|i| regions[i]).collect();
+}
+
+// Panel containing the region's center, or the one it overlaps the most.
+fn panel_of(r: &Region, panels: &[Region]) -> Option<usize> {
+    let (cx, cy) = r.center();
+    panels.iter().position(|p| p.contains(cx, cy)).or_else(|| {
+        panels
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, p.intersection_area(r)))
+            .filter(|(_, a)| *a > 0)
+            .max_by_key(|(_, a)| *a)
+            .map(|(i, _)| i)
+    })
+}
+
+fn xy_cut(items: Vec<(usize, Region)>, direction: ReadingDirection, out: &mut Vec<usize>) {
+    if items.len() <= 1 {
+        out.extend(items.into_iter().map(|(i, _)| i));
+        return;
+    }
+
+    if direction == ReadingDirection::TopToBottom {
+        let mut items = items;
+        items.sort_by_key(|(_, r)| (r.y, r.x));
+        out.extend(items.into_iter().map(|(i, _)| i));
+        return;
+    }
+
+    let mut rows = split(items, |r| (r.y, r.bottom()));
+    if rows.len() > 1 {
+        for row in rows {
+            xy_cut(row, direction, out);
+        }
+        return;
+    }
+
+    let mut columns = split(rows.remove(0), |r| (r.x, r.right()));
+    if columns.len() > 1 {
+        if direction == ReadingDirection::RightToLeft {
+            columns.reverse();
+        }
+        for column in columns {
+            xy_cut(column, direction, out);
+        }
+        return;
+    }
+
+    // Overlapping in both axes, no cut possible: read by center
+    let mut items = columns.remove(0);
+    items.sort_by_key(|(_, r)| {
+        let (cx, cy) = r.center();
+        let x = match direction {
+            ReadingDirection::RightToLeft => -(cx as i64),
+            _ => cx as i64,
+        };
+        (cy, x)
+    });
+    out.extend(items.into_iter().map(|(i, _)| i));
+}
+
+// Splits items into groups separated by gaps along one axis, in ascending order.
+fn split(
+    mut items: Vec<(usize, Region)>,
+    span: impl Fn(&Region) -> (u32, u32),
+) -> Vec<Vec<(usize, Region)>> {
+    items.sort_by_key(|(_, r)| span(r).0);
+
+    let mut groups: Vec<Vec<(usize, Region)>> = Vec::new();
+    let mut end = 0;
+    for (i, r) in items {
+        let (s, e) = span(&r);
+        match groups.last_mut() {
+            Some(group) if s < end => group.push((i, r)),
+            _ => groups.push(vec![(i, r)]),
+        }
+        end = end.max(e);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod reading_order_tests {
+    use super::*;
+
+    // A manga page: tall panel on the right, two stacked panels on the left
+    // and a wide panel below them.
+    fn page() -> (Vec<Region>, Vec<Region>) {
+        let panels = vec![
+            Region::new(0, 0, 95, 95),
+            Region::new(0, 105, 95, 95),
+            Region::new(105, 0, 95, 200),
+            Region::new(0, 210, 200, 90),
+        ];
+        let regions = vec![
+            Region::new(10, 20, 30, 20),   // 0: left top panel
+            Region::new(150, 10, 30, 20),  // 1: right panel, top
+            Region::new(150, 160, 30, 20), // 2: right panel, bottom
+            Region::new(10, 120, 30, 20),  // 3: left bottom panel
+            Region::new(20, 250, 30, 20),  // 4: bottom panel, left
+            Region::new(150, 245, 30, 20), // 5: bottom panel, right
+        ];
+        (regions, panels)
+    }
+
+    #[test]
+    fn reading_order_panel_aware() {
+        let (regions, panels) = page();
+
+        assert_eq!(
+            page_order(&regions, &panels, ReadingDirection::RightToLeft),
+            vec![1, 2, 0, 3, 5, 4]
+        );
+        assert_eq!(
+            page_order(&regions, &panels, ReadingDirection::LeftToRight),
+            vec![0, 3, 1, 2, 4, 5]
+        );
+    }
+
+    #[test]
+    fn reading_order_without_panels() {
+        let (regions, _) = page();
+
+        // Rows come first without panel information
+        assert_eq!(
+            page_order(&regions, &[], ReadingDirection::RightToLeft),
+            vec![1, 0, 3, 2, 5, 4]
+        );
+        assert_eq!(
+            page_order(&regions, &[], ReadingDirection::TopToBottom),
+            vec![1, 0, 3, 2, 5, 4]
+        );
+    }
+
+    #[test]
+    fn reading_order_overlapping_regions() {
+        let regions = [Region::new(0, 0, 50, 50), Region::new(30, 0, 50, 50)];
+
+        assert_eq!(
+            page_order(&regions, &[], ReadingDirection::RightToLeft),
+            vec![1, 0]
+        );
+        assert_eq!(
+            page_order(&regions, &[], ReadingDirection::LeftToRight),
+            vec![0, 1]
+        );
+    }
+}
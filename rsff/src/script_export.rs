@@ -0,0 +1,380 @@
+//! HTML and Markdown scripts, for reviewers reading a chapter without lescan.
+//!
+//! Both list the balloons page by page with their translation and proofread text side by side:
+//!
+//! | No | Type | TL   | PR    | Comments |
+//! |----|------|------|-------|----------|
+//! | 1  | ()   | Hey! | Hey!! | Loud     |
+//!
+//! The HTML script is a single self-contained file. Page thumbnails and balloon images are
+//! embedded in it and rows are colored by balloon type.
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
+
+use crate::balloon::Balloon;
+use crate::consts::TYPES;
+use crate::docx_export::section_heading;
+use crate::markup::{self, TextStyle};
+use crate::sfx::TXT_SFX_MARKER;
+use crate::style::Color;
+use crate::Document;
+
+/// Page thumbnails are scaled down to fit in a square of this size, in pixels.
+pub const PAGE_THUMBNAIL_SIZE: u32 = 320;
+/// Balloon images are scaled down to fit in a square of this size, in pixels.
+pub const BALLOON_THUMBNAIL_SIZE: u32 = 160;
+
+const SFX_COLOR: Color = Color::new(0xe7, 0x4c, 0x3c);
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em auto; max-width: 70em; color: #222; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.4em; vertical-align: top; text-align: left; }
+th { background: #f3f3f3; }
+td.tl, td.pr { width: 30%; }
+td.comments { color: #666; font-style: italic; }
+.type { font-family: monospace; white-space: nowrap; }
+.legend span { display: inline-block; margin-right: 1em; padding-left: 0.4em; }
+img.page { float: right; margin: 0 0 1em 1em; border: 1px solid #ccc; }
+section { clear: both; }";
+
+/// Options of the HTML exporter.
+///
+/// `page_thumbnails`: Embeds a thumbnail of every page next to its balloons.\
+/// `balloon_images`: Embeds balloon images in an image column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HtmlExportOptions {
+    pub page_thumbnails: bool,
+    pub balloon_images: bool,
+}
+
+impl Default for HtmlExportOptions {
+    fn default() -> Self {
+        Self {
+            page_thumbnails: true,
+            balloon_images: true,
+        }
+    }
+}
+
+/// Color of a balloon type in the HTML script.
+pub fn type_color(d: &Document, t: &TYPES) -> Color {
+    match t {
+        TYPES::DIALOGUE => Color::new(0x4a, 0x90, 0xd9),
+        TYPES::SQUARE => Color::new(0xe0, 0xa0, 0x30),
+        TYPES::THINKING => Color::new(0x9b, 0x59, 0xb6),
+        TYPES::ST => Color::new(0x7f, 0x8c, 0x8d),
+        TYPES::OT => Color::new(0x27, 0xae, 0x60),
+        TYPES::CUSTOM(name) => d.custom_type(name).map_or(Color::BLACK, |t| t.color),
+    }
+}
+
+// Balloons grouped by consecutive pages, with the heading of every group.
+fn sections(d: &Document) -> Vec<(Option<usize>, String, Vec<usize>)> {
+    let mut sections: Vec<(Option<usize>, String, Vec<usize>)> = Vec::new();
+    for (i, b) in d.balloons.iter().enumerate() {
+        match sections.last_mut() {
+            Some((page, _, balloons)) if *page == b.page => balloons.push(i),
+            _ => sections.push((b.page, section_heading(d, b.page), vec![i])),
+        }
+    }
+    sections
+}
+
+// Type label and translation lines of a balloon, sound effects are written like in txt files.
+fn type_and_tl(d: &Document, b: &Balloon) -> (String, Vec<String>) {
+    match &b.sfx {
+        Some(sfx) => (TXT_SFX_MARKER.to_string(), vec![sfx.to_txt()]),
+        None => (d.type_prefix(&b.btype), b.tl_content.clone()),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn data_uri(img_type: &str, data: &[u8]) -> String {
+    format!("data:image/{};base64,{}", img_type, B64.encode(data))
+}
+
+fn span_to_html(text: &str, style: &TextStyle) -> String {
+    let mut html = escape_html(text);
+    if style.bold {
+        html = format!("<b>{}</b>", html);
+    }
+    if style.italic {
+        html = format!("<i>{}</i>", html);
+    }
+    if style.underline {
+        html = format!("<u>{}</u>", html);
+    }
+    if style.small_caps {
+        html = format!("<span style=\"font-variant: small-caps\">{}</span>", html);
+    }
+    if let Some(font) = &style.font {
+        html = format!(
+            "<span style=\"font-family: '{}'\">{}</span>",
+            escape_html(font),
+            html
+        );
+    }
+    html
+}
+
+fn lines_to_html(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|l| {
+            markup::parse_lossy(l)
+                .iter()
+                .map(|s| span_to_html(&s.text, &s.style))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+fn page_thumbnail(path: &str) -> Option<String> {
+    let img = image::open(path).ok()?;
+    let mut png = std::io::Cursor::new(Vec::new());
+    img.thumbnail(PAGE_THUMBNAIL_SIZE, PAGE_THUMBNAIL_SIZE)
+        .write_to(&mut png, image::ImageFormat::Png)
+        .ok()?;
+    Some(data_uri("png", &png.into_inner()))
+}
+
+/// Writes a document as a self-contained HTML page.
+/// Pages that can't be read are written without thumbnails.
+pub fn to_html(d: &Document, options: &HtmlExportOptions) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!(
+        "<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n",
+        escape_html(&d.METADATA_INFO),
+        STYLE
+    ));
+    html.push_str(&format!(
+        "<header>\n<h1>{}</h1>\n<p>{}, {} balloons, {} translation characters</p>\n",
+        escape_html(&d.METADATA_INFO),
+        escape_html(&d.METADATA_SCRIPT_VERSION),
+        d.len(),
+        d.tl_chars()
+    ));
+
+    html.push_str("<p class=\"legend\">");
+    for t in d.balloon_types() {
+        html.push_str(&format!(
+            "<span style=\"border-left: 0.8em solid {}\">{}</span>",
+            type_color(d, &t),
+            escape_html(&d.type_prefix(&t))
+        ));
+    }
+    html.push_str(&format!(
+        "<span style=\"border-left: 0.8em solid {}\">{}</span></p>\n</header>\n",
+        SFX_COLOR, TXT_SFX_MARKER
+    ));
+
+    let mut header = vec!["No", "Type"];
+    if options.balloon_images {
+        header.push("Image");
+    }
+    header.extend(["TL", "PR", "Comments"]);
+
+    for (page, heading, balloons) in sections(d) {
+        html.push_str(&format!("<section>\n<h2>{}</h2>\n", escape_html(&heading)));
+        let thumbnail = page
+            .filter(|_| options.page_thumbnails)
+            .and_then(|p| d.images.as_ref()?.get(p))
+            .and_then(|path| page_thumbnail(path));
+        if let Some(uri) = thumbnail {
+            html.push_str(&format!(
+                "<img class=\"page\" src=\"{}\" alt=\"{}\">\n",
+                uri,
+                escape_html(&heading)
+            ));
+        }
+
+        html.push_str("<table>\n<tr>");
+        for h in &header {
+            html.push_str(&format!("<th>{}</th>", h));
+        }
+        html.push_str("</tr>\n");
+
+        for i in balloons {
+            let b = &d.balloons[i];
+            let (btype, tl) = type_and_tl(d, b);
+            let color = match b.sfx {
+                Some(_) => SFX_COLOR,
+                None => type_color(d, &b.btype),
+            };
+
+            html.push_str(&format!(
+                "<tr style=\"border-left: 0.5em solid {}\">\
+                 <td>{}</td><td class=\"type\">{}</td>",
+                color,
+                i + 1,
+                escape_html(&btype)
+            ));
+            if options.balloon_images {
+                let img = b
+                    .balloon_img
+                    .as_ref()
+                    .and_then(|img| img.thumbnail(BALLOON_THUMBNAIL_SIZE).ok());
+                match img {
+                    Some(img) => html.push_str(&format!(
+                        "<td><img src=\"{}\" alt=\"{}\"></td>",
+                        data_uri(&img.img_type, &img.img_data),
+                        i + 1
+                    )),
+                    None => html.push_str("<td></td>"),
+                }
+            }
+            html.push_str(&format!(
+                "<td class=\"tl\">{}</td><td class=\"pr\">{}</td><td class=\"comments\">{}</td></tr>\n",
+                lines_to_html(&tl),
+                lines_to_html(&b.pr_content),
+                b.comments
+                    .iter()
+                    .map(|c| escape_html(c))
+                    .collect::<Vec<_>>()
+                    .join("<br>")
+            ));
+        }
+        html.push_str("</table>\n</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// Table cells can't have line breaks or unescaped pipes.
+fn escape_markdown(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '|' | '*' | '_' | '`' | '<' | '>' | '[' | ']' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn lines_to_markdown(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|l| {
+            markup::parse_lossy(l)
+                .iter()
+                .map(|s| {
+                    let mut text = escape_markdown(&s.text);
+                    if s.style.bold && !text.is_empty() {
+                        text = format!("**{}**", text);
+                    }
+                    if s.style.italic && !text.is_empty() {
+                        text = format!("*{}*", text);
+                    }
+                    text
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+/// Writes a document as Markdown with a table of balloons for every page.
+/// Images are left out, bold and italic markup is kept.
+pub fn to_markdown(d: &Document) -> String {
+    let mut md = format!(
+        "# {}\n\n{}, {} balloons, {} translation characters\n",
+        escape_markdown(&d.METADATA_INFO),
+        escape_markdown(&d.METADATA_SCRIPT_VERSION),
+        d.len(),
+        d.tl_chars()
+    );
+
+    for (_, heading, balloons) in sections(d) {
+        md.push_str(&format!("\n## {}\n\n", escape_markdown(&heading)));
+        md.push_str("| No | Type | TL | PR | Comments |\n");
+        md.push_str("|----|------|----|----|----------|\n");
+        for i in balloons {
+            let b = &d.balloons[i];
+            let (btype, tl) = type_and_tl(d, b);
+            md.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                i + 1,
+                escape_markdown(&btype),
+                lines_to_markdown(&tl),
+                lines_to_markdown(&b.pr_content),
+                b.comments
+                    .iter()
+                    .map(|c| escape_markdown(c))
+                    .collect::<Vec<_>>()
+                    .join("<br>")
+            ));
+        }
+    }
+    md
+}
+
+#[cfg(test)]
+mod script_export_tests {
+    use super::*;
+    use crate::test_util::sample_document;
+
+    #[test]
+    fn script_export_html() {
+        let d = sample_document();
+        let html = to_html(&d, &HtmlExportOptions::default());
+
+        assert!(html.contains("<title>Chapter &lt;12&gt;</title>"));
+        assert!(html.contains("<h2>Page 1 (001.png)</h2>"));
+        assert!(html.contains("<h2>Page 2 (002.png)</h2>"));
+        assert!(html.contains("<h2>Unplaced balloons</h2>"));
+        assert!(html.contains(
+            "<td class=\"tl\">Hey!<br>Say &quot;hi&quot; &amp; go</td>\
+             <td class=\"pr\">Hey!!<br><b>Say &quot;hi&quot;</b> &amp; go</td>"
+        ));
+        assert!(html.contains("<td class=\"comments\">Loud | clear</td>"));
+        assert!(html.contains("border-left: 0.5em solid #010203"));
+        assert!(html.contains("<td class=\"type\">#SFX</td>"));
+        assert!(html.contains("<img src=\"data:image/png;base64,"));
+
+        let no_images = to_html(
+            &d,
+            &HtmlExportOptions {
+                page_thumbnails: false,
+                balloon_images: false,
+            },
+        );
+        assert!(!no_images.contains("<img"));
+        assert!(!no_images.contains("<th>Image</th>"));
+    }
+
+    #[test]
+    fn script_export_markdown() {
+        let md = to_markdown(&sample_document());
+
+        assert!(md.starts_with("# Chapter \\<12\\>\n"));
+        assert!(md.contains(
+            "\n## Page 1 (001.png)\n\n| No | Type | TL | PR | Comments |\n|----|------|----|----|----------|\n\
+             | 1 | () | Hey!<br>Say \"hi\" & go | Hey!!<br>**Say \"hi\"** & go | Loud \\| clear |\n"
+        ));
+        assert!(md.contains("| 2 | HW | ね… |  |  |\n"));
+        assert!(md.contains("| 3 | #SFX | ドン \\| don \\| BOOM \\| replace |  |  |\n"));
+        assert!(md.contains("\n## Unplaced balloons\n"));
+    }
+}
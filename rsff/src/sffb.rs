@@ -0,0 +1,256 @@
+//! sffb, a compressed document with balloon images stored as raw bytes.
+//!
+//! sffz files keep balloon images as base64 inside the JSON document, which makes them
+//! a third larger and has them encoded and compressed again on every save. An sffb file
+//! starts with a header and a text section, an sffz compressed JSON document without
+//! images, followed by a blob section with the raw bytes of every image in balloon order:
+//!
+//! ```notrust
+//! "SFFB" | version: u8 | text section length: u64 LE | text section | blob section
+//! ```
+//!
+//! Images are png or webp crops which don't compress any further, so the blob section is
+//! stored as is. The text section can be read without touching the blob section, see
+//! [`read_text`].
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::codec::{self, Codec};
+use crate::img_data::BalloonImage;
+use crate::Document;
+
+/// First bytes of an sffb file.
+pub const SFFB_MAGIC: &[u8; 4] = b"SFFB";
+
+const SFFB_VERSION: u8 = 1;
+const HEADER_LEN: usize = SFFB_MAGIC.len() + 1 + 8;
+
+/// Location of a balloon image in the blob section.
+///
+/// `offset`: start of the image, from the start of the blob section\
+/// `len`: length of the image in bytes
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ImageRef {
+    pub img_type: String,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// Text section of an sffb file, the document without balloon images.
+///
+/// `images`: image location of every balloon, in balloon order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextSection {
+    pub document: Document,
+    pub images: Vec<Option<ImageRef>>,
+    #[serde(skip)]
+    blob_start: u64,
+}
+
+impl TextSection {
+    /// Reads the image of a balloon from the sffb file the section was read from.
+    /// Returns `None` for balloons without an image.
+    pub fn load_image<R: Read + Seek>(
+        &self,
+        r: &mut R,
+        balloon: usize,
+    ) -> std::io::Result<Option<BalloonImage>> {
+        match self.images.get(balloon) {
+            Some(Some(img)) => {
+                r.seek(SeekFrom::Start(self.blob_start + img.offset))?;
+                Ok(Some(BalloonImage {
+                    img_type: img.img_type.clone(),
+                    img_data: read_blob(r, img.len)?,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_blob<R: Read>(r: &mut R, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut blob = Vec::new();
+    r.take(len).read_to_end(&mut blob)?;
+    if blob.len() as u64 != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(blob)
+}
+
+/// Writes the document as sffb, the text section is compressed with the codec.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::codec::Codec;
+/// use rsff::{sffb, Document};
+///
+/// let mut sffb_file = Vec::new();
+/// sffb::write(&Document::default(), Codec::Zstd, &mut sffb_file).unwrap();
+/// let d = sffb::read(&*sffb_file).unwrap();
+/// ```
+pub fn write<W: Write>(d: &Document, codec: Codec, w: &mut W) -> std::io::Result<()> {
+    let mut document = d.clone();
+    let mut blobs = Vec::new();
+    let mut offset = 0;
+    let images = document
+        .balloons
+        .iter_mut()
+        .map(|b| {
+            b.balloon_img.take().map(|img| {
+                let img_ref = ImageRef {
+                    img_type: img.img_type,
+                    offset,
+                    len: img.img_data.len() as u64,
+                };
+                offset += img_ref.len;
+                blobs.push(img.img_data);
+                img_ref
+            })
+        })
+        .collect();
+
+    let section = TextSection {
+        document,
+        images,
+        blob_start: 0,
+    };
+    let text = codec::compress(&serde_json::to_vec(&section)?, codec)?;
+
+    w.write_all(SFFB_MAGIC)?;
+    w.write_all(&[SFFB_VERSION])?;
+    w.write_all(&(text.len() as u64).to_le_bytes())?;
+    w.write_all(&text)?;
+    for blob in blobs {
+        w.write_all(&blob)?;
+    }
+    Ok(())
+}
+
+/// Reads the header and the text section of an sffb file.
+/// The reader is left at the start of the blob section, images aren't read.
+pub fn read_text<R: Read>(r: &mut R) -> std::io::Result<TextSection> {
+    let mut header = [0; HEADER_LEN];
+    r.read_exact(&mut header)?;
+    if !header.starts_with(SFFB_MAGIC) {
+        return Err(invalid_data("Not an sffb file!"));
+    }
+    if header[SFFB_MAGIC.len()] != SFFB_VERSION {
+        return Err(invalid_data("Unsupported sffb version!"));
+    }
+
+    let text_len = u64::from_le_bytes(header[SFFB_MAGIC.len() + 1..].try_into().unwrap());
+    let text = codec::decompress(&read_blob(r, text_len)?)?;
+    let mut section: TextSection = serde_json::from_slice(&text)?;
+    if section.images.len() != section.document.balloons.len() {
+        return Err(invalid_data("Balloon and image counts don't match!"));
+    }
+    section.blob_start = HEADER_LEN as u64 + text_len;
+    Ok(section)
+}
+
+/// Reads the text section of an sffb file, see [`read_text`].
+pub fn open_text<P: AsRef<Path>>(file_path: P) -> std::io::Result<TextSection> {
+    read_text(&mut BufReader::new(File::open(file_path)?))
+}
+
+/// Reads a whole sffb file.
+pub fn read<R: Read>(mut r: R) -> std::io::Result<Document> {
+    let section = read_text(&mut r)?;
+    let mut d = section.document;
+
+    // Images are written in balloon order, so the blob section is read without seeking
+    let mut offset = 0;
+    for (b, img) in d.balloons.iter_mut().zip(section.images) {
+        if let Some(img) = img {
+            if img.offset != offset {
+                return Err(invalid_data("Images are out of order!"));
+            }
+            offset += img.len;
+            b.add_image(img.img_type, read_blob(&mut r, img.len)?);
+        }
+    }
+    Ok(d)
+}
+
+#[cfg(test)]
+mod sffb_tests {
+    use super::*;
+    use crate::Balloon;
+    use std::io::Cursor;
+
+    fn document() -> Document {
+        let mut d = Document::default();
+        for i in 0..3 {
+            let mut b = Balloon::default();
+            b.add_tl(format!("tl {}", i));
+            if i != 1 {
+                b.add_image("png".to_string(), vec![i as u8; 10 + i]);
+            }
+            d.balloons.push(b);
+        }
+        d
+    }
+
+    #[test]
+    fn sffb_round_trip() {
+        let d = document();
+        for codec in Codec::ALL {
+            let mut sffb_file = Vec::new();
+            write(&d, codec, &mut sffb_file).unwrap();
+
+            assert!(sffb_file.starts_with(SFFB_MAGIC));
+            assert_eq!(read(&*sffb_file).unwrap().balloons, d.balloons);
+        }
+    }
+
+    #[test]
+    fn sffb_read_text() {
+        let d = document();
+        let mut sffb_file = Vec::new();
+        write(&d, Codec::Zstd, &mut sffb_file).unwrap();
+
+        // The blob section isn't needed for the text
+        let text_len = sffb_file.len() - 22;
+        let section = read_text(&mut &sffb_file[..text_len]).unwrap();
+        assert_eq!(section.document.balloons[2].tl_content, vec!["tl 2"]);
+        assert!(section.document.balloons[0].balloon_img.is_none());
+        assert_eq!(
+            section.images[2],
+            Some(ImageRef {
+                img_type: "png".to_string(),
+                offset: 10,
+                len: 12,
+            })
+        );
+
+        let mut r = Cursor::new(sffb_file);
+        assert_eq!(section.load_image(&mut r, 1).unwrap(), None);
+        assert_eq!(
+            section.load_image(&mut r, 2).unwrap(),
+            d.balloons[2].balloon_img
+        );
+    }
+
+    #[test]
+    fn sffb_invalid() {
+        assert!(read(&b"SFFZ\x02"[..]).is_err());
+
+        let mut sffb_file = Vec::new();
+        write(&document(), Codec::ZlibFast, &mut sffb_file).unwrap();
+        sffb_file[4] = 9;
+        assert!(read(&*sffb_file).is_err());
+
+        sffb_file[4] = SFFB_VERSION;
+        sffb_file.pop();
+        assert!(read(&*sffb_file).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Sound effects.
+//!
+//! A balloon with [`Sfx`] data is a sound effect drawn on the art instead of a text
+//! balloon. Sound effects are listed in their own section of txt and docx files:
+//!
+//! ```notrust
+//! #SFX
+//!
+//! 3: ドン | don | BOOM | replace
+//! ```
+//!
+//! The number is the balloon's position in the document, so the section can be merged
+//! back into the balloon list when the file is read.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Starts the sound effect section of txt files.
+pub const TXT_SFX_MARKER: &str = "#SFX";
+
+const TXT_SEPARATOR: &str = " | ";
+
+/// How the translation of a sound effect is typeset. Default value is `Replace`.
+///
+/// `Replace`: Original is cleaned and redrawn in the translation\
+/// `Subtitle`: Original is kept, translation is written small next to it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SfxTreatment {
+    #[default]
+    Replace,
+    Subtitle,
+}
+
+impl SfxTreatment {
+    pub const ALL: [Self; 2] = [Self::Replace, Self::Subtitle];
+
+    fn as_txt(&self) -> &'static str {
+        match self {
+            Self::Replace => "replace",
+            Self::Subtitle => "subtitle",
+        }
+    }
+}
+
+/// A sound effect: the onomatopoeia on the page and its translation.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::sfx::Sfx;
+///
+/// let sfx = Sfx::new("ドン", "don", "BOOM");
+/// assert_eq!(sfx.to_string(), "ドン (don) → BOOM");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Sfx {
+    pub original: String,
+    #[serde(default)]
+    pub romanization: String,
+    pub translation: String,
+    #[serde(default)]
+    pub treatment: SfxTreatment,
+}
+
+impl Sfx {
+    pub fn new(
+        original: impl Into<String>,
+        romanization: impl Into<String>,
+        translation: impl Into<String>,
+    ) -> Self {
+        Self {
+            original: original.into(),
+            romanization: romanization.into(),
+            translation: translation.into(),
+            treatment: SfxTreatment::default(),
+        }
+    }
+
+    /// Line of the txt sound effect section, without the balloon number.
+    pub fn to_txt(&self) -> String {
+        [
+            self.original.as_str(),
+            self.romanization.as_str(),
+            self.translation.as_str(),
+            self.treatment.as_txt(),
+        ]
+        .join(TXT_SEPARATOR)
+    }
+
+    /// Parses a line of the txt sound effect section into the balloon number
+    /// (starting from 1) and the sound effect.
+    pub fn from_txt(line: &str) -> Option<(usize, Self)> {
+        let (number, rest) = line.split_once(':')?;
+        let number = number.trim().parse().ok()?;
+        Some((number, Self::from_txt_fields(rest)))
+    }
+
+    /// Parses the output of `to_txt`. Missing fields are left empty.
+    pub fn from_txt_fields(fields: &str) -> Self {
+        let mut parts = fields.split(TXT_SEPARATOR.trim()).map(|p| p.trim());
+        Self {
+            original: parts.next().unwrap_or_default().to_string(),
+            romanization: parts.next().unwrap_or_default().to_string(),
+            translation: parts.next().unwrap_or_default().to_string(),
+            treatment: match parts.next() {
+                Some("subtitle") => SfxTreatment::Subtitle,
+                _ => SfxTreatment::Replace,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Sfx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.original)?;
+        if !self.romanization.is_empty() {
+            write!(f, " ({})", self.romanization)?;
+        }
+        write!(f, " → {}", self.translation)
+    }
+}
+
+#[cfg(test)]
+mod sfx_tests {
+    use super::*;
+
+    #[test]
+    fn sfx_txt_line() {
+        let mut sfx = Sfx::new("ゴゴゴ", "gogogo", "RUMBLE");
+        sfx.treatment = SfxTreatment::Subtitle;
+
+        let line = format!("7: {}", sfx.to_txt());
+        assert_eq!(line, "7: ゴゴゴ | gogogo | RUMBLE | subtitle");
+        assert_eq!(Sfx::from_txt(&line), Some((7, sfx)));
+
+        let (_, short) = Sfx::from_txt("2: ドン").unwrap();
+        assert_eq!(short, Sfx::new("ドン", "", ""));
+        assert_eq!(Sfx::from_txt("ドン | don"), None);
+    }
+}
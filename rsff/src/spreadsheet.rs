@@ -0,0 +1,514 @@
+//! Reading and writing balloons as spreadsheets (CSV and XLSX).
+//!
+//! Every balloon is a row. Written files start with a header row naming the columns:
+//!
+//! | index | page | type | source | tl   | pr    | comments | status     |
+//! |-------|------|------|--------|------|-------|----------|------------|
+//! | 1     | 1    | ()   | おい!  | Hey! | Hey!! | Loud     | Translated |
+//!
+//! Lines of a balloon are kept in a single cell, separated by line breaks.
+//! Types are written with their prefixes. Custom types are declared by rows of the
+//! `#TYPE` type before the balloons, with the `<prefix> <color> <name>` part of the txt
+//! declaration in the tl cell. Sound effects have the `#SFX` type, with their fields in
+//! the tl cell like the txt sound effect section.
+
+use std::fmt;
+use std::io::{Cursor, Read, Seek, Write};
+use std::str::FromStr;
+
+use calamine::Reader;
+
+use crate::balloon::Balloon;
+use crate::consts::{BalloonStatus, CustomType, TYPES};
+use crate::sfx::{Sfx, TXT_SFX_MARKER};
+use crate::{Document, Result, TXT_TYPE_MARKER};
+
+/// A balloon field a spreadsheet column can hold.
+///
+/// `Index`: Position of the balloon in the document, starting from 1\
+/// `Page`: Page of the balloon, starting from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SheetField {
+    Index,
+    Page,
+    Type,
+    Source,
+    Tl,
+    Pr,
+    Comments,
+    Status,
+}
+
+impl SheetField {
+    pub const ALL: [Self; 8] = [
+        Self::Index,
+        Self::Page,
+        Self::Type,
+        Self::Source,
+        Self::Tl,
+        Self::Pr,
+        Self::Comments,
+        Self::Status,
+    ];
+
+    /// Name of the field in header rows and column lists.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Index => "index",
+            Self::Page => "page",
+            Self::Type => "type",
+            Self::Source => "source",
+            Self::Tl => "tl",
+            Self::Pr => "pr",
+            Self::Comments => "comments",
+            Self::Status => "status",
+        }
+    }
+
+    // Header cells are matched case insensitively, with a few common names.
+    fn from_header(cell: &str) -> Option<Self> {
+        match cell.trim().to_lowercase().as_str() {
+            "#" | "no" | "number" => Some(Self::Index),
+            "pg" => Some(Self::Page),
+            "raw" | "original" => Some(Self::Source),
+            "text" | "translation" => Some(Self::Tl),
+            "proofread" | "proofreading" => Some(Self::Pr),
+            "comment" | "note" | "notes" => Some(Self::Comments),
+            name => Self::ALL.into_iter().find(|f| f.name() == name),
+        }
+    }
+}
+
+/// Which field every column of a spreadsheet holds, `None` for ignored columns.
+///
+/// Written as a comma separated list of field names, with `-` for ignored columns.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::spreadsheet::{SheetColumns, SheetField};
+///
+/// let columns: SheetColumns = "page, -, tl, pr".parse().unwrap();
+/// assert_eq!(columns.0[0], Some(SheetField::Page));
+/// assert_eq!(columns.0[1], None);
+/// assert_eq!(columns.to_string(), "page, -, tl, pr");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SheetColumns(pub Vec<Option<SheetField>>);
+
+impl Default for SheetColumns {
+    /// Every field, in the order of `SheetField::ALL`.
+    fn default() -> Self {
+        Self(SheetField::ALL.into_iter().map(Some).collect())
+    }
+}
+
+impl SheetColumns {
+    /// Columns named by a header row. `None` if a cell of the row isn't a field name
+    /// or a field is named twice.
+    pub fn from_header(cells: &[String]) -> Option<Self> {
+        let mut columns = Vec::with_capacity(cells.len());
+        for cell in cells {
+            if cell.trim().is_empty() {
+                columns.push(None);
+                continue;
+            }
+            let field = SheetField::from_header(cell)?;
+            if columns.contains(&Some(field)) {
+                return None;
+            }
+            columns.push(Some(field));
+        }
+        columns.iter().any(Option::is_some).then_some(Self(columns))
+    }
+
+    // Cell of a field as it is, `None` if it's blank.
+    fn get<'a>(&self, row: &'a [String], field: SheetField) -> Option<&'a str> {
+        let i = self.0.iter().position(|f| *f == Some(field))?;
+        row.get(i)
+            .map(|c| c.as_str())
+            .filter(|c| !c.trim().is_empty())
+    }
+
+    // Cell of a field holding a number or a name, without surrounding whitespace.
+    fn key<'a>(&self, row: &'a [String], field: SheetField) -> Option<&'a str> {
+        self.get(row, field).map(str::trim)
+    }
+
+    fn lines(&self, row: &[String], field: SheetField) -> Vec<String> {
+        self.get(row, field)
+            .map(|c| c.lines().map(String::from).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl FromStr for SheetColumns {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut columns = Vec::new();
+        for name in s.split(',').map(|n| n.trim()) {
+            let field = match name {
+                "-" | "" => None,
+                name => Some(
+                    SheetField::ALL
+                        .into_iter()
+                        .find(|f| f.name() == name)
+                        .ok_or_else(|| format!("Unknown column: {}", name))?,
+                ),
+            };
+            if field.is_some() && columns.contains(&field) {
+                return Err(format!("Column {} is given twice", name));
+            }
+            columns.push(field);
+        }
+        Ok(Self(columns))
+    }
+}
+
+impl fmt::Display for SheetColumns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Vec::from_iter(self.0.iter().map(|c| c.map_or("-", |c| c.name())));
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// Options of the spreadsheet importer.
+///
+/// `columns`: Column mapping of sheets without a header row.\
+/// `detect_header`: Sheets starting with a row of field names use those columns instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SheetImportOptions {
+    pub columns: SheetColumns,
+    pub detect_header: bool,
+}
+
+impl Default for SheetImportOptions {
+    fn default() -> Self {
+        Self {
+            columns: SheetColumns::default(),
+            detect_header: true,
+        }
+    }
+}
+
+fn status_name(status: BalloonStatus) -> String {
+    format!("{:?}", status)
+}
+
+fn parse_status(name: &str) -> BalloonStatus {
+    BalloonStatus::ALL
+        .into_iter()
+        .find(|s| status_name(*s).eq_ignore_ascii_case(name))
+        .unwrap_or_default()
+}
+
+/// Rows of a document: a header row with every field, a row for every custom type,
+/// then a row for every balloon.
+pub fn doc_to_rows(d: &Document) -> Vec<Vec<String>> {
+    let mut rows = vec![Vec::from_iter(
+        SheetField::ALL.iter().map(|f| f.name().to_string()),
+    )];
+
+    for t in &d.custom_types {
+        rows.push(
+            SheetField::ALL
+                .iter()
+                .map(|f| match f {
+                    SheetField::Type => TXT_TYPE_MARKER.to_string(),
+                    SheetField::Tl => format!("{} {} {}", t.prefix, t.color, t.name),
+                    _ => String::new(),
+                })
+                .collect(),
+        );
+    }
+
+    for (i, b) in d.balloons.iter().enumerate() {
+        let (btype, tl) = match &b.sfx {
+            Some(sfx) => (TXT_SFX_MARKER.to_string(), sfx.to_txt()),
+            None => (d.type_prefix(&b.btype), b.tl_content.join("\n")),
+        };
+        rows.push(
+            SheetField::ALL
+                .iter()
+                .map(|f| match f {
+                    SheetField::Index => (i + 1).to_string(),
+                    SheetField::Page => b.page.map(|p| (p + 1).to_string()).unwrap_or_default(),
+                    SheetField::Type => btype.clone(),
+                    SheetField::Source => b.source_content.join("\n"),
+                    SheetField::Tl => tl.clone(),
+                    SheetField::Pr => b.pr_content.join("\n"),
+                    SheetField::Comments => b.comments.join("\n"),
+                    SheetField::Status => status_name(b.status),
+                })
+                .collect(),
+        );
+    }
+    rows
+}
+
+/// Builds a document from spreadsheet rows.
+///
+/// Types are read by prefix or name, unknown types become custom types of the document
+/// with the default color. When there is an index column,
+/// balloons are sorted by it, rows without an index go to the end.
+pub fn rows_to_doc(rows: Vec<Vec<String>>, options: &SheetImportOptions) -> Result<Document> {
+    let mut rows = rows.into_iter().peekable();
+    let header = rows
+        .peek()
+        .filter(|_| options.detect_header)
+        .and_then(|row| SheetColumns::from_header(row));
+    let columns = match header {
+        Some(columns) => {
+            rows.next();
+            columns
+        }
+        None => options.columns.clone(),
+    };
+
+    let mut d = Document::default();
+    let mut indexed = Vec::new();
+    for row in rows {
+        if SheetField::ALL
+            .iter()
+            .all(|f| columns.get(&row, *f).is_none())
+        {
+            continue;
+        }
+        if columns.key(&row, SheetField::Type) == Some(TXT_TYPE_MARKER) {
+            let decl = columns.get(&row, SheetField::Tl).unwrap_or_default();
+            d.add_custom_type(Document::parse_txt_type(decl)?)?;
+            continue;
+        }
+
+        let mut b = Balloon {
+            source_content: columns.lines(&row, SheetField::Source),
+            tl_content: columns.lines(&row, SheetField::Tl),
+            pr_content: columns.lines(&row, SheetField::Pr),
+            comments: columns.lines(&row, SheetField::Comments),
+            status: columns
+                .key(&row, SheetField::Status)
+                .map(parse_status)
+                .unwrap_or_default(),
+            // Pages are numbered from 1 in spreadsheets
+            page: columns
+                .key(&row, SheetField::Page)
+                .and_then(|p| p.parse::<usize>().ok())
+                .and_then(|p| p.checked_sub(1)),
+            ..Default::default()
+        };
+
+        match columns.key(&row, SheetField::Type) {
+            Some(TXT_SFX_MARKER) => {
+                b.sfx = Some(Sfx::from_txt_fields(&b.tl_content.join(" ")));
+                b.tl_content.clear();
+            }
+            Some(label) => b.btype = sheet_type(&mut d, label),
+            None => {}
+        }
+
+        let index = columns
+            .key(&row, SheetField::Index)
+            .and_then(|i| i.parse::<usize>().ok())
+            .unwrap_or(usize::MAX);
+        indexed.push((index, b));
+    }
+
+    indexed.sort_by_key(|(i, _)| *i);
+    d.balloons = indexed.into_iter().map(|(_, b)| b).collect();
+    Ok(d)
+}
+
+// Type of a type cell, unknown labels are added as custom types.
+fn sheet_type(d: &mut Document, label: &str) -> TYPES {
+    if let Some(t) = d.type_from_label(label) {
+        return t;
+    }
+    let prefix = String::from_iter(label.chars().filter(|c| !c.is_whitespace() && *c != ':'));
+    match d.add_custom_type(CustomType::new(label, prefix, Default::default())) {
+        Ok(_) => TYPES::CUSTOM(label.to_string()),
+        Err(_) => TYPES::default(),
+    }
+}
+
+/// Writes a document as CSV.
+pub fn write_csv<W: Write>(d: &Document, w: W) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(w);
+    for row in doc_to_rows(d) {
+        writer.write_record(&row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a document from CSV. Rows may have different lengths.
+pub fn read_csv<R: Read>(r: R, options: &SheetImportOptions) -> Result<Document> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(r);
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        rows.push(record?.iter().map(String::from).collect());
+    }
+    rows_to_doc(rows, options)
+}
+
+/// Writes a document as an XLSX workbook with a single sheet.
+pub fn write_xlsx(d: &Document) -> Result<Vec<u8>> {
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    let sheet = workbook.add_worksheet();
+    let header_format = rust_xlsxwriter::Format::new().set_bold();
+    let cell_format = rust_xlsxwriter::Format::new()
+        .set_text_wrap()
+        .set_align(rust_xlsxwriter::FormatAlign::Top);
+
+    for (r, row) in doc_to_rows(d).iter().enumerate() {
+        for (c, (cell, field)) in row.iter().zip(SheetField::ALL).enumerate() {
+            let (r, c) = (r as u32, c as u16);
+            // Only numbers are numbers, texts like "007" must be kept as they are
+            let number = matches!(field, SheetField::Index | SheetField::Page)
+                .then(|| cell.parse::<u32>().ok())
+                .flatten();
+            if r == 0 {
+                sheet.write_string_with_format(r, c, cell, &header_format)?;
+            } else if let Some(n) = number {
+                sheet.write_number_with_format(r, c, n, &cell_format)?;
+            } else {
+                sheet.write_string_with_format(r, c, cell, &cell_format)?;
+            }
+        }
+    }
+    for field in [
+        SheetField::Source,
+        SheetField::Tl,
+        SheetField::Pr,
+        SheetField::Comments,
+    ] {
+        let c = SheetField::ALL
+            .iter()
+            .position(|f| *f == field)
+            .unwrap_or_default();
+        sheet.set_column_width(c as u16, 40)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+/// Reads a document from the first sheet of an XLSX workbook.
+pub fn read_xlsx<R: Read + Seek>(r: R, options: &SheetImportOptions) -> Result<Document> {
+    let mut workbook = calamine::Xlsx::new(r)?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or("Workbook has no sheets")??;
+
+    // The range starts at the first used cell, empty leading columns are put back
+    let (_, first_col) = range.start().unwrap_or_default();
+    let rows = range
+        .rows()
+        .map(|row| {
+            std::iter::repeat_n(String::new(), first_col as usize)
+                .chain(row.iter().map(|c| c.to_string()))
+                .collect()
+        })
+        .collect();
+    rows_to_doc(rows, options)
+}
+
+/// Reads a document from XLSX bytes.
+pub fn read_xlsx_bytes(bytes: Vec<u8>, options: &SheetImportOptions) -> Result<Document> {
+    read_xlsx(Cursor::new(bytes), options)
+}
+
+#[cfg(test)]
+mod spreadsheet_tests {
+    use super::*;
+    use crate::test_util::sample_document;
+
+    fn assert_same_balloons(read: &Document, d: &Document) {
+        assert_eq!(read.custom_types, d.custom_types);
+        assert_eq!(read.len(), d.len());
+        for (read, b) in read.balloons.iter().zip(&d.balloons) {
+            assert_eq!(read.btype, b.btype);
+            assert_eq!(read.page, b.page);
+            assert_eq!(read.source_content, b.source_content);
+            assert_eq!(read.tl_content, b.tl_content);
+            assert_eq!(read.pr_content, b.pr_content);
+            assert_eq!(read.comments, b.comments);
+            assert_eq!(read.status, b.status);
+            assert_eq!(read.sfx, b.sfx);
+        }
+    }
+
+    #[test]
+    fn spreadsheet_columns() {
+        let columns: SheetColumns = "index, -, tl".parse().unwrap();
+        assert_eq!(
+            columns.0,
+            vec![Some(SheetField::Index), None, Some(SheetField::Tl)]
+        );
+        assert!("tl, tl".parse::<SheetColumns>().is_err());
+        assert!("tl, raw".parse::<SheetColumns>().is_err());
+
+        let header = ["No", "", "Translation", "Notes"].map(String::from);
+        let columns = SheetColumns::from_header(&header).unwrap();
+        assert_eq!(columns.to_string(), "index, -, tl, comments");
+        assert!(SheetColumns::from_header(&["1", "Hey!"].map(String::from)).is_none());
+    }
+
+    #[test]
+    fn spreadsheet_csv_round_trip() {
+        let d = sample_document();
+        let mut csv = Vec::new();
+        write_csv(&d, &mut csv).unwrap();
+
+        let read = read_csv(&*csv, &SheetImportOptions::default()).unwrap();
+        assert_same_balloons(&read, &d);
+        assert_eq!(read.custom_types[0].prefix, "HW");
+        assert!(String::from_utf8(csv).unwrap().contains(",HW,"));
+    }
+
+    #[test]
+    fn spreadsheet_xlsx_round_trip() {
+        let mut d = sample_document();
+        d.add_balloon(Balloon {
+            tl_content: vec!["007".to_string(), "  indented ".to_string()],
+            comments: vec!["1.50".to_string()],
+            ..Default::default()
+        });
+        let xlsx = write_xlsx(&d).unwrap();
+
+        let read = read_xlsx_bytes(xlsx, &SheetImportOptions::default()).unwrap();
+        assert_same_balloons(&read, &d);
+    }
+
+    #[test]
+    fn spreadsheet_type_names() {
+        let csv = "type,tl\nHandwritten,a\n dialogue ,b\nNote,c\n";
+        let d = read_csv(csv.as_bytes(), &SheetImportOptions::default()).unwrap();
+
+        assert_eq!(
+            d.balloons[0].btype,
+            TYPES::CUSTOM("Handwritten".to_string())
+        );
+        assert_eq!(d.balloons[1].btype, TYPES::DIALOGUE);
+        assert_eq!(d.balloons[2].btype, TYPES::CUSTOM("Note".to_string()));
+        assert_eq!(d.custom_types[1].prefix, "Note");
+    }
+
+    #[test]
+    fn spreadsheet_configured_columns() {
+        let csv = "3,,Third\n1,2,First\n,,\n2,1,\"Second\nline\"\n";
+        let options = SheetImportOptions {
+            columns: "index, page, tl".parse().unwrap(),
+            detect_header: true,
+        };
+        let d = read_csv(csv.as_bytes(), &options).unwrap();
+
+        let tl = Vec::from_iter(d.balloons.iter().map(|b| b.tl_content.join("|")));
+        assert_eq!(tl, vec!["First", "Second|line", "Third"]);
+        assert_eq!(d.balloons[0].page, Some(1));
+        assert_eq!(d.balloons[2].page, None);
+    }
+}
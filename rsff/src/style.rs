@@ -0,0 +1,396 @@
+//! Typesetting style of balloons.
+//!
+//! Every style attribute is optional. A balloon's own style is laid over a named
+//! [`StylePreset`] of its document, which is picked by name or by the balloon's type.
+//! Attributes left unset everywhere are up to the typesetter.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::consts::TYPES;
+
+/// Horizontal alignment of the text in a balloon. Default value is `Center`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TextAlignment {
+    Left,
+    #[default]
+    Center,
+    Right,
+    Justify,
+}
+
+impl TextAlignment {
+    pub const ALL: [Self; 4] = [Self::Left, Self::Center, Self::Right, Self::Justify];
+
+    /// Name of the alignment in style attributes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Left => "left",
+            Self::Center => "center",
+            Self::Right => "right",
+            Self::Justify => "justify",
+        }
+    }
+}
+
+/// An RGB color, written as `#rrggbb` in documents.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::style::Color;
+///
+/// let c: Color = "#ff8000".parse().unwrap();
+/// assert_eq!(c, Color::new(255, 128, 0));
+/// assert_eq!(c.to_string(), "#ff8000");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Self = Self::new(0, 0, 0);
+    pub const WHITE: Self = Self::new(255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let hex = s.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(format!("Invalid color: {}", s));
+        }
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("Invalid color: {}", s))
+        };
+        Ok(Self::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(value: Color) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// Outline drawn around the letters. `width` is in pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Stroke {
+    pub color: Color,
+    pub width: u32,
+}
+
+/// Typesetting attributes of a balloon. Unset attributes are inherited.
+///
+/// `font_size`: In points.\
+/// `rotation`: In degrees, clockwise.
+///
+/// In scripts, the set attributes are written as `key=value` pairs separated by `;`,
+/// with the `font`, `size`, `bold`, `italic`, `align`, `color`, `stroke` and `rotation`
+/// keys. A stroke is its color and width: `stroke=#ffffff 3`.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::style::{BalloonStyle, TextAlignment};
+///
+/// let style = BalloonStyle {
+///     bold: Some(true),
+///     alignment: Some(TextAlignment::Left),
+///     ..Default::default()
+/// };
+/// assert!(!style.is_empty());
+/// assert_eq!(style.to_string(), "bold=true; align=left");
+/// assert_eq!("bold=true; align=left".parse::<BalloonStyle>().unwrap(), style);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BalloonStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub italic: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alignment: Option<TextAlignment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stroke: Option<Stroke>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<i32>,
+}
+
+impl BalloonStyle {
+    /// No attribute is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns this style with its unset attributes taken from `base`.
+    pub fn over(&self, base: &BalloonStyle) -> BalloonStyle {
+        BalloonStyle {
+            font_family: self
+                .font_family
+                .clone()
+                .or_else(|| base.font_family.clone()),
+            font_size: self.font_size.or(base.font_size),
+            bold: self.bold.or(base.bold),
+            italic: self.italic.or(base.italic),
+            alignment: self.alignment.or(base.alignment),
+            color: self.color.or(base.color),
+            stroke: self.stroke.or(base.stroke),
+            rotation: self.rotation.or(base.rotation),
+        }
+    }
+}
+
+impl fmt::Display for BalloonStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut attrs = Vec::new();
+        if let Some(font) = &self.font_family {
+            attrs.push(format!("font={}", font));
+        }
+        if let Some(size) = self.font_size {
+            attrs.push(format!("size={}", size));
+        }
+        if let Some(bold) = self.bold {
+            attrs.push(format!("bold={}", bold));
+        }
+        if let Some(italic) = self.italic {
+            attrs.push(format!("italic={}", italic));
+        }
+        if let Some(alignment) = self.alignment {
+            attrs.push(format!("align={}", alignment.name()));
+        }
+        if let Some(color) = self.color {
+            attrs.push(format!("color={}", color));
+        }
+        if let Some(stroke) = self.stroke {
+            attrs.push(format!("stroke={} {}", stroke.color, stroke.width));
+        }
+        if let Some(rotation) = self.rotation {
+            attrs.push(format!("rotation={}", rotation));
+        }
+        write!(f, "{}", attrs.join("; "))
+    }
+}
+
+impl FromStr for BalloonStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        fn value<T: FromStr>(key: &str, value: &str) -> std::result::Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid {} value: {}", key, value))
+        }
+
+        let mut style = Self::default();
+        for attr in s.split(';').map(|a| a.trim()).filter(|a| !a.is_empty()) {
+            let Some((key, v)) = attr.split_once('=') else {
+                return Err(format!("Invalid style attribute: {}", attr));
+            };
+            let (key, v) = (key.trim(), v.trim());
+            match key {
+                "font" => style.font_family = Some(v.to_string()),
+                "size" => style.font_size = Some(value(key, v)?),
+                "bold" => style.bold = Some(value(key, v)?),
+                "italic" => style.italic = Some(value(key, v)?),
+                "align" => {
+                    style.alignment = Some(
+                        TextAlignment::ALL
+                            .into_iter()
+                            .find(|a| a.name().eq_ignore_ascii_case(v))
+                            .ok_or_else(|| format!("Invalid align value: {}", v))?,
+                    )
+                }
+                "color" => style.color = Some(v.parse()?),
+                "stroke" => {
+                    let (color, width) = v
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(|| format!("Invalid stroke value: {}", v))?;
+                    style.stroke = Some(Stroke {
+                        color: color.parse()?,
+                        width: value(key, width.trim())?,
+                    });
+                }
+                "rotation" => style.rotation = Some(value(key, v)?),
+                _ => return Err(format!("Unknown style attribute: {}", key)),
+            }
+        }
+        Ok(style)
+    }
+}
+
+/// A named style of a document.
+/// `types` are the balloon types using this preset when a balloon doesn't pick one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StylePreset {
+    pub name: String,
+    #[serde(default)]
+    pub types: Vec<TYPES>,
+    #[serde(default)]
+    pub style: BalloonStyle,
+}
+
+impl StylePreset {
+    pub fn new(name: impl Into<String>, types: Vec<TYPES>, style: BalloonStyle) -> Self {
+        Self {
+            name: name.into(),
+            types,
+            style,
+        }
+    }
+}
+
+/// Presets of a new document, one for every balloon type.
+pub fn default_presets() -> Vec<StylePreset> {
+    vec![
+        StylePreset::new(
+            "Dialogue",
+            vec![TYPES::DIALOGUE],
+            BalloonStyle {
+                alignment: Some(TextAlignment::Center),
+                ..Default::default()
+            },
+        ),
+        StylePreset::new(
+            "Narration",
+            vec![TYPES::SQUARE],
+            BalloonStyle {
+                alignment: Some(TextAlignment::Center),
+                ..Default::default()
+            },
+        ),
+        StylePreset::new(
+            "Thought",
+            vec![TYPES::THINKING],
+            BalloonStyle {
+                italic: Some(true),
+                alignment: Some(TextAlignment::Center),
+                ..Default::default()
+            },
+        ),
+        StylePreset::new(
+            "Sub-text",
+            vec![TYPES::ST],
+            BalloonStyle {
+                alignment: Some(TextAlignment::Left),
+                ..Default::default()
+            },
+        ),
+        StylePreset::new(
+            "Over-text",
+            vec![TYPES::OT],
+            BalloonStyle {
+                bold: Some(true),
+                stroke: Some(Stroke {
+                    color: Color::WHITE,
+                    width: 3,
+                }),
+                ..Default::default()
+            },
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod style_tests {
+    use super::*;
+
+    #[test]
+    fn style_color_serde() {
+        let stroke = Stroke {
+            color: Color::new(0x12, 0xab, 0xff),
+            width: 2,
+        };
+        let json = serde_json::to_string(&stroke).unwrap();
+        assert_eq!(json, r##"{"color":"#12abff","width":2}"##);
+        assert_eq!(serde_json::from_str::<Stroke>(&json).unwrap(), stroke);
+
+        assert!("#12ab".parse::<Color>().is_err());
+        assert!("#12abzz".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn style_over_base() {
+        let base = BalloonStyle {
+            font_family: Some("CC Wild Words".to_string()),
+            font_size: Some(12),
+            italic: Some(true),
+            ..Default::default()
+        };
+        let own = BalloonStyle {
+            font_size: Some(20),
+            italic: Some(false),
+            ..Default::default()
+        };
+
+        let style = own.over(&base);
+        assert_eq!(style.font_family.as_deref(), Some("CC Wild Words"));
+        assert_eq!(style.font_size, Some(20));
+        assert_eq!(style.italic, Some(false));
+        assert_eq!(style.bold, None);
+        assert!(BalloonStyle::default().is_empty());
+    }
+
+    #[test]
+    fn style_attributes() {
+        let style = BalloonStyle {
+            font_family: Some("CC Wild Words".to_string()),
+            font_size: Some(20),
+            italic: Some(false),
+            alignment: Some(TextAlignment::Justify),
+            color: Some(Color::new(255, 0, 16)),
+            stroke: Some(Stroke {
+                color: Color::WHITE,
+                width: 3,
+            }),
+            rotation: Some(-15),
+            ..Default::default()
+        };
+        let attrs = style.to_string();
+        assert_eq!(
+            attrs,
+            "font=CC Wild Words; size=20; italic=false; align=justify; color=#ff0010; \
+             stroke=#ffffff 3; rotation=-15"
+        );
+        assert_eq!(attrs.parse::<BalloonStyle>().unwrap(), style);
+        assert_eq!(
+            " ".parse::<BalloonStyle>().unwrap(),
+            BalloonStyle::default()
+        );
+
+        assert!("bold".parse::<BalloonStyle>().is_err());
+        assert!("size=big".parse::<BalloonStyle>().is_err());
+        assert!("weight=700".parse::<BalloonStyle>().is_err());
+    }
+}
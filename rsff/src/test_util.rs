@@ -0,0 +1,63 @@
+//! Documents shared by the tests of the importers and exporters.
+
+use crate::balloon::Balloon;
+use crate::consts::{BalloonStatus, CustomType, TYPES};
+use crate::img_data::{crop_image, CropFormat, Region};
+use crate::sfx::Sfx;
+use crate::style::{BalloonStyle, Color, TextAlignment};
+use crate::Document;
+
+/// A document of two pages with a balloon of every kind:
+///
+/// 1. A translated and proofread dialogue on page 1, with a source, comments, a region and an image
+/// 2. A custom type balloon on page 1, with a region
+/// 3. A styled sound effect on page 2, with a region
+/// 4. A translated balloon that isn't placed on a page
+pub(crate) fn sample_document() -> Document {
+    let mut d = Document {
+        METADATA_INFO: "Chapter <12>".to_string(),
+        images: Some(vec![
+            "/scans/001.png".to_string(),
+            "/scans/002.png".to_string(),
+        ]),
+        ..Default::default()
+    };
+    d.add_custom_type(CustomType::new("Handwritten", "HW", Color::new(1, 2, 3)))
+        .unwrap();
+
+    let page = image::DynamicImage::ImageRgb8(image::RgbImage::new(300, 300));
+    d.add_balloon(Balloon {
+        source_content: vec!["おい!".to_string()],
+        tl_content: vec!["Hey!".to_string(), "Say \"hi\" & go".to_string()],
+        pr_content: vec!["Hey!!".to_string(), "[b]Say \"hi\"[/b] & go".to_string()],
+        comments: vec!["Loud | clear".to_string()],
+        status: BalloonStatus::Translated,
+        page: Some(0),
+        region: Some(Region::new(10, 20, 100, 50)),
+        balloon_img: crop_image(&page, &Region::new(0, 0, 300, 150), CropFormat::Png).ok(),
+        ..Default::default()
+    });
+    d.add_balloon(Balloon {
+        btype: TYPES::CUSTOM("Handwritten".to_string()),
+        tl_content: vec!["ね…".to_string()],
+        page: Some(0),
+        region: Some(Region::new(0, 100, 40, 40)),
+        ..Default::default()
+    });
+    d.add_balloon(Balloon {
+        sfx: Some(Sfx::new("ドン", "don", "BOOM")),
+        page: Some(1),
+        region: Some(Region::new(5, 5, 60, 30)),
+        style: BalloonStyle {
+            bold: Some(true),
+            italic: Some(true),
+            alignment: Some(TextAlignment::Left),
+            color: Some(Color::new(255, 0, 16)),
+            rotation: Some(-15),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    d.add_balloon_with_tl("Later");
+    d
+}
@@ -0,0 +1,231 @@
+//! Machine translation of balloon source texts.
+//!
+//! Translation services are plugged in through the [`TranslationProvider`] trait.
+//! [`HttpJsonProvider`] talks to a LibreTranslate compatible HTTP endpoint,
+//! [`FakeProvider`] returns predictable suggestions and is meant for tests.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "http")]
+use serde::{Deserialize, Serialize};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// A machine translation service.
+pub trait TranslationProvider: Send + Sync {
+    /// Translates a batch of source strings.
+    /// Returns exactly one suggestion per source string, in the same order.
+    fn translate(&self, sources: &[String]) -> Result<Vec<String>>;
+}
+
+/// Client of a LibreTranslate style JSON API.
+///
+/// Sends `{"q": [...], "source": "ja", "target": "en", "format": "text"}` to the endpoint
+/// and expects `{"translatedText": [...]}` back.
+///
+/// # Examples
+///
+/// ```
+/// use rsff::translate::HttpJsonProvider;
+///
+/// let provider = HttpJsonProvider::new("http://localhost:5000/translate", "ja", "en");
+/// ```
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpJsonProvider {
+    pub endpoint: String,
+    pub source_lang: String,
+    pub target_lang: String,
+    pub api_key: Option<String>,
+    /// Request timeout, in seconds.
+    pub timeout: u64,
+}
+
+#[cfg(feature = "http")]
+#[derive(Serialize)]
+struct TranslateRequest<'a> {
+    q: &'a [String],
+    source: &'a str,
+    target: &'a str,
+    format: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>,
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TranslatedText {
+    Batch(Vec<String>),
+    Single(String),
+}
+
+#[cfg(feature = "http")]
+#[derive(Deserialize)]
+struct TranslateResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: Option<TranslatedText>,
+    error: Option<String>,
+}
+
+#[cfg(feature = "http")]
+impl HttpJsonProvider {
+    pub fn new(
+        endpoint: impl Into<String>,
+        source_lang: impl Into<String>,
+        target_lang: impl Into<String>,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            source_lang: source_lang.into(),
+            target_lang: target_lang.into(),
+            api_key: None,
+            timeout: 60,
+        }
+    }
+
+    fn request_body(&self, sources: &[String]) -> serde_json::Result<String> {
+        serde_json::to_string(&TranslateRequest {
+            q: sources,
+            source: &self.source_lang,
+            target: &self.target_lang,
+            format: "text",
+            api_key: self.api_key.as_deref().filter(|k| !k.is_empty()),
+        })
+    }
+}
+
+#[cfg(feature = "http")]
+impl TranslationProvider for HttpJsonProvider {
+    fn translate(&self, sources: &[String]) -> Result<Vec<String>> {
+        if sources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let agent = ureq::AgentBuilder::new()
+            .timeout(std::time::Duration::from_secs(self.timeout))
+            .build();
+        let body = match agent
+            .post(&self.endpoint)
+            .set("Content-Type", "application/json")
+            .send_string(&self.request_body(sources)?)
+        {
+            Ok(resp) => resp.into_string()?,
+            // Error responses still carry a JSON body with the reason
+            Err(ureq::Error::Status(code, resp)) => {
+                let body = resp.into_string().unwrap_or_default();
+                return Err(parse_response(&body, sources.len())
+                    .err()
+                    .unwrap_or_else(|| format!("HTTP {}", code).into()));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        parse_response(&body, sources.len())
+    }
+}
+
+#[cfg(feature = "http")]
+fn parse_response(body: &str, expected: usize) -> Result<Vec<String>> {
+    let resp: TranslateResponse = serde_json::from_str(body)?;
+    if let Some(e) = resp.error {
+        return Err(e.into());
+    }
+
+    let suggestions = match resp.translated_text {
+        Some(TranslatedText::Batch(texts)) => texts,
+        Some(TranslatedText::Single(text)) => vec![text],
+        None => return Err("Response has no translatedText".into()),
+    };
+    if suggestions.len() != expected {
+        return Err(format!(
+            "Expected {} translations, got {}",
+            expected,
+            suggestions.len()
+        )
+        .into());
+    }
+
+    Ok(suggestions)
+}
+
+/// Suggests `prefix + source` for every source string and counts the calls.
+#[derive(Debug, Default)]
+pub struct FakeProvider {
+    pub prefix: String,
+    calls: AtomicUsize,
+}
+
+impl FakeProvider {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// How many batches were translated.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+}
+
+impl TranslationProvider for FakeProvider {
+    fn translate(&self, sources: &[String]) -> Result<Vec<String>> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        Ok(sources
+            .iter()
+            .map(|s| format!("{}{}", self.prefix, s))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod translate_tests {
+    use super::*;
+
+    #[test]
+    fn translate_fake_provider() {
+        let provider = FakeProvider::new("EN: ");
+        let res = provider
+            .translate(&["a".to_string(), "b".to_string()])
+            .unwrap();
+
+        assert_eq!(res, vec!["EN: a", "EN: b"]);
+        assert_eq!(provider.calls(), 1);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn translate_parse_response() {
+        assert_eq!(
+            parse_response(r#"{"translatedText": ["hi", "bye"]}"#, 2).unwrap(),
+            vec!["hi", "bye"]
+        );
+        assert_eq!(
+            parse_response(r#"{"translatedText": "hi"}"#, 1).unwrap(),
+            vec!["hi"]
+        );
+        assert!(parse_response(r#"{"translatedText": ["hi"]}"#, 2).is_err());
+
+        let err = parse_response(r#"{"error": "Invalid API key"}"#, 1).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid API key");
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn translate_request_body() {
+        let mut provider = HttpJsonProvider::new("http://localhost:5000/translate", "ja", "en");
+        let body = provider.request_body(&["猫".to_string()]).unwrap();
+        assert_eq!(
+            body,
+            r#"{"q":["猫"],"source":"ja","target":"en","format":"text"}"#
+        );
+
+        provider.api_key = Some("key".to_string());
+        assert!(provider
+            .request_body(&[])
+            .unwrap()
+            .ends_with(r#""api_key":"key"}"#));
+    }
+}
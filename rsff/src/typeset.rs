@@ -0,0 +1,440 @@
+//! Typesetting preview: draws the translated text into the balloons of a page.
+//!
+//! Meant to check whether the text fits before the real typesetting, not to replace it.
+//! Text is wrapped at word boundaries, centered, and drawn with the largest font size
+//! that fits into the balloon's region.
+
+use std::path::Path;
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::balloon::Balloon;
+use crate::img_data::Region;
+use crate::markup;
+use crate::Document;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Options for typesetting.
+///
+/// `padding_ratio`: Space left empty on each side of a region, as a fraction of its size.
+/// Balloons are round, so the corners of their regions are not usable.\
+/// `clear_balloons`: Paint the balloons white before drawing, hiding the original text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypesetOptions {
+    pub max_font_size: f32,
+    pub min_font_size: f32,
+    pub line_spacing: f32,
+    pub padding_ratio: f32,
+    pub color: [u8; 4],
+    pub clear_balloons: bool,
+}
+
+impl Default for TypesetOptions {
+    fn default() -> Self {
+        Self {
+            max_font_size: 42.0,
+            min_font_size: 8.0,
+            line_spacing: 1.15,
+            padding_ratio: 0.15,
+            color: [0, 0, 0, 255],
+            clear_balloons: true,
+        }
+    }
+}
+
+/// Wrapped text and the font size it was fitted with.
+/// `overflows` is true if the text doesn't fit even with the minimum font size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub font_size: f32,
+    pub lines: Vec<String>,
+    pub overflows: bool,
+}
+
+/// Text of a balloon to typeset: proofread content, or translation if there is none.
+/// Sound effects use their translation.
+/// Content lines are kept as line breaks, inline markup is removed.
+pub fn balloon_text(b: &Balloon) -> Option<String> {
+    if let Some(sfx) = &b.sfx {
+        return (!sfx.translation.is_empty()).then(|| sfx.translation.clone());
+    }
+
+    let content = if b.pr_chars() != 0 {
+        &b.pr_content
+    } else {
+        &b.tl_content
+    };
+    let text = content
+        .iter()
+        .map(|l| markup::strip(l))
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (!text.is_empty()).then_some(text)
+}
+
+/// Regions and texts of the balloons placed on a page.
+pub fn page_texts(doc: &Document, page: usize) -> Vec<(Region, String)> {
+    doc.balloons_on_page(page)
+        .filter_map(|(_, b)| Some((b.region?, balloon_text(b)?)))
+        .collect()
+}
+
+/// Wraps text into lines not wider than `max_width`, keeping its line breaks.
+/// Words wider than a line are broken between characters.
+pub fn wrap_text(text: &str, max_width: f32, measure: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if measure(&candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if measure(&line) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Finds the largest font size (in steps of 0.5) whose wrapped text fits into the box.
+///
+/// `measure(text, size)` returns the width of a line, `line_height(size)` the height of a line.
+pub fn fit_text(
+    text: &str,
+    width: f32,
+    height: f32,
+    opts: &TypesetOptions,
+    measure: impl Fn(&str, f32) -> f32,
+    line_height: impl Fn(f32) -> f32,
+) -> TextLayout {
+    // Words are only broken if nothing else fits
+    let layout_at = |size: f32| {
+        let lines = wrap_text(text, width, |s| measure(s, size));
+        let fits = lines.len() as f32 * line_height(size) <= height
+            && text.split_whitespace().all(|w| measure(w, size) <= width);
+        (lines, fits)
+    };
+
+    // Sizes are searched as multiples of 0.5
+    let (mut lo, mut hi) = (
+        (opts.min_font_size * 2.0).ceil() as u32,
+        (opts.max_font_size * 2.0).floor() as u32,
+    );
+    let mut best = None;
+    while lo <= hi {
+        let mid = (lo + hi) / 2;
+        let (lines, fits) = layout_at(mid as f32 / 2.0);
+        if fits {
+            best = Some((mid, lines));
+            lo = mid + 1;
+        } else if mid == 0 {
+            break;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    match best {
+        Some((size, lines)) => TextLayout {
+            font_size: size as f32 / 2.0,
+            lines,
+            overflows: false,
+        },
+        None => TextLayout {
+            font_size: opts.min_font_size,
+            lines: layout_at(opts.min_font_size).0,
+            overflows: true,
+        },
+    }
+}
+
+/// Draws text with a TTF/OTF font.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rsff::typeset::{Typesetter, TypesetOptions};
+/// use rsff::Document;
+///
+/// let doc = Document::open("chapter.sffx").unwrap();
+/// let ts = Typesetter::from_file("CCWildWords.ttf").unwrap();
+/// let page = ts.typeset_page(&doc, 0, &TypesetOptions::default()).unwrap();
+/// page.save("page_0_preview.png").unwrap();
+/// ```
+pub struct Typesetter {
+    font: FontVec,
+}
+
+impl Typesetter {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Ok(Self {
+            font: FontVec::try_from_vec(data)?,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Width of a single line of text, in pixels.
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        let mut width = 0.0;
+        let mut prev = None;
+        for c in text.chars() {
+            let id = font.glyph_id(c);
+            if let Some(prev) = prev {
+                width += font.kern(prev, id);
+            }
+            width += font.h_advance(id);
+            prev = Some(id);
+        }
+        width
+    }
+
+    fn line_height(&self, size: f32, opts: &TypesetOptions) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size));
+        (font.ascent() - font.descent()) * opts.line_spacing
+    }
+
+    /// Fits text into a region. See [`fit_text`].
+    pub fn layout(&self, text: &str, region: &Region, opts: &TypesetOptions) -> TextLayout {
+        let inner = inner_box(region, opts);
+        fit_text(
+            text,
+            inner.width as f32,
+            inner.height as f32,
+            opts,
+            |s, size| self.measure(s, size),
+            |size| self.line_height(size, opts),
+        )
+    }
+
+    /// Draws text centered into a region of an image. Nothing is drawn outside the region.
+    pub fn draw_text(
+        &self,
+        img: &mut RgbaImage,
+        text: &str,
+        region: &Region,
+        opts: &TypesetOptions,
+    ) {
+        let layout = self.layout(text, region, opts);
+        let inner = inner_box(region, opts);
+        let font = self.font.as_scaled(PxScale::from(layout.font_size));
+        let line_height = self.line_height(layout.font_size, opts);
+        let top = inner.y as f32
+            + (inner.height as f32 - line_height * layout.lines.len() as f32).max(0.0) / 2.0;
+        let color = Rgba(opts.color);
+
+        for (i, line) in layout.lines.iter().enumerate() {
+            let x =
+                inner.x as f32 + (inner.width as f32 - self.measure(line, layout.font_size)) / 2.0;
+            let mut caret = point(x, top + line_height * i as f32 + font.ascent());
+            let mut prev = None;
+
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(prev) = prev {
+                    caret.x += font.kern(prev, id);
+                }
+                let glyph = id.with_scale_and_position(layout.font_size, caret);
+                caret.x += font.h_advance(id);
+                prev = Some(id);
+
+                let Some(outlined) = self.font.outline_glyph(glyph) else {
+                    continue;
+                };
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    let x = bounds.min.x as i64 + gx as i64;
+                    let y = bounds.min.y as i64 + gy as i64;
+                    if x < 0 || y < 0 || !region.contains(x as u32, y as u32) {
+                        return;
+                    }
+                    if let Some(px) = img.get_pixel_mut_checked(x as u32, y as u32) {
+                        blend(px, color, coverage);
+                    }
+                });
+            }
+        }
+    }
+
+    /// Draws the texts into their regions on a copy of the page.
+    pub fn render_page(
+        &self,
+        page: &DynamicImage,
+        texts: &[(Region, String)],
+        opts: &TypesetOptions,
+    ) -> RgbaImage {
+        let mut img = page.to_rgba8();
+        for (region, text) in texts {
+            if opts.clear_balloons {
+                clear_ellipse(&mut img, region);
+            }
+            self.draw_text(&mut img, text, region, opts);
+        }
+        img
+    }
+
+    /// Draws the texts into their regions on a page file.
+    pub fn render_page_file<P: AsRef<Path>>(
+        &self,
+        page_path: P,
+        texts: &[(Region, String)],
+        opts: &TypesetOptions,
+    ) -> Result<RgbaImage> {
+        Ok(self.render_page(&image::open(page_path)?, texts, opts))
+    }
+
+    /// Renders a page of a document with its balloons typeset.
+    /// `page` is the index of the page in `Document.images`.
+    pub fn typeset_page(
+        &self,
+        doc: &Document,
+        page: usize,
+        opts: &TypesetOptions,
+    ) -> Result<RgbaImage> {
+        let path = doc
+            .images
+            .as_ref()
+            .and_then(|imgs| imgs.get(page))
+            .ok_or_else(|| format!("Document has no page {}", page))?;
+
+        self.render_page_file(path, &page_texts(doc, page), opts)
+    }
+}
+
+fn inner_box(region: &Region, opts: &TypesetOptions) -> Region {
+    let px = (region.width as f32 * opts.padding_ratio) as u32;
+    let py = (region.height as f32 * opts.padding_ratio) as u32;
+    Region::new(
+        region.x.saturating_add(px),
+        region.y.saturating_add(py),
+        region.width.saturating_sub(px * 2).max(1),
+        region.height.saturating_sub(py * 2).max(1),
+    )
+}
+
+fn blend(px: &mut Rgba<u8>, color: Rgba<u8>, coverage: f32) {
+    let a = coverage.clamp(0.0, 1.0) * color.0[3] as f32 / 255.0;
+    for c in 0..3 {
+        px.0[c] = (px.0[c] as f32 * (1.0 - a) + color.0[c] as f32 * a).round() as u8;
+    }
+}
+
+// Paints the ellipse inscribed in a region white, slightly inset to spare the balloon's outline.
+fn clear_ellipse(img: &mut RgbaImage, region: &Region) {
+    let rx = region.width as f32 / 2.0 * 0.92;
+    let ry = region.height as f32 / 2.0 * 0.92;
+    let cx = region.x as f32 + region.width as f32 / 2.0;
+    let cy = region.y as f32 + region.height as f32 / 2.0;
+
+    for y in region.y..region.bottom().min(img.height()) {
+        for x in region.x..region.right().min(img.width()) {
+            let dx = (x as f32 + 0.5 - cx) / rx;
+            let dy = (y as f32 + 0.5 - cy) / ry;
+            if dx * dx + dy * dy <= 1.0 {
+                img.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod typeset_tests {
+    use super::*;
+
+    // Every character is `size / 2` wide
+    fn mono(s: &str, size: f32) -> f32 {
+        s.chars().count() as f32 * size / 2.0
+    }
+
+    #[test]
+    fn typeset_balloon_text() {
+        let mut b = Balloon::default();
+        assert_eq!(balloon_text(&b), None);
+
+        b.add_tl("Hello");
+        b.add_tl("there");
+        assert_eq!(balloon_text(&b).unwrap(), "Hello\nthere");
+
+        b.add_pr("Hi!");
+        assert_eq!(balloon_text(&b).unwrap(), "Hi!");
+    }
+
+    #[test]
+    fn typeset_wrap_text() {
+        let lines = wrap_text("the quick brown fox\njumps", 50.0, |s| mono(s, 10.0));
+        assert_eq!(lines, vec!["the quick", "brown fox", "jumps"]);
+
+        // Longer than a line, broken between characters
+        let lines = wrap_text("AAAAAAAAAAAAA", 50.0, |s| mono(s, 10.0));
+        assert_eq!(lines, vec!["AAAAAAAAAA", "AAA"]);
+    }
+
+    #[test]
+    fn typeset_fit_text() {
+        let opts = TypesetOptions::default();
+
+        // A 10 character word in 100px: at most 10px wide characters, so size 20
+        let layout = fit_text("HelloWorld", 100.0, 100.0, &opts, mono, |s| s);
+        assert_eq!(layout.font_size, 20.0);
+        assert_eq!(layout.lines, vec!["HelloWorld"]);
+
+        // Two short lines fit better than one long line
+        let layout = fit_text("Hello World", 60.0, 100.0, &opts, mono, |s| s);
+        assert_eq!(layout.lines, vec!["Hello", "World"]);
+        assert_eq!(layout.font_size, 24.0);
+
+        let layout = fit_text("Way too long for this", 10.0, 10.0, &opts, mono, |s| s);
+        assert!(layout.overflows);
+        assert_eq!(layout.font_size, opts.min_font_size);
+    }
+
+    #[test]
+    fn typeset_render_page() {
+        // Uses a system font if there is one, glyph drawing can't be checked without a font.
+        let Ok(ts) = Typesetter::from_file("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf")
+        else {
+            return;
+        };
+        let page =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(200, 200, Rgba([128, 128, 128, 255])));
+        let region = Region::new(50, 50, 100, 60);
+        let img = ts.render_page(
+            &page,
+            &[(region, "Hello there".to_string())],
+            &TypesetOptions::default(),
+        );
+
+        let dark_inside = (region.y..region.y + region.height)
+            .flat_map(|y| (region.x..region.x + region.width).map(move |x| (x, y)))
+            .filter(|(x, y)| img.get_pixel(*x, *y).0[0] < 100)
+            .count();
+        assert!(dark_inside > 0);
+        assert_eq!(img.get_pixel(10, 10).0, [128, 128, 128, 255]);
+    }
+}
@@ -0,0 +1,325 @@
+//! Photoshop and GIMP scripts that start the typesetting.
+//!
+//! The scripts open every page and add a text layer for each placed balloon, with the text
+//! of [`typeset::balloon_text`](crate::typeset::balloon_text) in the balloon's region and
+//! its style. Strokes are left to the typesetter.
+//!
+//! The output only depends on the document, so the same document always gives the same script.
+
+use crate::img_data::Region;
+use crate::sfx::TXT_SFX_MARKER;
+use crate::style::{BalloonStyle, Color, TextAlignment};
+use crate::typeset::balloon_text;
+use crate::Document;
+
+/// Font size of balloons without one in their style, in points.
+pub const DEFAULT_FONT_SIZE: u32 = 24;
+/// Font of balloons without one in their style, for GIMP.
+/// Photoshop keeps its current font instead.
+pub const DEFAULT_GIMP_FONT: &str = "Sans-serif";
+
+const JSX_HEADER: &str = "\
+// Photoshop typesetting script exported by rsff.
+// Run it from File > Scripts > Browse... Every page is opened with a text layer
+// for each placed balloon. Fonts are PostScript names, strokes are left to the typesetter.
+#target photoshop
+
+app.preferences.rulerUnits = Units.PIXELS;
+app.preferences.typeUnits = TypeUnits.POINTS;
+
+function addBalloon(doc, name, text, bounds, style) {
+    var layer = doc.artLayers.add();
+    layer.kind = LayerKind.TEXT;
+    layer.name = name;
+    var item = layer.textItem;
+    item.kind = TextType.PARAGRAPHTEXT;
+    item.contents = text;
+    item.position = [UnitValue(bounds[0], \"px\"), UnitValue(bounds[1], \"px\")];
+    item.width = UnitValue(bounds[2], \"px\");
+    item.height = UnitValue(bounds[3], \"px\");
+    if (style.font !== null) item.font = style.font;
+    item.size = style.size;
+    item.fauxBold = style.bold;
+    item.fauxItalic = style.italic;
+    item.justification = style.justification;
+    var color = new SolidColor();
+    color.rgb.hexValue = style.color;
+    item.color = color;
+    if (style.rotation !== 0) layer.rotate(style.rotation, AnchorPosition.MIDDLECENTER);
+    return layer;
+}
+";
+
+const GIMP_HEADER: &str = "\
+# GIMP typesetting script exported by rsff, for GIMP 2.10 with Python-Fu.
+# Paste it into Filters > Python-Fu > Console. Every page is opened with a text layer
+# for each placed balloon. Strokes are left to the typesetter.
+import math
+from gimpfu import *
+
+def open_page(path):
+    image = pdb.gimp_file_load(path, path)
+    pdb.gimp_display_new(image)
+    return image
+
+def add_balloon(image, name, text, bounds, font, size, justification, color, rotation):
+    layer = pdb.gimp_text_layer_new(image, text, font, size, UNIT_POINT)
+    pdb.gimp_image_insert_layer(image, layer, None, 0)
+    pdb.gimp_item_set_name(layer, name)
+    pdb.gimp_text_layer_set_justification(layer, justification)
+    pdb.gimp_text_layer_set_color(layer, color)
+    pdb.gimp_text_layer_resize(layer, bounds[2], bounds[3])
+    pdb.gimp_layer_set_offsets(layer, bounds[0], bounds[1])
+    if rotation:
+        pdb.gimp_item_transform_rotate(layer, math.radians(rotation), True, 0, 0)
+    return layer
+";
+
+/// A text layer of a script.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ScriptLayer {
+    pub name: String,
+    pub text: String,
+    pub region: Region,
+    pub style: BalloonStyle,
+}
+
+/// A page of a script, with its layers in balloon order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ScriptPage {
+    pub number: usize,
+    pub path: String,
+    pub layers: Vec<ScriptLayer>,
+}
+
+/// Pages with placed balloons and their layers.
+/// Layers are named after the balloon number and type, like `3 ()`.
+pub(crate) fn script_pages(d: &Document) -> Vec<ScriptPage> {
+    let Some(images) = &d.images else {
+        return Vec::new();
+    };
+
+    images
+        .iter()
+        .enumerate()
+        .map(|(page, path)| ScriptPage {
+            number: page + 1,
+            path: path.clone(),
+            layers: d
+                .balloons_on_page(page)
+                .filter_map(|(i, b)| {
+                    let btype = match b.sfx {
+                        Some(_) => TXT_SFX_MARKER.to_string(),
+                        None => d.type_prefix(&b.btype),
+                    };
+                    Some(ScriptLayer {
+                        name: format!("{} {}", i + 1, btype),
+                        text: balloon_text(b)?,
+                        region: b.region?,
+                        style: d.balloon_style(b),
+                    })
+                })
+                .collect(),
+        })
+        .filter(|p| !p.layers.is_empty())
+        .collect()
+}
+
+// Non-ASCII characters are escaped so the scripts don't depend on the file encoding.
+fn quote(s: &str, newline: &str, escape_char: impl Fn(char) -> String) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str(newline),
+            ' '..='~' => quoted.push(c),
+            c => quoted.push_str(&escape_char(c)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn js_string(s: &str) -> String {
+    // Photoshop breaks lines of text layers at carriage returns
+    quote(s, "\\r", |c| {
+        let mut units = [0; 2];
+        c.encode_utf16(&mut units)
+            .iter()
+            .map(|u| format!("\\u{:04x}", u))
+            .collect()
+    })
+}
+
+fn py_string(s: &str) -> String {
+    let quoted = quote(s, "\\n", |c| match c as u32 {
+        c @ 0..=0xffff => format!("\\u{:04x}", c),
+        c => format!("\\U{:08x}", c),
+    });
+    format!("u{}", quoted)
+}
+
+fn hex(c: Color) -> String {
+    format!("{:02x}{:02x}{:02x}", c.r, c.g, c.b)
+}
+
+/// Writes a Photoshop ExtendScript (`.jsx`) that opens every page with placed balloons
+/// and adds a paragraph text layer for each of them.
+pub fn to_jsx(d: &Document) -> String {
+    let mut jsx = String::from(JSX_HEADER);
+    for page in script_pages(d) {
+        jsx.push_str(&format!(
+            "\n// Page {}\nvar doc = app.open(new File({}));\n",
+            page.number,
+            js_string(&page.path)
+        ));
+        for layer in page.layers {
+            let style = &layer.style;
+            let justification = match style.alignment.unwrap_or_default() {
+                TextAlignment::Left => "LEFT",
+                TextAlignment::Center => "CENTER",
+                TextAlignment::Right => "RIGHT",
+                TextAlignment::Justify => "FULLYJUSTIFIED",
+            };
+            let r = layer.region;
+            jsx.push_str(&format!(
+                "addBalloon(doc, {}, {}, [{}, {}, {}, {}], {{font: {}, size: {}, bold: {}, italic: {}, \
+                 justification: Justification.{}, color: \"{}\", rotation: {}}});\n",
+                js_string(&layer.name),
+                js_string(&layer.text),
+                r.x,
+                r.y,
+                r.width,
+                r.height,
+                style
+                    .font_family
+                    .as_deref()
+                    .map_or("null".to_string(), js_string),
+                style.font_size.unwrap_or(DEFAULT_FONT_SIZE),
+                style.bold.unwrap_or_default(),
+                style.italic.unwrap_or_default(),
+                justification,
+                hex(style.color.unwrap_or(Color::BLACK)),
+                style.rotation.unwrap_or_default(),
+            ));
+        }
+    }
+    jsx
+}
+
+/// Writes a GIMP 2.10 Python-Fu script that opens every page with placed balloons
+/// and adds a text layer for each of them.
+///
+/// GIMP has no faux bold or italic, so bold and italic balloons use the `Bold` and `Italic`
+/// faces of their fonts.
+pub fn to_gimp_script(d: &Document) -> String {
+    let mut py = String::from(GIMP_HEADER);
+    for page in script_pages(d) {
+        py.push_str(&format!(
+            "\n# Page {}\nimage = open_page({})\n",
+            page.number,
+            py_string(&page.path)
+        ));
+        for layer in page.layers {
+            let style = &layer.style;
+            let mut font = style
+                .font_family
+                .clone()
+                .unwrap_or(DEFAULT_GIMP_FONT.to_string());
+            if style.bold.unwrap_or_default() {
+                font.push_str(" Bold");
+            }
+            if style.italic.unwrap_or_default() {
+                font.push_str(" Italic");
+            }
+            let justification = match style.alignment.unwrap_or_default() {
+                TextAlignment::Left => "LEFT",
+                TextAlignment::Center => "CENTER",
+                TextAlignment::Right => "RIGHT",
+                TextAlignment::Justify => "FILL",
+            };
+            let color = style.color.unwrap_or(Color::BLACK);
+            let r = layer.region;
+            py.push_str(&format!(
+                "add_balloon(image, {}, {}, ({}, {}, {}, {}), {}, {}, TEXT_JUSTIFY_{}, ({}, {}, {}), {})\n",
+                py_string(&layer.name),
+                py_string(&layer.text),
+                r.x,
+                r.y,
+                r.width,
+                r.height,
+                py_string(&font),
+                style.font_size.unwrap_or(DEFAULT_FONT_SIZE),
+                justification,
+                color.r,
+                color.g,
+                color.b,
+                style.rotation.unwrap_or_default(),
+            ));
+        }
+    }
+    py
+}
+
+#[cfg(test)]
+mod typeset_script_tests {
+    use super::*;
+    use crate::test_util::sample_document;
+
+    // The sample with a font for dialogues and a Windows path, which must be escaped.
+    fn script_sample() -> Document {
+        let mut d = sample_document();
+        if let Some(images) = d.images.as_mut() {
+            images[1] = "C:\\scans\\002.png".to_string();
+        }
+        let dialogue = d.style_presets.iter_mut().find(|p| p.name == "Dialogue");
+        let dialogue = &mut dialogue.unwrap().style;
+        dialogue.font_family = Some("CC Wild Words".to_string());
+        dialogue.font_size = Some(18);
+        d
+    }
+
+    #[test]
+    fn typeset_script_jsx() {
+        let jsx = to_jsx(&script_sample());
+
+        // The last balloon isn't placed, so it has no layer. Custom types have no preset.
+        assert!(jsx.starts_with(JSX_HEADER));
+        assert_eq!(
+            &jsx[JSX_HEADER.len()..],
+            r#"
+// Page 1
+var doc = app.open(new File("/scans/001.png"));
+addBalloon(doc, "1 ()", "Hey!!\rSay \"hi\" & go", [10, 20, 100, 50], {font: "CC Wild Words", size: 18, bold: false, italic: false, justification: Justification.CENTER, color: "000000", rotation: 0});
+addBalloon(doc, "2 HW", "\u306d\u2026", [0, 100, 40, 40], {font: null, size: 24, bold: false, italic: false, justification: Justification.CENTER, color: "000000", rotation: 0});
+
+// Page 2
+var doc = app.open(new File("C:\\scans\\002.png"));
+addBalloon(doc, "3 #SFX", "BOOM", [5, 5, 60, 30], {font: "CC Wild Words", size: 18, bold: true, italic: true, justification: Justification.LEFT, color: "ff0010", rotation: -15});
+"#
+        );
+    }
+
+    #[test]
+    fn typeset_script_gimp() {
+        let py = to_gimp_script(&script_sample());
+
+        assert!(py.starts_with(GIMP_HEADER));
+        assert_eq!(
+            &py[GIMP_HEADER.len()..],
+            r#"
+# Page 1
+image = open_page(u"/scans/001.png")
+add_balloon(image, u"1 ()", u"Hey!!\nSay \"hi\" & go", (10, 20, 100, 50), u"CC Wild Words", 18, TEXT_JUSTIFY_CENTER, (0, 0, 0), 0)
+add_balloon(image, u"2 HW", u"\u306d\u2026", (0, 100, 40, 40), u"Sans-serif", 24, TEXT_JUSTIFY_CENTER, (0, 0, 0), 0)
+
+# Page 2
+image = open_page(u"C:\\scans\\002.png")
+add_balloon(image, u"3 #SFX", u"BOOM", (5, 5, 60, 30), u"CC Wild Words Bold Italic", 18, TEXT_JUSTIFY_LEFT, (255, 0, 16), -15)
+"#
+        );
+        assert_eq!(py_string("😀"), r#"u"\U0001f600""#);
+        assert_eq!(js_string("😀"), r#""\ud83d\ude00""#);
+    }
+}
@@ -0,0 +1,430 @@
+//! XLIFF 2.0 export and import, for CAT tools.
+//!
+//! Every balloon is a `<unit>` with the id `b<number>`, numbers starting from 1:
+//!
+//! ```notrust
+//! <unit id="b1">
+//!   <mda:metadata>
+//!     <mda:metaGroup category="rsff">
+//!       <mda:meta type="type">()</mda:meta>
+//!       <mda:meta type="page">1</mda:meta>
+//!     </mda:metaGroup>
+//!   </mda:metadata>
+//!   <notes>
+//!     <note category="comment">Loud</note>
+//!   </notes>
+//!   <segment state="translated">
+//!     <source>おい!</source>
+//!     <target>Hey!</target>
+//!   </segment>
+//! </unit>
+//! ```
+//!
+//! The target is the proofread text if the balloon has any, the translation otherwise.
+//! Sound effects have the `#SFX` type and use their original and translation. When a
+//! file is merged back, units are matched to balloons by their ids. Balloons shouldn't
+//! be added, removed or reordered between the export and the merge, units whose source
+//! text or type don't match their balloons anymore are reported instead of merged.
+
+use std::io::Cursor;
+
+use quick_xml::events::{BytesDecl, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+use crate::balloon::Balloon;
+use crate::consts::BalloonStatus;
+use crate::sfx::TXT_SFX_MARKER;
+use crate::{Document, MergeReport, Result};
+
+const XLIFF_NAMESPACE: &str = "urn:oasis:names:tc:xliff:document:2.0";
+const METADATA_NAMESPACE: &str = "urn:oasis:names:tc:xliff:metadata:2.0";
+const META_CATEGORY: &str = "rsff";
+const COMMENT_CATEGORY: &str = "comment";
+
+fn unit_id(index: usize) -> String {
+    format!("b{}", index + 1)
+}
+
+fn balloon_index(id: &str) -> Option<usize> {
+    id.strip_prefix('b')?.parse::<usize>().ok()?.checked_sub(1)
+}
+
+// Type label and source text of a balloon's unit.
+fn unit_source(d: &Document, b: &Balloon) -> (String, String) {
+    match &b.sfx {
+        Some(sfx) => (TXT_SFX_MARKER.to_string(), sfx.original.clone()),
+        None => (d.type_prefix(&b.btype), b.source_content.join("\n")),
+    }
+}
+
+// Segment states of XLIFF 2.0.
+fn segment_state(status: BalloonStatus) -> &'static str {
+    match status {
+        BalloonStatus::Pending | BalloonStatus::MachineDraft => "initial",
+        BalloonStatus::Translated => "translated",
+        BalloonStatus::Proofread => "reviewed",
+    }
+}
+
+fn status_of_state(state: &str) -> Option<BalloonStatus> {
+    match state {
+        "translated" => Some(BalloonStatus::Translated),
+        "reviewed" | "final" => Some(BalloonStatus::Proofread),
+        _ => None,
+    }
+}
+
+/// Writes a document as XLIFF 2.0.
+/// `source_lang` and `target_lang` are BCP 47 language tags, like `ja` and `en`.
+pub fn to_xliff(d: &Document, source_lang: &str, target_lang: &str) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    writer
+        .create_element("xliff")
+        .with_attributes([
+            ("xmlns", XLIFF_NAMESPACE),
+            ("xmlns:mda", METADATA_NAMESPACE),
+            ("version", "2.0"),
+            ("srcLang", source_lang),
+            ("trgLang", target_lang),
+        ])
+        .write_inner_content(|w| {
+            w.create_element("file")
+                .with_attributes([("id", "f1"), ("original", d.METADATA_INFO.as_str())])
+                .write_inner_content(|w| {
+                    for (i, b) in d.balloons.iter().enumerate() {
+                        let (btype, source) = unit_source(d, b);
+                        let target = match &b.sfx {
+                            Some(sfx) => sfx.translation.clone(),
+                            None if b.pr_chars() != 0 => b.pr_content.join("\n"),
+                            None => b.tl_content.join("\n"),
+                        };
+
+                        w.create_element("unit")
+                            .with_attribute(("id", unit_id(i).as_str()))
+                            .write_inner_content(|w| {
+                                w.create_element("mda:metadata").write_inner_content(|w| {
+                                    w.create_element("mda:metaGroup")
+                                        .with_attribute(("category", META_CATEGORY))
+                                        .write_inner_content(|w| {
+                                            w.create_element("mda:meta")
+                                                .with_attribute(("type", "type"))
+                                                .write_text_content(BytesText::new(&btype))?;
+                                            if let Some(page) = b.page {
+                                                w.create_element("mda:meta")
+                                                    .with_attribute(("type", "page"))
+                                                    .write_text_content(BytesText::new(
+                                                        &(page + 1).to_string(),
+                                                    ))?;
+                                            }
+                                            Ok(())
+                                        })?;
+                                    Ok(())
+                                })?;
+
+                                if !b.comments.is_empty() {
+                                    w.create_element("notes").write_inner_content(|w| {
+                                        for comment in &b.comments {
+                                            w.create_element("note")
+                                                .with_attribute(("category", COMMENT_CATEGORY))
+                                                .write_text_content(BytesText::new(comment))?;
+                                        }
+                                        Ok(())
+                                    })?;
+                                }
+
+                                w.create_element("segment")
+                                    .with_attribute(("state", segment_state(b.status)))
+                                    .write_inner_content(|w| {
+                                        w.create_element("source")
+                                            .write_text_content(BytesText::new(&source))?;
+                                        w.create_element("target")
+                                            .write_text_content(BytesText::new(&target))?;
+                                        Ok(())
+                                    })?;
+                                Ok(())
+                            })?;
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
+
+/// A unit read from an XLIFF file.
+/// `btype` is the balloon type of the rsff metadata, `None` for files of other tools.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XliffUnit {
+    pub id: String,
+    pub btype: Option<String>,
+    pub source: String,
+    pub target: Option<String>,
+    pub state: Option<String>,
+    pub notes: Vec<String>,
+}
+
+/// Reads the units of an XLIFF 2.0 file. Segments of a unit are joined.
+pub fn read_units(xml: &str) -> Result<Vec<XliffUnit>> {
+    let mut reader = Reader::from_str(xml);
+    let mut units = Vec::new();
+    let mut unit: Option<XliffUnit> = None;
+    // Text of the element being read, if it is a source, a target, a type or a comment note
+    let mut text: Option<String> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => start_element(&e, &mut unit, &mut text)?,
+            // Self-closing elements are empty ones, `<target/>` clears a translation
+            Event::Empty(e) => {
+                start_element(&e, &mut unit, &mut text)?;
+                end_element(e.local_name().as_ref(), &mut units, &mut unit, &mut text);
+            }
+            Event::Text(t) => {
+                if let Some(text) = text.as_mut() {
+                    text.push_str(&t.unescape()?);
+                }
+            }
+            Event::CData(t) => {
+                if let Some(text) = text.as_mut() {
+                    text.push_str(&String::from_utf8_lossy(&t));
+                }
+            }
+            Event::End(e) => end_element(e.local_name().as_ref(), &mut units, &mut unit, &mut text),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(units)
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>> {
+    Ok(e.try_get_attribute(name)?
+        .map(|a| a.unescape_value().map(|v| v.to_string()))
+        .transpose()?)
+}
+
+// Starts a unit, or the text of the elements whose text is read.
+fn start_element(
+    e: &BytesStart,
+    unit: &mut Option<XliffUnit>,
+    text: &mut Option<String>,
+) -> Result<()> {
+    match e.local_name().as_ref() {
+        b"unit" => {
+            *unit = Some(XliffUnit {
+                id: attribute(e, "id")?.unwrap_or_default(),
+                ..Default::default()
+            });
+        }
+        b"segment" => {
+            if let (Some(unit), Some(state)) = (unit.as_mut(), attribute(e, "state")?) {
+                unit.state = Some(state);
+            }
+        }
+        b"source" | b"target" => *text = Some(String::new()),
+        b"meta" if attribute(e, "type")?.as_deref() == Some("type") => *text = Some(String::new()),
+        b"note"
+            if attribute(e, "category")?
+                .as_deref()
+                .is_none_or(|c| c == COMMENT_CATEGORY) =>
+        {
+            *text = Some(String::new())
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Ends a unit, or puts the text read into it.
+fn end_element(
+    name: &[u8],
+    units: &mut Vec<XliffUnit>,
+    unit: &mut Option<XliffUnit>,
+    text: &mut Option<String>,
+) {
+    match name {
+        b"unit" => units.extend(unit.take()),
+        b"source" => {
+            if let (Some(unit), Some(t)) = (unit.as_mut(), text.take()) {
+                unit.source.push_str(&t);
+            }
+        }
+        b"meta" => {
+            if let (Some(unit), Some(t)) = (unit.as_mut(), text.take()) {
+                unit.btype = Some(t);
+            }
+        }
+        b"target" => {
+            if let (Some(unit), Some(t)) = (unit.as_mut(), text.take()) {
+                unit.target.get_or_insert_with(String::new).push_str(&t);
+            }
+        }
+        b"note" => {
+            if let (Some(unit), Some(t)) = (unit.as_mut(), text.take()) {
+                unit.notes.push(t);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Merges the targets, states and comment notes of an XLIFF file into a document.
+///
+/// Units are matched to balloons by their ids. A target replaces the proofread text of
+/// balloons having one, and the translation of the rest. Units whose source text or type
+/// isn't the one of their balloon are reported as mismatched and not merged,
+/// units without a matching balloon are skipped.
+pub fn merge_xliff(d: &mut Document, xml: &str) -> Result<MergeReport> {
+    let mut report = MergeReport::default();
+    for unit in read_units(xml)? {
+        let Some(i) = balloon_index(&unit.id).filter(|i| *i < d.balloons.len()) else {
+            report.unmatched += 1;
+            continue;
+        };
+        // Line endings may be changed by CAT tools
+        let (btype, source) = unit_source(d, &d.balloons[i]);
+        if unit.btype.is_some_and(|t| t != btype) || !unit.source.lines().eq(source.lines()) {
+            report.mismatched.push(i);
+            continue;
+        }
+        let b = &mut d.balloons[i];
+
+        if let Some(target) = unit.target {
+            let lines = Vec::from_iter(target.lines().map(String::from));
+            let has_pr = b.pr_chars() != 0;
+            match b.sfx.as_mut() {
+                Some(sfx) => sfx.translation = target,
+                None if has_pr => b.pr_content = lines,
+                None => b.tl_content = lines,
+            }
+        }
+        if let Some(status) = unit.state.as_deref().and_then(status_of_state) {
+            b.status = status;
+        }
+        b.comments = unit.notes;
+        report.merged += 1;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod xliff_tests {
+    use super::*;
+    use crate::test_util::sample_document;
+
+    #[test]
+    fn xliff_export() {
+        let xliff = to_xliff(&sample_document(), "ja", "en").unwrap();
+
+        assert!(xliff.contains(r#"srcLang="ja" trgLang="en""#));
+        assert!(xliff.contains(r#"<file id="f1" original="Chapter &lt;12&gt;">"#));
+        assert!(xliff.contains(r#"<unit id="b1">"#));
+        assert!(xliff.contains(r#"<mda:meta type="page">1</mda:meta>"#));
+        assert!(xliff.contains(r#"<mda:meta type="type">HW</mda:meta>"#));
+        assert!(xliff.contains("<source>おい!</source>"));
+        assert!(xliff.contains("<target>ね…</target>"));
+        assert!(xliff.contains(r#"<note category="comment">Loud | clear</note>"#));
+        assert!(xliff.contains("<source>ドン</source>"));
+
+        let units = read_units(&xliff).unwrap();
+        assert_eq!(units.len(), 4);
+        assert_eq!(
+            units[0].target.as_deref(),
+            Some("Hey!!\n[b]Say \"hi\"[/b] & go")
+        );
+        assert_eq!(units[0].state.as_deref(), Some("translated"));
+        assert_eq!(units[0].notes, vec!["Loud | clear"]);
+        assert_eq!(units[0].source, "おい!");
+        assert_eq!(units[1].btype.as_deref(), Some("HW"));
+        assert_eq!(units[3].state.as_deref(), Some("initial"));
+    }
+
+    #[test]
+    fn xliff_merge() {
+        let mut d = sample_document();
+        let xliff = to_xliff(&d, "ja", "en")
+            .unwrap()
+            .replace("<target>ね…</target>", "<target>ねえ…</target>")
+            .replace("<target>BOOM</target>", "<target>BAM</target>")
+            .replace(
+                r#"<note category="comment">Loud | clear</note>"#,
+                r#"<note category="comment">Louder</note><note category="qa">Length</note>"#,
+            )
+            .replace(r#"<unit id="b1">"#, r#"<unit id="b9">"#);
+
+        // The first unit doesn't match a balloon anymore
+        let report = merge_xliff(&mut d, &xliff).unwrap();
+        assert_eq!(report.merged, 3);
+        assert_eq!(report.unmatched, 1);
+        assert!(report.mismatched.is_empty());
+        assert_eq!(d.balloons[0].comments, vec!["Loud | clear"]);
+        assert_eq!(d.balloons[1].tl_content, vec!["ねえ…"]);
+        assert_eq!(d.balloons[2].sfx.as_ref().unwrap().translation, "BAM");
+
+        let mut d = sample_document();
+        let xliff = to_xliff(&d, "ja", "en")
+            .unwrap()
+            .replace(
+                r#"<segment state="translated">"#,
+                r#"<segment state="final">"#,
+            )
+            .replace("Hey!!\n", "Hey!!!\n")
+            .replace("Loud | clear</note>", "Louder</note>");
+        merge_xliff(&mut d, &xliff).unwrap();
+        assert_eq!(d.balloons[0].status, BalloonStatus::Proofread);
+        assert_eq!(d.balloons[0].comments, vec!["Louder"]);
+        // Proofread balloons get the target as their proofread text
+        assert_eq!(d.balloons[0].tl_content, vec!["Hey!", "Say \"hi\" & go"]);
+        assert_eq!(d.balloons[0].pr_content[0], "Hey!!!");
+
+        // A balloon added before the others shifts the ids, no unit matches its balloon
+        let mut d = sample_document();
+        let xliff = to_xliff(&d, "ja", "en").unwrap();
+        d.balloons.insert(0, Default::default());
+        let report = merge_xliff(&mut d, &xliff).unwrap();
+        assert_eq!(report.merged, 0);
+        assert_eq!(report.mismatched, vec![0, 1, 2, 3]);
+        assert_eq!(d.balloons[1].comments, vec!["Loud | clear"]);
+        assert_eq!(d.balloons[3].sfx.as_ref().unwrap().translation, "BOOM");
+
+        // Changed line endings still match
+        let mut d = sample_document();
+        d.balloons[0].source_content.push("おいおい".to_string());
+        let xliff = to_xliff(&d, "ja", "en")
+            .unwrap()
+            .replace("おい!\n", "おい!\r\n");
+        assert_eq!(merge_xliff(&mut d, &xliff).unwrap().merged, 4);
+    }
+
+    #[test]
+    fn xliff_self_closing_elements() {
+        let mut d = sample_document();
+        let xliff = to_xliff(&d, "ja", "en")
+            .unwrap()
+            .replace("<target>ね…</target>", "<target/>")
+            .replace(
+                r#"<note category="comment">Loud | clear</note>"#,
+                r#"<note category="comment"/>"#,
+            );
+        assert!(xliff.contains(r#"<mda:meta type="type">#SFX</mda:meta>"#));
+
+        let units = read_units(&xliff).unwrap();
+        assert_eq!(units[0].notes, vec![""]);
+        assert_eq!(units[1].target.as_deref(), Some(""));
+
+        let report = merge_xliff(&mut d, &xliff).unwrap();
+        assert_eq!(report.merged, 4);
+        assert!(d.balloons[1].tl_content.is_empty());
+
+        // Balloons without source text are matched by self-closing sources
+        let xml = r#"<xliff><file><unit id="b1"><segment><source/><target>Hi</target></segment></unit></file></xliff>"#;
+        let mut d = Document::default();
+        d.add_balloon_empty();
+        assert_eq!(merge_xliff(&mut d, xml).unwrap().merged, 1);
+        assert_eq!(d.balloons[0].tl_content, vec!["Hi"]);
+    }
+}